@@ -16,6 +16,7 @@ import {
 	Bug,
 	CircleUserIcon,
 	Clapperboard,
+	CloudOff,
 	FileText,
 	Film,
 	Globe,
@@ -40,6 +41,9 @@ export function Header() {
 	const openPreferences = useGlobalStore(state => state.dialogs.preferences.toggle);
 	const openWatchPreferences = useGlobalStore(state => state.dialogs.watchPreferences.toggle);
 	const direction = useGlobalStore(state => state.i18n.direction);
+	const locale = useGlobalStore(state => state.i18n.locale);
+	const online = useGlobalStore(state => state.network.online);
+	const staleSince = useGlobalStore(state => state.network.staleSince);
 
 	const { country } = useCountry();
 	const { t } = useTranslation();
@@ -126,6 +130,25 @@ export function Header() {
 					</nav>
 
 					<div className="flex items-center gap-1 justify-self-end">
+						{(!online || staleSince) && (
+							<Tooltip>
+								<TooltipTrigger asChild>
+									<span data-testid="offline" className="text-muted-foreground p-2">
+										<CloudOff className="h-4 w-4" />
+									</span>
+								</TooltipTrigger>
+								<TooltipContent side="bottom" className="flex flex-col py-1 text-xs">
+									{!online && <span>{t("header.offline")}</span>}
+									{staleSince && (
+										<span>
+											{t("header.stale", {
+												date: new Date(staleSince).toLocaleString(locale),
+											})}
+										</span>
+									)}
+								</TooltipContent>
+							</Tooltip>
+						)}
 						<CommandCenter />
 
 						<Menubar className="z-[200]">
@@ -1,4 +1,5 @@
-import { type Movie, RatingSource, type TVShow, WatchPriceType } from "@popcorntime/graphql/types";
+import type { MediaMedia } from "@popcorntime/graphql/bindings";
+import { RatingSource, WatchPriceType } from "@popcorntime/graphql/types";
 import { type Country, getLocalesForCountry, type Locale } from "@popcorntime/i18n";
 import { Badge } from "@popcorntime/ui/components/badge";
 import { Button, buttonVariants } from "@popcorntime/ui/components/button";
@@ -8,6 +9,7 @@ import { ScrollArea } from "@popcorntime/ui/components/scroll-area";
 import { Spinner } from "@popcorntime/ui/components/spinner";
 import { Table, TableBody, TableCell, TableRow } from "@popcorntime/ui/components/table";
 import { Tabs, TabsContent, TabsList, TabsTrigger } from "@popcorntime/ui/components/tabs";
+import { cachedImageUrl } from "@popcorntime/ui/lib/medias";
 import { timeDisplay } from "@popcorntime/ui/lib/time";
 import { cn } from "@popcorntime/ui/lib/utils";
 import { Calendar, Clock, ExternalLink, Star, X } from "lucide-react";
@@ -35,25 +37,25 @@ function MediaContent() {
 	const toggle = useGlobalStore(state => state.dialogs.media.toggle);
 	const { country } = useCountry();
 	const [isLoading, setIsLoading] = useState(false);
-	const { invoke } = useTauri();
-	const [media, setMedia] = useState<Movie | TVShow | null>(null);
+	const { invokeOperation } = useTauri();
+	const [media, setMedia] = useState<MediaMedia | null>(null);
 	const { t } = useTranslation();
 	const officialLocales = useMemo(() => [...getLocalesForCountry(country)], [country]);
 
 	const fetch = useCallback(
 		async (slug: string) => {
 			setIsLoading(true);
-			const results = await invoke<Movie | TVShow>("media", {
-				params: {
+			const results = await invokeOperation("media", [
+				{
 					country: country.toUpperCase() as Country,
 					slug,
 					language: locale,
 				},
-			});
+			]);
 			setMedia(results);
 			setIsLoading(false);
 		},
-		[country, locale, invoke]
+		[country, locale, invokeOperation]
 	);
 
 	useEffect(() => {
@@ -175,25 +177,12 @@ function MediaContent() {
 								)}
 							>
 								<source
-									srcSet={`https://img.popcorntime.app/o/${backdropId}.webp`}
+									srcSet={cachedImageUrl(`o/${backdropId}.jpg`)}
 									media="(min-width: 960px)"
-									type="image/webp"
-								/>
-								<source
-									srcSet={`https://img.popcorntime.app/o/${backdropId}@960.webp`}
-									type="image/webp"
-								/>
-								<source
-									srcSet={`https://img.popcorntime.app/o/${backdropId}.jpg`}
-									media="(min-width: 960px)"
-									type="image/jpeg"
-								/>
-								<source
-									srcSet={`https://img.popcorntime.app/o/${backdropId}@960.jpg`}
 									type="image/jpeg"
 								/>
 								<img
-									src={`https://img.popcorntime.app/o/${backdropId}.jpg`}
+									src={cachedImageUrl(`o/${backdropId}.jpg`, 960)}
 									alt={media.title}
 									className="aspect-[16/9] h-full w-full object-cover"
 									loading="eager"
@@ -37,7 +37,7 @@ export function OnboardingTimeline() {
 
 	const startBrowsing = useCallback(() => {
 		setIsWorking(true);
-		invoke("set_onboarded", undefined).then(() => {
+		invoke("setOnboarded", []).then(() => {
 			setIsWorking(false);
 			setOnboarded(true);
 			navigate("/");
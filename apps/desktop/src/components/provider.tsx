@@ -1,4 +1,5 @@
-import { type Availability, WatchPriceType } from "@popcorntime/graphql/types";
+import type { MediaMediaAvailabilities } from "@popcorntime/graphql/bindings";
+import { WatchPriceType } from "@popcorntime/graphql/types";
 import { cn } from "@popcorntime/ui/lib/utils";
 import { useMemo } from "react";
 import { useTranslation } from "react-i18next";
@@ -50,7 +51,7 @@ export function ProviderIcon({
 	);
 }
 
-export function ProviderText({ availability }: { availability: Availability }) {
+export function ProviderText({ availability }: { availability: MediaMediaAvailabilities }) {
 	const { t } = useTranslation();
 	const translateKey = useMemo(() => {
 		if (
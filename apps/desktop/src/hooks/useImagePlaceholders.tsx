@@ -0,0 +1,36 @@
+import type { Placeholder } from "@popcorntime/graphql/bindings";
+import { useEffect, useRef, useState } from "react";
+import { useTauri } from "@/hooks/useTauri";
+
+/**
+ * Placeholders of the posters and backdrops among `paths` (see `artworkPath`), fetched
+ * in one batch for the paths without one yet.
+ */
+export function useImagePlaceholders(paths: Array<string | undefined>) {
+	const { invoke } = useTauri();
+	const [placeholders, setPlaceholders] = useState<Partial<Record<string, Placeholder>>>({});
+	const requested = useRef(new Set<string>());
+
+	useEffect(() => {
+		const missing = [
+			...new Set(paths.filter((path): path is string => !!path && !requested.current.has(path))),
+		];
+		if (missing.length === 0) return;
+		for (const path of missing) requested.current.add(path);
+
+		invoke("imagePlaceholders", [missing], { hideToast: true })
+			.then(result => {
+				// retried on the next change, e.g. back online
+				for (const path of missing) {
+					if (!result[path]) requested.current.delete(path);
+				}
+				setPlaceholders(prev => ({ ...prev, ...result }));
+			})
+			.catch(() => {
+				// placeholders are optional, the images load anyway
+				for (const path of missing) requested.current.delete(path);
+			});
+	}, [paths, invoke]);
+
+	return placeholders;
+}
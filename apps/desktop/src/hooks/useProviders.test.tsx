@@ -6,6 +6,7 @@ import userEvent from "@testing-library/user-event";
 import { MemoryRouter } from "react-router";
 import { afterEach, describe, expect, it } from "vitest";
 import { resetGlobalStore, useGlobalStore } from "@/stores/global";
+import { fetched } from "@/test/mock";
 
 const ALL = {
 	CA: [
@@ -44,7 +45,7 @@ let FAVORITES = {
 } satisfies Partial<Record<Country, ProviderSearchForCountry[]>>;
 
 import { CountryProvider } from "@/hooks/useCountry";
-import { type InvokeParams, useProviders } from "@/hooks/useProviders";
+import { useProviders } from "@/hooks/useProviders";
 
 function Harness() {
 	const { getProviders, addToFavorites, removeFromFavorites } = useProviders();
@@ -93,10 +94,10 @@ beforeEach(async () => {
 
 	mockIPC((cmd, args: unknown) => {
 		if (cmd === "providers") {
-			const { params } = args as { params: InvokeParams };
-			if (params.country !== "CA") return [];
+			const { params } = args as { params: { country: Country; favorites: boolean } };
+			if (params.country !== "CA") return fetched([]);
 			const { favorites, country } = params;
-			return favorites ? FAVORITES[country] : ALL[country];
+			return fetched(favorites ? FAVORITES[country] : ALL[country]);
 		}
 
 		if (cmd === "add_favorites_provider") {
@@ -104,7 +105,7 @@ beforeEach(async () => {
 				params: { country: Country; providerKey: string };
 			};
 
-			if (params.country !== "CA") return [];
+			if (params.country !== "CA") return fetched(false);
 
 			const base = FAVORITES[params.country] ?? [];
 			const toAdd = (ALL[params.country] ?? []).find(p => p.key === params.providerKey);
@@ -113,20 +114,20 @@ beforeEach(async () => {
 			const next = exists || !toAdd ? base.slice() : [...base, toAdd];
 			FAVORITES = { ...FAVORITES, [params.country]: next };
 
-			return;
+			return fetched(true);
 		}
 
 		if (cmd === "remove_favorites_provider") {
 			const { params } = args as {
 				params: { country: Country; providerKey: string };
 			};
-			if (params.country !== "CA") return [];
+			if (params.country !== "CA") return fetched(false);
 
 			const base = FAVORITES[params.country] ?? [];
 			const next = base.filter(p => p.key !== params.providerKey);
 			FAVORITES = { ...FAVORITES, [params.country]: next };
 
-			return;
+			return fetched(true);
 		}
 	});
 });
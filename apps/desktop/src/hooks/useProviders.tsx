@@ -1,31 +1,24 @@
-import type { ProviderSearchForCountry } from "@popcorntime/graphql/types";
+import type { ProvidersProviders } from "@popcorntime/graphql/bindings";
 import type { Country } from "@popcorntime/i18n/types";
 import { useCallback } from "react";
 import { useCountry } from "@/hooks/useCountry";
 import { useTauri } from "@/hooks/useTauri";
 import { useGlobalStore } from "@/stores/global";
 
-export interface InvokeParams {
-	country: Country;
-	favorites: boolean;
-}
-
 export const useProviders = () => {
 	const setInitialized = useGlobalStore(state => state.providers.setInitialized);
 	const setIsLoading = useGlobalStore(state => state.providers.setIsLoading);
 	const setProviders = useGlobalStore(state => state.providers.setProviders);
 	const setFavoriteProviders = useGlobalStore(state => state.providers.setFavorites);
 	const { country } = useCountry();
-	const { invoke } = useTauri();
+	const { invokeOperation } = useTauri();
 
 	const loadProviders = useCallback(
-		async (favorites: boolean, country: Country): Promise<ProviderSearchForCountry[]> => {
+		async (favorites: boolean, country: Country): Promise<ProvidersProviders[]> => {
 			try {
-				return invoke<ProviderSearchForCountry[]>(
+				return invokeOperation(
 					"providers",
-					{
-						params: { country: country, favorites },
-					},
+					[{ country: country, query: null, favorites }],
 					{
 						hideToast: true,
 					}
@@ -35,7 +28,7 @@ export const useProviders = () => {
 				return [];
 			}
 		},
-		[invoke]
+		[invokeOperation]
 	);
 
 	const getProviders = useCallback(
@@ -61,29 +54,29 @@ export const useProviders = () => {
 	const addToFavorites = useCallback(
 		async (providerKey: string) => {
 			setIsLoading(true);
-			await invoke<boolean>("add_favorites_provider", {
-				params: { country: country.toUpperCase(), providerKey },
-			});
+			await invokeOperation("addFavoritesProvider", [
+				{ country: country.toUpperCase(), providerKey },
+			]);
 			setIsLoading(false);
 			// update favs
 			const favs = await loadProviders(true, country.toUpperCase() as Country);
 			setFavoriteProviders(favs);
 		},
-		[country, loadProviders, invoke, setFavoriteProviders, setIsLoading]
+		[country, loadProviders, invokeOperation, setFavoriteProviders, setIsLoading]
 	);
 
 	const removeFromFavorites = useCallback(
 		async (providerKey: string) => {
 			setIsLoading(true);
-			await invoke<boolean>("remove_favorites_provider", {
-				params: { country: country.toUpperCase(), providerKey },
-			});
+			await invokeOperation("removeFavoritesProvider", [
+				{ country: country.toUpperCase(), providerKey },
+			]);
 			setIsLoading(false);
 			// update favs
 			const favs = await loadProviders(true, country.toUpperCase() as Country);
 			setFavoriteProviders(favs);
 		},
-		[country, loadProviders, invoke, setFavoriteProviders, setIsLoading]
+		[country, loadProviders, invokeOperation, setFavoriteProviders, setIsLoading]
 	);
 
 	return {
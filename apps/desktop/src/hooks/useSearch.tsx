@@ -1,14 +1,11 @@
-import {
-	type MediaSearch,
-	type PageInfo,
-	type SearchArguments,
-	SortKey,
-} from "@popcorntime/graphql/types";
+import type { SearchSearch, SearchVariables } from "@popcorntime/graphql/bindings";
+import { type SearchArguments, SortKey } from "@popcorntime/graphql/types";
 import type { Country, Locale } from "@popcorntime/i18n";
-import { useCallback, useEffect, useRef, useState } from "react";
+import { useCallback, useEffect, useId, useRef, useState } from "react";
 import isEqual from "react-fast-compare";
 import { useDebounce } from "use-debounce";
-import { useTauri } from "@/hooks/useTauri";
+import { TauriError, useTauri } from "@/hooks/useTauri";
+import { Code } from "@/utils/error";
 
 export type SearchParams = {
 	limit?: number;
@@ -21,33 +18,54 @@ export type SearchParams = {
 	enabled?: boolean;
 };
 
-export type SearchResults = {
-	nodes: Array<MediaSearch>;
-	pageInfo: PageInfo;
-};
+function toArguments(args: SearchArguments): SearchVariables["arguments"] {
+	return {
+		collection: args.collection ?? null,
+		kind: args.kind ?? null,
+		year: args.year ?? null,
+		providers: args.providers ?? null,
+		priceTypes: args.priceTypes ?? null,
+		genres: args.genres ?? null,
+		audio: args.audio ?? null,
+		subtitle: args.subtitle ?? null,
+		country: args.country ?? null,
+		withPoster: args.withPoster ?? null,
+		featured: args.featured ?? null,
+	};
+}
 
 // reverted pagination for 'updated at'
-function toInput(p: SearchParams) {
+function toInput(p: SearchParams, country: Country): SearchVariables {
 	const limit = p.limit ?? 24;
-	const sort = p.sortKey;
-	const reverse = sort === SortKey.UPDATED_AT;
+	const reverse = p.sortKey === SortKey.UPDATED_AT;
 	return {
-		...p,
-		...(reverse
-			? { last: limit, before: p.cursor || undefined }
-			: { first: limit, after: p.cursor || undefined }),
+		first: reverse ? null : limit,
+		after: reverse ? null : p.cursor || null,
+		last: reverse ? limit : null,
+		before: reverse ? p.cursor || null : null,
+		sortKey: p.sortKey ?? null,
+		country,
+		language: p.language ?? null,
+		query: p.query ?? null,
+		arguments: p.arguments ? toArguments(p.arguments) : null,
 	};
 }
 
 export function useSearch(params: SearchParams, onChange?: (params: SearchParams) => void) {
-	const { invoke } = useTauri();
-	const [data, setData] = useState<null | SearchResults>(null);
+	const { invokeOperation } = useTauri();
+	const [data, setData] = useState<null | SearchSearch>(null);
 	const [debouncedParams] = useDebounce(params, 300);
 	const [isLoading, setIsLoading] = useState(false);
 	const prevParams = useRef<SearchParams | undefined>(undefined);
+	// a newer search of this instance cancels the pending one, other instances don't
+	const requestKey = useId();
+	const latestRequest = useRef(0);
 	const enabled = params.enabled !== false;
 
 	const fetch = useCallback(async () => {
+		const { country } = debouncedParams;
+		if (!country) return;
+
 		if (
 			!isEqual(debouncedParams.arguments, prevParams.current?.arguments) ||
 			debouncedParams.query !== prevParams.current?.query ||
@@ -62,13 +80,26 @@ export function useSearch(params: SearchParams, onChange?: (params: SearchParams
 		prevParams.current = debouncedParams;
 
 		setIsLoading(true);
+		const request = ++latestRequest.current;
 
-		const results = await invoke<SearchResults>("search_medias", {
-			params: toInput(debouncedParams),
-		});
+		let results: SearchSearch | null;
+		try {
+			results = await invokeOperation("searchMedias", [
+				requestKey,
+				toInput(debouncedParams, country),
+			]);
+		} catch (err) {
+			if (err instanceof TauriError && err.code === Code.Cancelled) {
+				// the newer search clears the loading state
+				if (request === latestRequest.current) setIsLoading(false);
+				return;
+			}
+			setIsLoading(false);
+			throw err;
+		}
 		setData(results ?? null);
 		setIsLoading(false);
-	}, [debouncedParams, invoke, onChange]);
+	}, [debouncedParams, invokeOperation, onChange, requestKey]);
 
 	useEffect(() => {
 		if (!debouncedParams || !enabled) return;
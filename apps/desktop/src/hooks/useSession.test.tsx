@@ -1,3 +1,4 @@
+import { emit } from "@tauri-apps/api/event";
 import { clearMocks, mockIPC } from "@tauri-apps/api/mocks";
 import { act, render, screen } from "@testing-library/react";
 import { MemoryRouter, useLocation } from "react-router";
@@ -5,6 +6,7 @@ import { afterEach, describe, expect, it } from "vitest";
 import { SessionProvider } from "@/hooks/useSession";
 import { TauriError } from "@/hooks/useTauri";
 import { resetGlobalStore, useGlobalStore } from "@/stores/global";
+import { fetched } from "@/test/mock";
 import { Code } from "@/utils/error";
 
 function LocationProbe() {
@@ -65,7 +67,7 @@ describe("SessionProvider with mockIPC", () => {
 	it("should initialize app with session", async () => {
 		mockIPC((cmd, _args) => {
 			if (cmd === "validate") null;
-			if (cmd === "user_preferences") return { country: "US", language: "fr" };
+			if (cmd === "user_preferences") return fetched({ country: "US", language: "fr" });
 		});
 
 		const s = useGlobalStore.getState();
@@ -93,7 +95,7 @@ describe("SessionProvider with mockIPC", () => {
 	it("should not initialize the app without preferences", async () => {
 		mockIPC((cmd, _args) => {
 			if (cmd === "validate") null;
-			if (cmd === "user_preferences") return null;
+			if (cmd === "user_preferences") return fetched(null);
 		});
 
 		const s = useGlobalStore.getState();
@@ -125,4 +127,36 @@ describe("SessionProvider with mockIPC", () => {
 
 		r.unmount();
 	});
+
+	it("should keep the session and report cached data offline", async () => {
+		mockIPC(
+			(cmd, _args) => {
+				if (cmd === "validate") return null;
+				if (cmd === "user_preferences") {
+					return {
+						data: { country: "US", language: "fr" },
+						errors: [],
+						origin: { source: "cache", fetchedAt: "2025-01-02T03:04:05Z", stale: true },
+					};
+				}
+			},
+			{ shouldMockEvents: true }
+		);
+
+		useGlobalStore.getState().settings.setOnboarded(true);
+		const r = renderWithProvider(1);
+		await act(async () => {});
+		await act(async () => emit("popcorntime://offline"));
+
+		expect(useGlobalStore.getState().session.isActive).toBe(true);
+		expect(useGlobalStore.getState().preferences.country).toBe("US");
+		expect(useGlobalStore.getState().network.online).toBe(false);
+		expect(useGlobalStore.getState().network.staleSince).toBe("2025-01-02T03:04:05Z");
+		expect(screen.getByTestId("loc")).toHaveTextContent("/browse/us");
+
+		await act(async () => emit("popcorntime://online"));
+		expect(useGlobalStore.getState().network.online).toBe(true);
+
+		r.unmount();
+	});
 });
@@ -1,4 +1,7 @@
-import type { UserPreferences } from "@popcorntime/graphql/types";
+import type {
+	PreferencesPreferences,
+	UpdatePreferencesVariables,
+} from "@popcorntime/graphql/bindings";
 import type { Country, Locale } from "@popcorntime/i18n";
 import { createContext, type ReactNode, useCallback, useContext, useEffect, useRef } from "react";
 import { useTranslation } from "react-i18next";
@@ -10,10 +13,14 @@ import { type TauriError, useTauri } from "@/hooks/useTauri";
 import { useGlobalStore } from "@/stores/global";
 import { Code } from "@/utils/error";
 
-type UpdatePreferencesParams = {
-	country: Country;
-	language: Locale;
-};
+type OutboxEvent =
+	| { status: "queued" | "replayed"; operation: string; variables: unknown }
+	| { status: "conflict" | "failed"; operation: string; variables: unknown; message: string };
+
+/** The codes of the API are the ones of the i18n package. */
+function toPreferences({ country, language }: PreferencesPreferences) {
+	return { country: country as Country, language: language as Locale };
+}
 
 const PUBLIC_ROUTES = [/^\/$/, /^\/login$/, /^\/onboarding(\/.*)?$/];
 
@@ -24,7 +31,7 @@ function isPublicRoute(pathname: string) {
 type Context = {
 	logout: () => Promise<void>;
 	revalidate: () => Promise<void>;
-	updatePreferences: (params: UpdatePreferencesParams) => Promise<void>;
+	updatePreferences: (params: UpdatePreferencesVariables) => Promise<void>;
 };
 const SessionContext = createContext<Context>({
 	logout: async () => {},
@@ -42,8 +49,9 @@ export const SessionProvider = ({ children }: { children: ReactNode }) => {
 	const { country } = useGlobalStore(useShallow(state => state.preferences));
 	const setPreferences = useGlobalStore(state => state.preferences.setPreferences);
 	const isActive = useGlobalStore(state => state.session.isActive);
+	const setOnline = useGlobalStore(state => state.network.setOnline);
 
-	const { invoke, listen } = useTauri();
+	const { invoke, invokeOperation, listen } = useTauri();
 	const { pathname } = useLocation();
 	const navigate = useNavigate();
 	const navigateRef = useRef(navigate);
@@ -55,7 +63,7 @@ export const SessionProvider = ({ children }: { children: ReactNode }) => {
 	const revalidate = useCallback(async () => {
 		setLoading(true);
 		try {
-			await invoke("validate", undefined, {
+			await invoke("validate", [], {
 				hideConsoleError: true,
 				hideToast: true,
 			});
@@ -75,7 +83,7 @@ export const SessionProvider = ({ children }: { children: ReactNode }) => {
 	}, [invoke, setActive, setLoading, setSessionInitialized]);
 
 	const logout = useCallback(async () => {
-		await invoke("logout", undefined, { hideConsoleError: true });
+		await invoke("logout", [], { hideConsoleError: true });
 		setActive(false);
 		if (pathRef.current !== "/login") navigate("/", { replace: true });
 	}, [invoke, navigate, setActive]);
@@ -87,6 +95,40 @@ export const SessionProvider = ({ children }: { children: ReactNode }) => {
 		});
 	}, [listen, revalidate]);
 
+	useEffect(() => {
+		// emitted when the API rejected the access token and it could not be refreshed
+		return listen("popcorntime://session_invalid", () => {
+			setActive(false);
+			if (!isPublicRoute(pathRef.current)) {
+				navigateRef.current("/login", { replace: true });
+			}
+		});
+	}, [listen, setActive]);
+
+	useEffect(() => {
+		// emitted when a mutation made offline was queued or replayed
+		return listen<OutboxEvent>("popcorntime://outbox", ({ payload }) => {
+			if (payload.status === "conflict" || payload.status === "failed") {
+				toast.error(t(`outbox.${payload.status}`), {
+					description: payload.message,
+					dismissible: true,
+					closeButton: true,
+					duration: 5000,
+				});
+			}
+		});
+	}, [listen, t]);
+
+	useEffect(() => {
+		// emitted when the API becomes (un)reachable, cached data is shown meanwhile
+		const unlistenOnline = listen("popcorntime://online", () => setOnline(true));
+		const unlistenOffline = listen("popcorntime://offline", () => setOnline(false));
+		return () => {
+			unlistenOnline();
+			unlistenOffline();
+		};
+	}, [listen, setOnline]);
+
 	useEffect(() => {
 		void revalidate();
 	}, [revalidate]);
@@ -96,17 +138,17 @@ export const SessionProvider = ({ children }: { children: ReactNode }) => {
 			return;
 		}
 
-		invoke<UserPreferences | null>("user_preferences", undefined, {
+		invokeOperation("userPreferences", [], {
 			hideConsoleError: true,
 			hideToast: true,
 		})
 			.then(prefs => {
-				setPreferences(prefs ?? undefined);
+				setPreferences(prefs ? toPreferences(prefs) : undefined);
 			})
 			// fallback to default preferences on error
 			.catch(console.error)
 			.finally(setPreferencesInitialized);
-	}, [isActive, invoke, setPreferences, setPreferencesInitialized]);
+	}, [isActive, invokeOperation, setPreferences, setPreferencesInitialized]);
 
 	useEffect(() => {
 		if (!isActive || !country) {
@@ -116,13 +158,10 @@ export const SessionProvider = ({ children }: { children: ReactNode }) => {
 	}, [isActive, country, getProviders]);
 
 	const updatePreferences = useCallback(
-		async (params: UpdatePreferencesParams) => {
+		async (params: UpdatePreferencesVariables) => {
 			try {
-				const preferences = await invoke<Pick<UserPreferences, "country" | "language">>(
-					"update_user_preferences",
-					{ params }
-				);
-				setPreferences(preferences);
+				const preferences = await invokeOperation("updateUserPreferences", [params]);
+				setPreferences(preferences ? toPreferences(preferences) : undefined);
 			} catch (err) {
 				toast.error(t("preferences.error"), {
 					dismissible: true,
@@ -132,7 +171,7 @@ export const SessionProvider = ({ children }: { children: ReactNode }) => {
 				console.error(err);
 			}
 		},
-		[invoke, setPreferences, t]
+		[invokeOperation, setPreferences, t]
 	);
 
 	return (
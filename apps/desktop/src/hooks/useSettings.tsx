@@ -11,7 +11,7 @@ export const SettingsProvider = ({ children }: { children: ReactNode }) => {
 
 	useEffect(() => {
 		if (!isActive) {
-			invoke<boolean>("is_onboarded", undefined).then(setOnboarded);
+			invoke("isOnboarded", []).then(setOnboarded);
 		}
 	}, [invoke, setOnboarded, isActive]);
 
@@ -1,9 +1,15 @@
-import { type InvokeArgs, invoke as invokeTauri } from "@tauri-apps/api/core";
+import {
+	type CommandError,
+	commands,
+	type Fetched,
+	type Result,
+} from "@popcorntime/graphql/bindings";
 import { type EventCallback, type EventName, listen as listenTauri } from "@tauri-apps/api/event";
 import { useCallback, useRef } from "react";
 import { useTranslation } from "react-i18next";
 import { useNavigate } from "react-router";
 import { toast } from "sonner";
+import { useGlobalStore } from "@/stores/global";
 import { Code } from "@/utils/error";
 import { capitalize } from "@/utils/text";
 
@@ -50,36 +56,84 @@ type Options = {
 	hideToast?: boolean;
 };
 
+type Commands = typeof commands;
+type Command = keyof Commands;
+
+/** The data a command resolves to, its `CommandError` is thrown. */
+type Data<K extends Command> =
+	Awaited<ReturnType<Commands[K]>> extends Result<infer T, CommandError>
+		? T
+		: Awaited<ReturnType<Commands[K]>>;
+
+/** The GraphQL operation commands, which return their data along with its origin. */
+type Operation = {
+	[K in Command]: Data<K> extends Fetched<unknown> ? K : never;
+}[Command];
+
+type OperationData<K extends Operation> = Data<K> extends Fetched<infer T> ? T : never;
+
 export function useTauri() {
 	const { t } = useTranslation();
 	const navigate = useNavigate();
 	const navRef = useRef(navigate);
 	const tRef = useRef(t);
 
-	const invoke = useCallback(async <T,>(command: string, args?: InvokeArgs, opts?: Options) => {
-		try {
-			return await invokeTauri<T>(command, args);
-		} catch (err) {
-			const tauriError = TauriError.fromError(err);
-			if (opts?.hideConsoleError !== true) {
-				console.error(`tauri->${command}: ${JSON.stringify(args ?? {})}`, tauriError, err);
-			}
+	const invoke = useCallback(
+		async <K extends Command>(
+			command: K,
+			args: Parameters<Commands[K]>,
+			opts?: Options
+		): Promise<Data<K>> => {
+			try {
+				const call = commands[command] as (
+					...args: Parameters<Commands[K]>
+				) => Promise<Result<Data<K>, CommandError> | undefined>;
+				const result = await call(...args);
+				if (result?.status === "error") {
+					throw result.error;
+				}
+				return result?.data as Data<K>;
+			} catch (err) {
+				const tauriError = TauriError.fromError(err);
+				// superseded by a newer request with the same `requestKey`
+				if (tauriError.code === Code.Cancelled) {
+					throw tauriError;
+				}
+				if (opts?.hideConsoleError !== true) {
+					console.error(`tauri->${command}: ${JSON.stringify(args)}`, tauriError, err);
+				}
 
-			if (tauriError.code === Code.GraphqlServerError) {
-				navRef.current("/maintenance");
-			}
+				if (tauriError.code === Code.GraphqlServerError) {
+					navRef.current("/maintenance");
+				}
+
+				if (!opts?.hideToast) {
+					toast.error(tRef.current(tauriError.message), {
+						dismissible: true,
+						closeButton: true,
+						duration: 5000,
+					});
+				}
 
-			if (!opts?.hideToast) {
-				toast.error(tRef.current(tauriError.message), {
-					dismissible: true,
-					closeButton: true,
-					duration: 5000,
-				});
+				throw tauriError;
 			}
+		},
+		[]
+	);
 
-			throw tauriError;
-		}
-	}, []);
+	/** Invoke a GraphQL operation command, recording the origin of its data. */
+	const invokeOperation = useCallback(
+		async <K extends Operation>(
+			command: K,
+			args: Parameters<Commands[K]>,
+			opts?: Options
+		): Promise<OperationData<K>> => {
+			const { data, origin } = (await invoke(command, args, opts)) as Fetched<OperationData<K>>;
+			useGlobalStore.getState().network.setOrigin(origin);
+			return data;
+		},
+		[invoke]
+	);
 
 	const listen = useCallback(<T,>(event: EventName, handle: EventCallback<T>) => {
 		const unlistenProm = listenTauri(event, handle);
@@ -92,6 +146,7 @@ export function useTauri() {
 
 	return {
 		invoke,
+		invokeOperation,
 		listen,
 	};
 }
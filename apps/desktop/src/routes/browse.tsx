@@ -1,6 +1,8 @@
-import { type MediaKind, type MediaSearch, SortKey } from "@popcorntime/graphql/types";
+import type { SearchSearchNodes } from "@popcorntime/graphql/bindings";
+import { type MediaKind, SortKey } from "@popcorntime/graphql/types";
 import { BrowseMedias } from "@popcorntime/ui/blocks/browse";
 import { useSidebar, useSidebarGroup } from "@popcorntime/ui/components/sidebar";
+import { artworkPath } from "@popcorntime/ui/lib/medias";
 import { useCallback, useEffect, useMemo, useRef, useState } from "react";
 import { useTranslation } from "react-i18next";
 import useInfiniteScroll from "react-infinite-scroll-hook";
@@ -8,6 +10,7 @@ import { useLocation, useParams } from "react-router";
 import placeholderImg from "@/assets/placeholder.svg";
 import { BrowseSidebarGroup } from "@/components/browse/sidebar";
 import { useCountry } from "@/hooks/useCountry";
+import { useImagePlaceholders } from "@/hooks/useImagePlaceholders";
 import { type SearchParams, useSearch } from "@/hooks/useSearch";
 import { useGlobalStore } from "@/stores/global";
 
@@ -24,7 +27,7 @@ export function BrowseRoute() {
 	const query = useGlobalStore(state => state.browse.query);
 	const openMediaDialog = useGlobalStore(state => state.dialogs.media.open);
 	const { t } = useTranslation();
-	const [dataAccumulator, setDataAccumulator] = useState<MediaSearch[]>([]);
+	const [dataAccumulator, setDataAccumulator] = useState<SearchSearchNodes[]>([]);
 	const { setOpen: setOpenSidebar } = useSidebar();
 	const { pathname } = useLocation();
 	const { kind } = useParams<{ kind: "movie" | "tv_show" }>();
@@ -130,6 +133,12 @@ export function BrowseRoute() {
 		setOpenSidebar(false);
 	}, [setOpenSidebar, pathname]);
 
+	const posterPaths = useMemo(
+		() => dataAccumulator.map(media => artworkPath(media.poster)),
+		[dataAccumulator]
+	);
+	const placeholders = useImagePlaceholders(posterPaths);
+
 	const [sentryRef] = useInfiniteScroll({
 		loading: isLoading,
 		hasNextPage,
@@ -141,6 +150,7 @@ export function BrowseRoute() {
 		<BrowseMedias
 			sentryRef={sentryRef}
 			medias={dataAccumulator}
+			placeholders={placeholders}
 			onOpen={openMediaDialog}
 			placeholder={placeholderImg}
 			isReady={!isLoading && initialized && dataAccumulator.length > 0}
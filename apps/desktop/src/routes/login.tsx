@@ -16,7 +16,7 @@ export function LoginRoute() {
 	const navigate = useNavigate();
 
 	async function initialize_session_authorization() {
-		await invoke("initialize_session_authorization");
+		await invoke("initializeSessionAuthorization", []);
 	}
 
 	useEffect(() => {
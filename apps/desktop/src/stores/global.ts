@@ -1,8 +1,5 @@
-import {
-	type ProviderSearchForCountry,
-	type SearchArguments,
-	SortKey,
-} from "@popcorntime/graphql/types";
+import type { Origin, ProvidersProviders } from "@popcorntime/graphql/bindings";
+import { type SearchArguments, SortKey } from "@popcorntime/graphql/types";
 import { i18n } from "@popcorntime/i18n";
 import type { Country, Locale } from "@popcorntime/i18n/types";
 import type { Update } from "@tauri-apps/plugin-updater";
@@ -77,15 +74,24 @@ export interface GlobalState {
 		lastChecked?: Date;
 		setLastChecked: (date?: Date) => void;
 	};
+	network: {
+		/** Whether the API is reachable, see the `popcorntime://online` and `offline` events */
+		online: boolean;
+		setOnline: (online: boolean) => void;
+		/** When the oldest outdated data on screen was fetched */
+		staleSince?: string;
+		/** Record where the data of an operation comes from */
+		setOrigin: (origin: Origin) => void;
+	};
 	providers: {
 		initialized: boolean;
 		setInitialized: () => void;
 		isLoading: boolean;
 		setIsLoading: (isLoading: boolean) => void;
-		providers: ProviderSearchForCountry[];
-		setProviders: (providers: ProviderSearchForCountry[]) => void;
-		favorites: ProviderSearchForCountry[];
-		setFavorites: (favorites: ProviderSearchForCountry[]) => void;
+		providers: ProvidersProviders[];
+		setProviders: (providers: ProvidersProviders[]) => void;
+		favorites: ProvidersProviders[];
+		setFavorites: (favorites: ProvidersProviders[]) => void;
 	};
 	browse: {
 		/** Search query */
@@ -214,6 +220,25 @@ export const useGlobalStore = create<GlobalState>()(
 							state.updater.lastChecked = date;
 						}),
 				},
+				network: {
+					online: true,
+					staleSince: undefined,
+					setOnline: (online: boolean) =>
+						set(state => {
+							state.network.online = online;
+						}),
+					setOrigin: (origin: Origin) =>
+						set(state => {
+							if (origin.source === "network") {
+								state.network.staleSince = undefined;
+							} else if (origin.source === "cache" && origin.stale) {
+								const { staleSince } = state.network;
+								if (!staleSince || Date.parse(origin.fetchedAt) < Date.parse(staleSince)) {
+									state.network.staleSince = origin.fetchedAt;
+								}
+							}
+						}),
+				},
 				providers: {
 					initialized: false,
 					isLoading: false,
@@ -227,11 +252,11 @@ export const useGlobalStore = create<GlobalState>()(
 						set(state => {
 							state.providers.isLoading = isLoading;
 						}),
-					setProviders: (providers: ProviderSearchForCountry[]) =>
+					setProviders: (providers: ProvidersProviders[]) =>
 						set(state => {
 							state.providers.providers = providers;
 						}),
-					setFavorites: (favorites: ProviderSearchForCountry[]) =>
+					setFavorites: (favorites: ProvidersProviders[]) =>
 						set(state => {
 							state.providers.favorites = favorites;
 						}),
@@ -317,7 +342,7 @@ export const resetGlobalStore = () => {
 	useGlobalStore.setState(initial, true);
 };
 
-function syncFavorites(favorites: ProviderSearchForCountry[]) {
+function syncFavorites(favorites: ProvidersProviders[]) {
 	const {
 		providers,
 		browse: { preferFavorites },
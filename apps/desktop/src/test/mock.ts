@@ -1,4 +1,6 @@
 /** biome-ignore-all lint/suspicious/noExplicitAny: mock */
+import type { Fetched } from "@popcorntime/graphql/bindings";
+
 const base = vi.fn<(message: unknown, data?: unknown) => string | number>();
 (base as any).success = vi.fn();
 (base as any).info = vi.fn();
@@ -9,3 +11,10 @@ const base = vi.fn<(message: unknown, data?: unknown) => string | number>();
 
 export const toast = base as unknown as typeof import("sonner").toast;
 export const pluginShellOpen = vi.fn();
+
+/** The value of an operation command served by the API. */
+export const fetched = <T>(data: T): Fetched<T> => ({
+	data,
+	errors: [],
+	origin: { source: "network" },
+});
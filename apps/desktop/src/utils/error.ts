@@ -1,5 +1,12 @@
 export enum Code {
 	Unknown = "errors.unknown",
 	GraphqlServerError = "errors.graphql.server",
+	GraphqlNotFound = "errors.graphql.not_found",
+	GraphqlForbidden = "errors.graphql.forbidden",
+	GraphqlRateLimited = "errors.graphql.rate_limited",
+	GraphqlUnauthenticated = "errors.graphql.unauthenticated",
+	GraphqlValidation = "errors.graphql.validation",
 	InvalidSession = "errors.session.invalid",
+	Offline = "errors.offline",
+	Cancelled = "errors.cancelled",
 }
@@ -8,6 +8,14 @@ pub enum Code {
   DatabaseNotAvailable,
   InvalidSession,
   GraphqlNoData,
+  GraphqlNotFound,
+  GraphqlForbidden,
+  GraphqlRateLimited,
+  GraphqlUnauthenticated,
+  GraphqlValidation,
+  Offline,
+  /// The request was superseded by a newer one with the same key.
+  Cancelled,
 }
 
 impl std::fmt::Display for Code {
@@ -18,6 +26,13 @@ impl std::fmt::Display for Code {
       Code::InvalidSession => "errors.session.invalid",
       Code::DatabaseNotAvailable => "errors.database.not_available",
       Code::GraphqlNoData => "errors.graphql.no_data",
+      Code::GraphqlNotFound => "errors.graphql.not_found",
+      Code::GraphqlForbidden => "errors.graphql.forbidden",
+      Code::GraphqlRateLimited => "errors.graphql.rate_limited",
+      Code::GraphqlUnauthenticated => "errors.graphql.unauthenticated",
+      Code::GraphqlValidation => "errors.graphql.validation",
+      Code::Offline => "errors.offline",
+      Code::Cancelled => "errors.cancelled",
     };
     f.write_str(code)
   }
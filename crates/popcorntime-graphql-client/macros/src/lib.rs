@@ -1,53 +1,562 @@
 use convert_case::{Case, Casing};
+use graphql_client_codegen::{
+  CodegenMode, GraphQLClientCodegenOptions, generate_module_token_stream,
+};
 use proc_macro::TokenStream;
 use quote::quote;
+use sha2::{Digest, Sha256};
 use syn::{
-  Ident, LitStr, Token,
+  Ident, LitInt, LitStr, Token, Type, bracketed, parenthesized,
   parse::{Parse, ParseStream},
   parse_macro_input,
+  punctuated::Punctuated,
 };
 
+/// The operation kind, `query` unless `mutation` or `mutation(idempotent)` is given.
+enum Kind {
+  Query,
+  Mutation { idempotent: bool },
+}
+
+impl Kind {
+  fn parse_after(kind: &Ident, input: ParseStream) -> syn::Result<Self> {
+    match kind.to_string().as_str() {
+      "query" => Ok(Kind::Query),
+      "mutation" if input.peek(syn::token::Paren) => {
+        let content;
+        parenthesized!(content in input);
+        let flag: Ident = content.parse()?;
+        if flag != "idempotent" {
+          return Err(syn::Error::new(flag.span(), "expected `idempotent`"));
+        }
+        Ok(Kind::Mutation { idempotent: true })
+      }
+      _ => Ok(Kind::Mutation { idempotent: false }),
+    }
+  }
+}
+
+/// The cache policy, e.g. `cache = cache_first(300)` with a TTL in seconds.
+enum Policy {
+  CacheFirst(LitInt),
+  NetworkFirst,
+  StaleWhileRevalidate(LitInt),
+  NetworkOnly,
+}
+
+impl Parse for Policy {
+  fn parse(input: ParseStream) -> syn::Result<Self> {
+    let policy: Ident = input.parse()?;
+    let ttl = |input: ParseStream| -> syn::Result<LitInt> {
+      let content;
+      parenthesized!(content in input);
+      content.parse()
+    };
+    match policy.to_string().as_str() {
+      "cache_first" => Ok(Policy::CacheFirst(ttl(input)?)),
+      "network_first" => Ok(Policy::NetworkFirst),
+      "stale_while_revalidate" => Ok(Policy::StaleWhileRevalidate(ttl(input)?)),
+      "network_only" => Ok(Policy::NetworkOnly),
+      _ => Err(syn::Error::new(
+        policy.span(),
+        "expected `cache_first(ttl)`, `network_first`, `stale_while_revalidate(ttl)` or `network_only`",
+      )),
+    }
+  }
+}
+
+/// The outbox policy, `outbox = queue`, `outbox = replace` or `outbox = toggles(Operation)`.
+enum Outbox {
+  Queue,
+  Replace,
+  Toggles(Ident),
+}
+
+impl Parse for Outbox {
+  fn parse(input: ParseStream) -> syn::Result<Self> {
+    let policy: Ident = input.parse()?;
+    match policy.to_string().as_str() {
+      "queue" => Ok(Outbox::Queue),
+      "replace" => Ok(Outbox::Replace),
+      "toggles" => {
+        let content;
+        parenthesized!(content in input);
+        Ok(Outbox::Toggles(content.parse()?))
+      }
+      _ => Err(syn::Error::new(
+        policy.span(),
+        "expected `queue`, `replace` or `toggles(Operation)`",
+      )),
+    }
+  }
+}
+
+/// The client side limits, e.g. `limit(per_second = 4, burst = 8, max_in_flight = 2)`.
+#[derive(Default)]
+struct Limit {
+  per_second: Option<f64>,
+  burst: Option<u32>,
+  max_in_flight: Option<usize>,
+}
+
+impl Parse for Limit {
+  fn parse(input: ParseStream) -> syn::Result<Self> {
+    let content;
+    let paren = parenthesized!(content in input);
+    let mut limit = Limit::default();
+    loop {
+      let option: Ident = content.parse()?;
+      content.parse::<Token![=]>()?;
+      match option.to_string().as_str() {
+        "per_second" => {
+          let lit: syn::Lit = content.parse()?;
+          limit.per_second = Some(match &lit {
+            syn::Lit::Float(lit) => lit.base10_parse()?,
+            syn::Lit::Int(lit) => lit.base10_parse()?,
+            _ => return Err(syn::Error::new(lit.span(), "expected a number")),
+          });
+        }
+        "burst" => limit.burst = Some(content.parse::<LitInt>()?.base10_parse()?),
+        "max_in_flight" => limit.max_in_flight = Some(content.parse::<LitInt>()?.base10_parse()?),
+        _ => {
+          return Err(syn::Error::new(
+            option.span(),
+            "expected `per_second`, `burst` or `max_in_flight`",
+          ));
+        }
+      }
+      if content.parse::<Option<Token![,]>>()?.is_none() || content.is_empty() {
+        break;
+      }
+    }
+    if limit.burst.is_some() && limit.per_second.is_none() {
+      return Err(syn::Error::new(
+        paren.span.join(),
+        "`burst` requires `per_second`",
+      ));
+    }
+    Ok(limit)
+  }
+}
+
+/// The Tauri command generated by `graphql_command!` for an operation, e.g.
+/// `graphql_command!(Media(name = media, unwrap = media, returns = Option<media::MediaMedia>))`.
+///
+/// The command validates the session first unless `auth = public` is given, and takes
+/// the operation variables as `params` unless `params = none` is given. Commands
+/// declared `cancellable` take an optional `request_key`, a newer request with the same
+/// key cancels the pending one. The command returns a `command::Fetched` holding the
+/// `returns` value and the origin of the response.
+struct Command {
+  operation: Ident,
+  name: Ident,
+  unwrap: Ident,
+  returns: Type,
+  public: bool,
+  params: bool,
+  cancellable: bool,
+}
+
+impl Parse for Command {
+  fn parse(input: ParseStream) -> syn::Result<Self> {
+    let operation = input.parse()?;
+    let content;
+    let paren = parenthesized!(content in input);
+    let (mut name, mut unwrap, mut returns) = (None, None, None);
+    let (mut public, mut params, mut cancellable) = (false, true, false);
+
+    loop {
+      let option: Ident = content.parse()?;
+      if option == "cancellable" {
+        cancellable = true;
+        if content.parse::<Option<Token![,]>>()?.is_none() || content.is_empty() {
+          break;
+        }
+        continue;
+      }
+      content.parse::<Token![=]>()?;
+      match option.to_string().as_str() {
+        "name" => name = Some(content.parse()?),
+        "unwrap" => unwrap = Some(content.parse()?),
+        "returns" => returns = Some(content.parse()?),
+        "auth" => {
+          let auth: Ident = content.parse()?;
+          public = match auth.to_string().as_str() {
+            "session" => false,
+            "public" => true,
+            _ => {
+              return Err(syn::Error::new(
+                auth.span(),
+                "expected `session` or `public`",
+              ));
+            }
+          };
+        }
+        "params" => {
+          let none: Ident = content.parse()?;
+          if none != "none" {
+            return Err(syn::Error::new(none.span(), "expected `none`"));
+          }
+          params = false;
+        }
+        _ => {
+          return Err(syn::Error::new(
+            option.span(),
+            "expected `name`, `unwrap`, `returns`, `auth`, `params` or `cancellable`",
+          ));
+        }
+      }
+      if content.parse::<Option<Token![,]>>()?.is_none() || content.is_empty() {
+        break;
+      }
+    }
+
+    let missing = |option| syn::Error::new(paren.span.join(), format!("missing `{option}`"));
+    Ok(Self {
+      operation,
+      name: name.ok_or_else(|| missing("name"))?,
+      unwrap: unwrap.ok_or_else(|| missing("unwrap"))?,
+      returns: returns.ok_or_else(|| missing("returns"))?,
+      public,
+      params,
+      cancellable,
+    })
+  }
+}
+
 struct Args {
   name: Ident,
   _c1: Token![,],
   query: LitStr,
+  kind: Kind,
+  cache: Policy,
+  invalidates: Vec<Ident>,
+  outbox: Option<Outbox>,
+  /// Send the persisted query as a GET request, which CDNs can cache.
+  get: bool,
+  limit: Limit,
 }
 
 impl Parse for Args {
   fn parse(input: ParseStream) -> syn::Result<Self> {
+    let name = input.parse()?;
+    let _c1 = input.parse()?;
+    let query = input.parse()?;
+    let mut kind = Kind::Query;
+    let mut cache = Policy::NetworkOnly;
+    let mut invalidates = Vec::new();
+    let mut outbox = None;
+    let mut get = None;
+    let mut limit = Limit::default();
+
+    while input.parse::<Option<Token![,]>>()?.is_some() {
+      let option: Ident = input.parse()?;
+      match option.to_string().as_str() {
+        "query" | "mutation" => kind = Kind::parse_after(&option, input)?,
+        "cache" => {
+          input.parse::<Token![=]>()?;
+          cache = input.parse()?;
+        }
+        "invalidates" => {
+          input.parse::<Token![=]>()?;
+          let content;
+          bracketed!(content in input);
+          invalidates = Punctuated::<Ident, Token![,]>::parse_terminated(&content)?
+            .into_iter()
+            .collect();
+        }
+        "outbox" => {
+          input.parse::<Token![=]>()?;
+          outbox = Some(input.parse()?);
+        }
+        "get" => get = Some(option),
+        "limit" => limit = input.parse()?,
+        _ => {
+          return Err(syn::Error::new(
+            option.span(),
+            "expected `query`, `mutation`, `cache`, `invalidates`, `outbox`, `get` or `limit`",
+          ));
+        }
+      }
+    }
+
+    if let Some(get) = &get
+      && !matches!(kind, Kind::Query)
+    {
+      return Err(syn::Error::new(
+        get.span(),
+        "only queries can be sent with GET",
+      ));
+    }
+
     Ok(Self {
-      name: input.parse()?,
-      _c1: input.parse()?,
-      query: input.parse()?,
+      name,
+      _c1,
+      query,
+      kind,
+      cache,
+      invalidates,
+      outbox,
+      get: get.is_some(),
+      limit,
     })
   }
 }
 
 #[proc_macro]
 pub fn define_graphql_query(input: TokenStream) -> TokenStream {
-  let Args { name, query, .. } = parse_macro_input!(input as Args);
+  let Args {
+    name,
+    query,
+    kind,
+    cache,
+    invalidates,
+    outbox,
+    get,
+    limit,
+    ..
+  } = parse_macro_input!(input as Args);
   let module = Ident::new(&name.to_string().to_case(Case::Snake), name.span());
+  let kind = match kind {
+    Kind::Query => quote! { OperationKind::Query },
+    Kind::Mutation { idempotent } => quote! { OperationKind::Mutation { idempotent: #idempotent } },
+  };
+  let cache = match cache {
+    Policy::CacheFirst(ttl) => quote! {
+      CachePolicy::CacheFirst { ttl: std::time::Duration::from_secs(#ttl) }
+    },
+    Policy::NetworkFirst => quote! { CachePolicy::NetworkFirst },
+    Policy::StaleWhileRevalidate(ttl) => quote! {
+      CachePolicy::StaleWhileRevalidate { ttl: std::time::Duration::from_secs(#ttl) }
+    },
+    Policy::NetworkOnly => quote! { CachePolicy::NetworkOnly },
+  };
+  let invalidates = invalidates.iter().map(ToString::to_string);
+  let outbox = match outbox {
+    None => quote! { None },
+    Some(Outbox::Queue) => quote! { Some(OutboxPolicy::Queue) },
+    Some(Outbox::Replace) => quote! { Some(OutboxPolicy::Replace) },
+    Some(Outbox::Toggles(opposite)) => {
+      let opposite = opposite.to_string();
+      quote! { Some(OutboxPolicy::Toggle { opposite: #opposite }) }
+    }
+  };
+  let rate = match limit.per_second {
+    None => quote! { None },
+    Some(per_second) => {
+      // a burst of one request by default, i.e. evenly spaced requests
+      let burst = limit.burst.unwrap_or(1);
+      quote! { Some(RateLimit { per_second: #per_second, burst: #burst }) }
+    }
+  };
+  let max_in_flight = match limit.max_in_flight {
+    None => quote! { None },
+    Some(max_in_flight) => quote! { Some(#max_in_flight) },
+  };
+
+  let operation = match operation(&name, &query) {
+    Ok(operation) => operation,
+    Err(err) => return err.to_compile_error().into(),
+  };
 
   TokenStream::from(quote! {
-      #[derive(graphql_client::GraphQLQuery)]
-      #[graphql(
-          schema_path = "gql/schema.json",
-          query_path  = #query,
-          variables_derives = "Clone, Debug, Deserialize",
-          response_derives  = "Debug, Serialize, Deserialize"
-      )]
       pub struct #name;
 
+      #operation
+
       impl ApiClient {
           pub async fn #module(
               &self,
               vars: &#module::Variables,
-          ) -> Result<Option<#module::ResponseData>> {
+          ) -> Result<GraphqlResponse<#module::ResponseData>> {
               let body = #name::build_query(vars.clone());
-              let resp: graphql_client::Response<#module::ResponseData> =
-                  self.query(&body, false).await.context(Code::GraphqlServerError)?;
-              Ok(resp.data)
+              self
+                  .query(
+                      &body,
+                      RequestOptions {
+                          kind: #kind,
+                          cache: #cache,
+                          invalidates: &[#(#invalidates),*],
+                          outbox: #outbox,
+                          persisted: Some(PersistedQuery {
+                              hash: #module::QUERY_HASH,
+                              get: #get,
+                          }),
+                          limits: OperationLimits {
+                              rate: #rate,
+                              max_in_flight: #max_in_flight,
+                          },
+                          ..Default::default()
+                      },
+                  )
+                  .await
+          }
+      }
+  })
+}
+
+/// The enums of the schema, defined once in `enums.rs` rather than in every operation
+/// module.
+fn schema_enums(schema: &std::path::Path) -> anyhow::Result<Vec<String>> {
+  let schema: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(schema)?)?;
+  let schema = schema.get("data").unwrap_or(&schema);
+  Ok(
+    schema["__schema"]["types"]
+      .as_array()
+      .into_iter()
+      .flatten()
+      .filter(|ty| ty["kind"] == "ENUM")
+      .filter_map(|ty| ty["name"].as_str())
+      .filter(|name| !name.starts_with("__"))
+      .map(ToString::to_string)
+      .collect(),
+  )
+}
+
+/// What `#[derive(graphql_client::GraphQLQuery)]` generates, with the `Variables` and
+/// `ResponseData` of each operation named after it in the TypeScript bindings, and the
+/// sha256 `QUERY_HASH` of the document sent as persisted query.
+fn operation(name: &Ident, query: &LitStr) -> syn::Result<proc_macro2::TokenStream> {
+  let manifest_dir = std::env::var("CARGO_MANIFEST_DIR")
+    .map_err(|_| syn::Error::new(query.span(), "CARGO_MANIFEST_DIR is not defined"))?;
+  let manifest_dir = std::path::Path::new(&manifest_dir);
+
+  let schema = manifest_dir.join("gql/schema.json");
+  let extern_enums = schema_enums(&schema).map_err(|err| {
+    syn::Error::new(
+      query.span(),
+      format!("Failed to read the schema enums: {err}"),
+    )
+  })?;
+
+  let mut options = GraphQLClientCodegenOptions::new(CodegenMode::Derive);
+  options.set_query_file(manifest_dir.join(query.value()));
+  options.set_extern_enums(extern_enums);
+  options.set_variables_derives("Clone, Debug, Deserialize, specta::Type".to_string());
+  options.set_response_derives("Debug, Serialize, Deserialize, specta::Type".to_string());
+  options.set_struct_ident(name.clone());
+  options.set_module_visibility(syn1::Visibility::Public(syn1::VisPublic {
+    pub_token: Default::default(),
+  }));
+  options.set_operation_name(name.to_string());
+
+  let tokens = generate_module_token_stream(manifest_dir.join(query.value()), &schema, options)
+    .map_err(|err| {
+      syn::Error::new(
+        query.span(),
+        format!("Failed to generate GraphQLQuery impl: {err}"),
+      )
+    })?;
+
+  let mut file: syn::File = syn::parse2(tokens)?;
+  for item in &mut file.items {
+    let syn::Item::Mod(module) = item else {
+      continue;
+    };
+    let Some((_, items)) = &mut module.content else {
+      continue;
+    };
+    if let Some(hash) = items.iter().find_map(query_hash) {
+      items.push(syn::parse_quote!(pub const QUERY_HASH: &str = #hash;));
+    }
+    for item in items {
+      let syn::Item::Struct(item) = item else {
+        continue;
+      };
+      if item.ident == "Variables" || item.ident == "ResponseData" {
+        let rename = format!("{name}{}", item.ident);
+        item
+          .attrs
+          .push(syn::parse_quote!(#[specta(rename = #rename)]));
+      }
+    }
+  }
+
+  Ok(quote! { #file })
+}
+
+/// The hex encoded sha256 of the `QUERY` constant.
+fn query_hash(item: &syn::Item) -> Option<String> {
+  let syn::Item::Const(item) = item else {
+    return None;
+  };
+  let syn::Expr::Lit(syn::ExprLit {
+    lit: syn::Lit::Str(query),
+    ..
+  }) = item.expr.as_ref()
+  else {
+    return None;
+  };
+  (item.ident == "QUERY").then(|| hex::encode(Sha256::digest(query.value())))
+}
+
+/// The `#[tauri::command]` calling an operation of `define_graphql_query!`, see
+/// [`Command`].
+///
+/// `ApiClient`, `Error`, the `command` helpers and the operation module must be in
+/// scope.
+#[proc_macro]
+pub fn graphql_command(input: TokenStream) -> TokenStream {
+  let Command {
+    operation,
+    name,
+    unwrap,
+    returns,
+    public,
+    params,
+    cancellable,
+  } = parse_macro_input!(input as Command);
+  let module = Ident::new(
+    &operation.to_string().to_case(Case::Snake),
+    operation.span(),
+  );
+  let (auth_param, auth_skip, validate) = if public {
+    (quote! {}, quote! {}, quote! {})
+  } else {
+    (
+      quote! { auth_service: tauri::State<'_, popcorntime_session::AuthorizationService>, },
+      quote! { auth_service, },
+      quote! { auth_service.validate().await?; },
+    )
+  };
+  let (params_param, vars) = if params {
+    (quote! { params: #module::Variables, }, quote! { params })
+  } else {
+    (quote! {}, quote! { #module::Variables {} })
+  };
+
+  let request = quote! { api_client.#module(&#vars) };
+  let (cancel_param, cancel_skip, request) = if cancellable {
+    (
+      quote! {
+          requests: tauri::State<'_, command::CancellableRequests>,
+          request_key: Option<String>,
+      },
+      quote! { requests, },
+      quote! {
+          match request_key {
+              Some(key) => requests.run(key, #request).await,
+              None => #request.await,
           }
+      },
+    )
+  } else {
+    (quote! {}, quote! {}, quote! { #request.await })
+  };
+
+  TokenStream::from(quote! {
+      #[tauri::command(async)]
+      #[specta::specta]
+      #[tracing::instrument(skip(api_client, #auth_skip #cancel_skip), err(Debug))]
+      pub async fn #name(
+          api_client: tauri::State<'_, ApiClient>,
+          #auth_param
+          #cancel_param
+          #params_param
+      ) -> std::result::Result<command::Fetched<#returns>, Error> {
+          #validate
+
+          Ok(command::Fetched::from_response(#request?, |data| data.#unwrap)?)
       }
   })
 }
@@ -0,0 +1,232 @@
+use crate::{
+  schema::{InputValue, Kind, Schema, TypeRef},
+  usage::Usage,
+};
+use std::collections::BTreeMap;
+
+/// The changes from `old` to `new` which break the operations described by `usage`,
+/// which was collected against `old`.
+pub fn breaking_changes(old: &Schema, new: &Schema, usage: &Usage) -> Vec<String> {
+  let mut changes = Vec::new();
+
+  for name in &usage.types {
+    let (Some(old_type), Some(new_type)) = (old.get(name), new.get(name)) else {
+      if old.get(name).is_some() {
+        changes.push(format!("`{name}` was removed"));
+      }
+      continue;
+    };
+    if old_type.kind != new_type.kind {
+      changes.push(format!(
+        "`{name}` changed from {:?} to {:?}",
+        old_type.kind, new_type.kind
+      ));
+      continue;
+    }
+
+    match new_type.kind {
+      Kind::Enum => {
+        for value in old_type.enum_values.iter().flatten() {
+          let exists = new_type
+            .enum_values
+            .iter()
+            .flatten()
+            .any(|new_value| new_value.name == value.name);
+          if !exists {
+            changes.push(format!("`{name}.{}` was removed", value.name));
+          }
+        }
+      }
+      Kind::InputObject => {
+        for field in new_type.input_fields.iter().flatten() {
+          if old_type.input_field(&field.name).is_none() && field.is_required() {
+            changes.push(format!(
+              "`{name}.{}` is a new required input field",
+              field.name
+            ));
+          }
+        }
+      }
+      Kind::Interface | Kind::Union => {
+        for possible_type in old_type.possible_types() {
+          let still_possible = new_type.possible_types().any(|ty| ty == possible_type);
+          if usage.types.contains(possible_type) && !still_possible {
+            changes.push(format!(
+              "`{possible_type}` is no longer a possible type of `{name}`"
+            ));
+          }
+        }
+      }
+      Kind::Scalar | Kind::Object => {}
+    }
+  }
+
+  for coordinate in &usage.fields {
+    let Some((ty, name)) = coordinate.split_once('.') else {
+      continue;
+    };
+    let (Some(old_type), Some(new_type)) = (old.get(ty), new.get(ty)) else {
+      // the whole type is already reported
+      continue;
+    };
+
+    if old_type.kind == Kind::InputObject {
+      let Some(old_field) = old_type.input_field(name) else {
+        continue;
+      };
+      match new_type.input_field(name) {
+        None => changes.push(format!("`{coordinate}` was removed")),
+        Some(new_field) => input_change(&mut changes, coordinate, old_field, new_field),
+      }
+      continue;
+    }
+
+    let Some(old_field) = old_type.field(name) else {
+      continue;
+    };
+    let Some(new_field) = new_type.field(name) else {
+      changes.push(format!("`{coordinate}` was removed"));
+      continue;
+    };
+    if !output_compatible(&old_field.ty, &new_field.ty) {
+      changes.push(format!(
+        "`{coordinate}` changed type from `{}` to `{}`",
+        old_field.ty, new_field.ty
+      ));
+    }
+
+    let old_args: BTreeMap<_, _> = old_field.args.iter().map(|arg| (&arg.name, arg)).collect();
+    for new_arg in &new_field.args {
+      let arg_coordinate = format!("{coordinate}({}:)", new_arg.name);
+      let used = usage.arguments.contains(&arg_coordinate);
+      match old_args.get(&new_arg.name) {
+        Some(old_arg) if used => input_change(&mut changes, &arg_coordinate, old_arg, new_arg),
+        None if new_arg.is_required() => {
+          changes.push(format!("`{arg_coordinate}` is a new required argument"))
+        }
+        _ => {}
+      }
+    }
+    for old_arg in &old_field.args {
+      let arg_coordinate = format!("{coordinate}({}:)", old_arg.name);
+      if usage.arguments.contains(&arg_coordinate) && new_field.arg(&old_arg.name).is_none() {
+        changes.push(format!("`{arg_coordinate}` was removed"));
+      }
+    }
+  }
+
+  changes
+}
+
+fn input_change(changes: &mut Vec<String>, coordinate: &str, old: &InputValue, new: &InputValue) {
+  // inputs go the other way, what we send for `old` must be accepted as `new`
+  if !output_compatible(&new.ty, &old.ty) {
+    changes.push(format!(
+      "`{coordinate}` changed type from `{}` to `{}`",
+      old.ty, new.ty
+    ));
+  }
+}
+
+/// Whether a value of type `new` can be read where a value of type `old` was expected,
+/// e.g. a nullable field becoming non null is fine but not the reverse.
+pub fn output_compatible(old: &TypeRef, new: &TypeRef) -> bool {
+  match (old, new) {
+    (TypeRef::NonNull(old), TypeRef::NonNull(new)) => output_compatible(old, new),
+    (TypeRef::NonNull(_), _) => false,
+    (_, TypeRef::NonNull(new)) => output_compatible(old, new),
+    (TypeRef::List(old), TypeRef::List(new)) => output_compatible(old, new),
+    (TypeRef::Named(old), TypeRef::Named(new)) => old == new,
+    _ => false,
+  }
+}
+
+/// The fields of the schema's objects and interfaces that no operation selects,
+/// grouped by type.
+pub fn unused_fields<'s>(schema: &'s Schema, usage: &Usage) -> BTreeMap<&'s str, Vec<&'s str>> {
+  let mut unused = BTreeMap::new();
+  for ty in schema.types.values() {
+    if ty.name.starts_with("__") || !matches!(ty.kind, Kind::Object | Kind::Interface) {
+      continue;
+    }
+    let fields: Vec<_> = ty
+      .fields
+      .iter()
+      .flatten()
+      .map(|field| field.name.as_str())
+      .filter(|field| !usage.fields.contains(&format!("{}.{field}", ty.name)))
+      .collect();
+    if !fields.is_empty() {
+      unused.insert(ty.name.as_str(), fields);
+    }
+  }
+  unused
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::usage::Source;
+  use std::path::Path;
+
+  fn checked_in() -> (Schema, Vec<Source>) {
+    let gql = Path::new(env!("CARGO_MANIFEST_DIR")).join("gql");
+    let json = std::fs::read_to_string(gql.join("schema.json")).unwrap();
+    (
+      Schema::parse(&json).unwrap(),
+      Source::read_dir(&gql).unwrap(),
+    )
+  }
+
+  #[test]
+  fn operations_match_the_checked_in_schema() {
+    let (schema, sources) = checked_in();
+    let usage = Usage::collect(&schema, &sources).unwrap();
+    assert_eq!(usage.errors, Vec::<String>::new());
+    assert!(usage.fields.contains("QueryRoot.search"));
+    assert!(usage.fields.contains("Movie.title"));
+    assert_eq!(
+      breaking_changes(&schema, &schema, &usage),
+      Vec::<String>::new()
+    );
+  }
+
+  #[test]
+  fn reports_changes_to_used_fields_only() {
+    let (old, sources) = checked_in();
+    let usage = Usage::collect(&old, &sources).unwrap();
+
+    let mut new = old.clone();
+    let page_info = new.types.get_mut("PageInfo").unwrap();
+    let fields = page_info.fields.as_mut().unwrap();
+    let has_next_page = fields
+      .iter_mut()
+      .find(|field| field.name == "hasNextPage")
+      .unwrap();
+    has_next_page.ty = TypeRef::Named("Boolean".to_string());
+    fields.retain(|field| field.name != "endCursor" && field.name != "startCursor");
+
+    let changes = breaking_changes(&old, &new, &usage);
+    assert!(changes.contains(&"`PageInfo.endCursor` was removed".to_string()));
+    assert!(
+      changes
+        .contains(&"`PageInfo.hasNextPage` changed type from `Boolean!` to `Boolean`".to_string())
+    );
+    assert!(
+      !changes.iter().any(|change| change.contains("startCursor")),
+      "{changes:?}"
+    );
+  }
+
+  #[test]
+  fn outputs_may_become_non_null() {
+    let string = TypeRef::Named("String".to_string());
+    let non_null = TypeRef::NonNull(Box::new(string.clone()));
+    assert!(output_compatible(&string, &non_null));
+    assert!(!output_compatible(&non_null, &string));
+    assert!(!output_compatible(
+      &string,
+      &TypeRef::List(Box::new(string.clone()))
+    ));
+  }
+}
@@ -0,0 +1,131 @@
+//! Keep `gql/schema.json` up to date and check our operations against it.
+//!
+//! ```sh
+//! # introspect the API, rewrite the schema and report what breaks our operations
+//! cargo run -p popcorntime-graphql-client --features schema-tool --bin graphql-schema -- refresh [ENDPOINT]
+//! # validate the operations against the checked-in schema
+//! cargo run -p popcorntime-graphql-client --features schema-tool --bin graphql-schema -- check
+//! ```
+//!
+//! Both list the fields of the schema that no operation selects. The endpoint defaults
+//! to `GRAPHQL_SERVER`.
+
+mod diff;
+mod schema;
+mod usage;
+
+use anyhow::{Context, Result};
+use popcorntime_graphql_client::{client::build_client, consts::GRAPHQL_SERVER};
+use popcorntime_http::HttpConfig;
+use schema::{INTROSPECTION_QUERY, Schema};
+use serde_json::{Value, json};
+use std::{path::Path, process::ExitCode};
+use usage::{Source, Usage};
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+  let args: Vec<String> = std::env::args().skip(1).collect();
+  let result = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+    ["refresh"] => refresh(GRAPHQL_SERVER).await,
+    ["refresh", endpoint] => refresh(endpoint).await,
+    ["check"] => check(),
+    _ => {
+      eprintln!("usage: graphql-schema refresh [ENDPOINT] | check");
+      return ExitCode::from(2);
+    }
+  };
+
+  match result {
+    Ok(true) => ExitCode::SUCCESS,
+    Ok(false) => ExitCode::FAILURE,
+    Err(err) => {
+      eprintln!("error: {err:?}");
+      ExitCode::FAILURE
+    }
+  }
+}
+
+fn gql_dir() -> &'static Path {
+  Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/gql"))
+}
+
+/// Rewrite `schema.json` from the endpoint. Returns whether our operations still work.
+async fn refresh(endpoint: &str) -> Result<bool> {
+  let schema_path = gql_dir().join("schema.json");
+  let sources = Source::read_dir(gql_dir())?;
+
+  let response: Value = build_client(&HttpConfig::default())?
+    .post(endpoint)
+    .json(&json!({
+      "operationName": "IntrospectionQuery",
+      "query": INTROSPECTION_QUERY,
+    }))
+    .send()
+    .await
+    .and_then(|response| response.error_for_status())
+    .with_context(|| format!("failed to introspect {endpoint}"))?
+    .json()
+    .await
+    .context("invalid introspection response")?;
+  let json = serde_json::to_string_pretty(&response)?;
+  let new = Schema::parse(&json)?;
+
+  // an unreadable previous schema is what a refresh is meant to fix, only skip the diff
+  let old = std::fs::read_to_string(&schema_path)
+    .context("failed to read the previous schema")
+    .and_then(|json| Schema::parse(&json));
+  std::fs::write(&schema_path, json).with_context(|| format!("failed to write {schema_path:?}"))?;
+  println!("wrote {}", schema_path.display());
+
+  let breaking = match old {
+    Ok(old) => {
+      let usage = Usage::collect(&old, &sources)?;
+      diff::breaking_changes(&old, &new, &usage)
+    }
+    Err(err) => {
+      eprintln!("warning: not comparing with the previous schema: {err:#}");
+      Vec::new()
+    }
+  };
+  // also catches operations which were already invalid
+  let usage = Usage::collect(&new, &sources)?;
+  print_list("breaking changes affecting the operations", &breaking);
+  print_list("invalid operations", &usage.errors);
+  print_unused(&new, &usage);
+
+  Ok(breaking.is_empty() && usage.errors.is_empty())
+}
+
+/// Validate the operations against `schema.json`. Returns whether they're all valid.
+fn check() -> Result<bool> {
+  let schema_path = gql_dir().join("schema.json");
+  let json = std::fs::read_to_string(&schema_path)
+    .with_context(|| format!("failed to read {schema_path:?}"))?;
+  let schema = Schema::parse(&json)?;
+  let usage = Usage::collect(&schema, &Source::read_dir(gql_dir())?)?;
+
+  print_list("invalid operations", &usage.errors);
+  print_unused(&schema, &usage);
+  Ok(usage.errors.is_empty())
+}
+
+fn print_list(title: &str, items: &[String]) {
+  if items.is_empty() {
+    return;
+  }
+  println!("\n{title}:");
+  for item in items {
+    println!("  - {item}");
+  }
+}
+
+fn print_unused(schema: &Schema, usage: &Usage) {
+  let unused = diff::unused_fields(schema, usage);
+  if unused.is_empty() {
+    return;
+  }
+  println!("\nunused fields:");
+  for (ty, fields) in unused {
+    println!("  {ty}: {}", fields.join(", "));
+  }
+}
@@ -0,0 +1,277 @@
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use std::{collections::BTreeMap, fmt};
+
+/// The query whose response is stored in `gql/schema.json`.
+pub const INTROSPECTION_QUERY: &str = r#"
+query IntrospectionQuery {
+  __schema {
+    queryType { name }
+    mutationType { name }
+    subscriptionType { name }
+    types { ...FullType }
+    directives {
+      name
+      description
+      locations
+      args { ...InputValue }
+    }
+  }
+}
+
+fragment FullType on __Type {
+  kind
+  name
+  description
+  fields(includeDeprecated: true) {
+    name
+    description
+    args { ...InputValue }
+    type { ...TypeRef }
+    isDeprecated
+    deprecationReason
+  }
+  inputFields { ...InputValue }
+  interfaces { ...TypeRef }
+  enumValues(includeDeprecated: true) {
+    name
+    description
+    isDeprecated
+    deprecationReason
+  }
+  possibleTypes { ...TypeRef }
+}
+
+fragment InputValue on __InputValue {
+  name
+  description
+  type { ...TypeRef }
+  defaultValue
+}
+
+fragment TypeRef on __Type {
+  kind
+  name
+  ofType {
+    kind
+    name
+    ofType {
+      kind
+      name
+      ofType {
+        kind
+        name
+        ofType {
+          kind
+          name
+          ofType {
+            kind
+            name
+            ofType {
+              kind
+              name
+              ofType {
+                kind
+                name
+              }
+            }
+          }
+        }
+      }
+    }
+  }
+}
+"#;
+
+/// The parts of an introspection response needed to check our operations.
+#[derive(Debug, Clone)]
+pub struct Schema {
+  pub query_type: String,
+  pub mutation_type: Option<String>,
+  pub subscription_type: Option<String>,
+  pub types: BTreeMap<String, FullType>,
+}
+
+impl Schema {
+  /// Read an introspection response, e.g. the content of `gql/schema.json`.
+  pub fn parse(json: &str) -> Result<Self> {
+    let response: Response = serde_json::from_str(json).context("invalid introspection")?;
+    let Some(data) = response.data else {
+      bail!(
+        "introspection failed: {}",
+        response.errors.unwrap_or_default()
+      );
+    };
+    let schema = data.schema;
+    Ok(Self {
+      query_type: schema.query_type.name,
+      mutation_type: schema.mutation_type.map(|ty| ty.name),
+      subscription_type: schema.subscription_type.map(|ty| ty.name),
+      types: schema
+        .types
+        .into_iter()
+        .map(|ty| (ty.name.clone(), ty))
+        .collect(),
+    })
+  }
+
+  pub fn get(&self, name: &str) -> Option<&FullType> {
+    self.types.get(name)
+  }
+}
+
+#[derive(Deserialize)]
+struct Response {
+  data: Option<Data>,
+  errors: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct Data {
+  #[serde(rename = "__schema")]
+  schema: RawSchema,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawSchema {
+  query_type: Named,
+  mutation_type: Option<Named>,
+  subscription_type: Option<Named>,
+  types: Vec<FullType>,
+}
+
+#[derive(Deserialize)]
+struct Named {
+  name: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Kind {
+  Scalar,
+  Object,
+  Interface,
+  Union,
+  Enum,
+  InputObject,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FullType {
+  pub kind: Kind,
+  pub name: String,
+  pub fields: Option<Vec<Field>>,
+  pub input_fields: Option<Vec<InputValue>>,
+  pub enum_values: Option<Vec<EnumValue>>,
+  pub possible_types: Option<Vec<TypeRef>>,
+}
+
+impl FullType {
+  pub fn field(&self, name: &str) -> Option<&Field> {
+    self
+      .fields
+      .iter()
+      .flatten()
+      .find(|field| field.name == name)
+  }
+
+  pub fn input_field(&self, name: &str) -> Option<&InputValue> {
+    self
+      .input_fields
+      .iter()
+      .flatten()
+      .find(|field| field.name == name)
+  }
+
+  /// The names of the objects implementing an interface or part of a union.
+  pub fn possible_types(&self) -> impl Iterator<Item = &str> {
+    self.possible_types.iter().flatten().map(TypeRef::name)
+  }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Field {
+  pub name: String,
+  pub args: Vec<InputValue>,
+  #[serde(rename = "type")]
+  pub ty: TypeRef,
+}
+
+impl Field {
+  pub fn arg(&self, name: &str) -> Option<&InputValue> {
+    self.args.iter().find(|arg| arg.name == name)
+  }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputValue {
+  pub name: String,
+  #[serde(rename = "type")]
+  pub ty: TypeRef,
+  pub default_value: Option<String>,
+}
+
+impl InputValue {
+  /// Whether the value must be provided, i.e. it's non null without a default.
+  pub fn is_required(&self) -> bool {
+    matches!(self.ty, TypeRef::NonNull(_)) && self.default_value.is_none()
+  }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EnumValue {
+  pub name: String,
+}
+
+/// A possibly wrapped reference to a named type, e.g. `[Media!]!`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "RawTypeRef")]
+pub enum TypeRef {
+  Named(String),
+  List(Box<TypeRef>),
+  NonNull(Box<TypeRef>),
+}
+
+impl TypeRef {
+  /// The name of the innermost type.
+  pub fn name(&self) -> &str {
+    match self {
+      Self::Named(name) => name,
+      Self::List(ty) | Self::NonNull(ty) => ty.name(),
+    }
+  }
+}
+
+impl fmt::Display for TypeRef {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Named(name) => f.write_str(name),
+      Self::List(ty) => write!(f, "[{ty}]"),
+      Self::NonNull(ty) => write!(f, "{ty}!"),
+    }
+  }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawTypeRef {
+  kind: String,
+  name: Option<String>,
+  of_type: Option<Box<TypeRef>>,
+}
+
+impl TryFrom<RawTypeRef> for TypeRef {
+  type Error = String;
+
+  fn try_from(raw: RawTypeRef) -> std::result::Result<Self, Self::Error> {
+    match (raw.kind.as_str(), raw.name, raw.of_type) {
+      ("LIST", _, Some(ty)) => Ok(Self::List(ty)),
+      ("NON_NULL", _, Some(ty)) => Ok(Self::NonNull(ty)),
+      (_, Some(name), _) => Ok(Self::Named(name)),
+      (kind, ..) => Err(format!("incomplete `{kind}` type reference")),
+    }
+  }
+}
@@ -0,0 +1,265 @@
+use crate::schema::{FullType, Kind, Schema};
+use anyhow::{Context, Result};
+use graphql_parser::query::{
+  Definition, Document, FragmentDefinition, OperationDefinition, Selection, SelectionSet, Type,
+  TypeCondition, VariableDefinition,
+};
+use std::{
+  collections::{BTreeSet, HashMap},
+  path::{Path, PathBuf},
+};
+
+/// A `.graphql` file of the crate.
+pub struct Source {
+  pub path: PathBuf,
+  pub content: String,
+}
+
+impl Source {
+  /// Read the operations of `gql`, i.e. every `.graphql` file.
+  pub fn read_dir(dir: &Path) -> Result<Vec<Self>> {
+    let mut sources = Vec::new();
+    for entry in std::fs::read_dir(dir).with_context(|| format!("failed to read {dir:?}"))? {
+      let path = entry?.path();
+      if path.extension().is_some_and(|ext| ext == "graphql") {
+        let content =
+          std::fs::read_to_string(&path).with_context(|| format!("failed to read {path:?}"))?;
+        sources.push(Self { path, content });
+      }
+    }
+    sources.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(sources)
+  }
+}
+
+/// The parts of the schema our operations depend on, as schema coordinates, e.g.
+/// `Media.title` or `QueryRoot.search(country:)`.
+#[derive(Debug, Default)]
+pub struct Usage {
+  pub types: BTreeSet<String>,
+  pub fields: BTreeSet<String>,
+  pub arguments: BTreeSet<String>,
+  /// Operations which aren't valid against the schema.
+  pub errors: Vec<String>,
+}
+
+impl Usage {
+  /// Resolve the operations of `sources` against `schema`.
+  pub fn collect(schema: &Schema, sources: &[Source]) -> Result<Self> {
+    let mut usage = Self::default();
+    for source in sources {
+      let document = graphql_parser::parse_query::<&str>(&source.content)
+        .with_context(|| format!("failed to parse {:?}", source.path))?;
+      Walker {
+        schema,
+        file: file_name(&source.path),
+        fragments: fragments(&document),
+        usage: &mut usage,
+      }
+      .document(&document);
+    }
+    Ok(usage)
+  }
+}
+
+fn file_name(path: &Path) -> String {
+  path
+    .file_name()
+    .map(|name| name.to_string_lossy().into_owned())
+    .unwrap_or_default()
+}
+
+fn fragments<'d, 'a>(
+  document: &'d Document<'a, &'a str>,
+) -> HashMap<&'a str, &'d FragmentDefinition<'a, &'a str>> {
+  document
+    .definitions
+    .iter()
+    .filter_map(|definition| match definition {
+      Definition::Fragment(fragment) => Some((fragment.name, fragment)),
+      Definition::Operation(_) => None,
+    })
+    .collect()
+}
+
+struct Walker<'s, 'd, 'a> {
+  schema: &'s Schema,
+  file: String,
+  fragments: HashMap<&'a str, &'d FragmentDefinition<'a, &'a str>>,
+  usage: &'s mut Usage,
+}
+
+impl<'d, 'a> Walker<'_, 'd, 'a> {
+  fn document(&mut self, document: &'d Document<'a, &'a str>) {
+    for definition in &document.definitions {
+      let Definition::Operation(operation) = definition else {
+        // fragments are walked where they are spread
+        continue;
+      };
+      let (root, variables, selection_set) = match operation {
+        OperationDefinition::SelectionSet(selection_set) => {
+          (Some(&self.schema.query_type), &[][..], selection_set)
+        }
+        OperationDefinition::Query(query) => (
+          Some(&self.schema.query_type),
+          &query.variable_definitions[..],
+          &query.selection_set,
+        ),
+        OperationDefinition::Mutation(mutation) => (
+          self.schema.mutation_type.as_ref(),
+          &mutation.variable_definitions[..],
+          &mutation.selection_set,
+        ),
+        OperationDefinition::Subscription(subscription) => (
+          self.schema.subscription_type.as_ref(),
+          &subscription.variable_definitions[..],
+          &subscription.selection_set,
+        ),
+      };
+      let Some(root) = root else {
+        self.error(format!(
+          "the schema has no root type for `{}`",
+          operation_name(operation)
+        ));
+        continue;
+      };
+      for variable in variables {
+        self.variable(variable);
+      }
+      self.selection_set(root, selection_set, &mut Vec::new());
+    }
+  }
+
+  fn error(&mut self, message: String) {
+    self.usage.errors.push(format!("{}: {message}", self.file));
+  }
+
+  /// Record an input type, and everything it may contain.
+  fn variable(&mut self, variable: &VariableDefinition<'a, &'a str>) {
+    let mut ty = &variable.var_type;
+    let name = loop {
+      match ty {
+        Type::NamedType(name) => break *name,
+        Type::ListType(inner) | Type::NonNullType(inner) => ty = inner,
+      }
+    };
+    if self.schema.get(name).is_none() {
+      self.error(format!(
+        "unknown type `{name}` of variable `${}`",
+        variable.name
+      ));
+      return;
+    }
+    self.input_type(name);
+  }
+
+  fn input_type(&mut self, name: &str) {
+    if !self.usage.types.insert(name.to_string()) {
+      return;
+    }
+    let Some(ty) = self.schema.get(name) else {
+      return;
+    };
+    for field in ty.input_fields.iter().flatten() {
+      self.usage.fields.insert(format!("{name}.{}", field.name));
+      self.input_type(field.ty.name());
+    }
+  }
+
+  fn selection_set(
+    &mut self,
+    ty: &str,
+    selection_set: &'d SelectionSet<'a, &'a str>,
+    spreads: &mut Vec<&'a str>,
+  ) {
+    let schema = self.schema;
+    let Some(parent) = schema.get(ty) else {
+      self.error(format!("unknown type `{ty}`"));
+      return;
+    };
+    self.usage.types.insert(ty.to_string());
+
+    for selection in &selection_set.items {
+      match selection {
+        Selection::Field(field) => {
+          if field.name.starts_with("__") {
+            continue;
+          }
+          let Some(definition) = parent.field(field.name) else {
+            self.error(format!("unknown field `{ty}.{}`", field.name));
+            continue;
+          };
+          let arguments: Vec<_> = field.arguments.iter().map(|(arg, _)| *arg).collect();
+          self.field(parent, field.name, &arguments);
+
+          for arg in &arguments {
+            if definition.arg(arg).is_none() {
+              self.error(format!("unknown argument `{ty}.{}({arg}:)`", field.name));
+            }
+          }
+          for arg in definition.args.iter().filter(|arg| arg.is_required()) {
+            if !arguments.contains(&arg.name.as_str()) {
+              self.error(format!(
+                "missing required argument `{ty}.{}({}:)`",
+                field.name, arg.name
+              ));
+            }
+          }
+
+          let field_type = definition.ty.name();
+          if field.selection_set.items.is_empty() {
+            self.usage.types.insert(field_type.to_string());
+          } else {
+            self.selection_set(field_type, &field.selection_set, spreads);
+          }
+        }
+        Selection::FragmentSpread(spread) => {
+          let Some(fragment) = self.fragments.get(spread.fragment_name).copied() else {
+            self.error(format!("unknown fragment `{}`", spread.fragment_name));
+            continue;
+          };
+          // a fragment spreading itself is invalid, don't loop forever
+          if spreads.contains(&fragment.name) {
+            continue;
+          }
+          spreads.push(fragment.name);
+          let TypeCondition::On(condition) = fragment.type_condition;
+          self.selection_set(condition, &fragment.selection_set, spreads);
+          spreads.pop();
+        }
+        Selection::InlineFragment(fragment) => {
+          let condition = match fragment.type_condition {
+            Some(TypeCondition::On(condition)) => condition,
+            None => ty,
+          };
+          self.selection_set(condition, &fragment.selection_set, spreads);
+        }
+      }
+    }
+  }
+
+  /// Record a field and its arguments, selected on an interface they're also used on
+  /// every implementation.
+  fn field(&mut self, parent: &FullType, name: &str, arguments: &[&str]) {
+    let implementations = match parent.kind {
+      Kind::Interface => parent.possible_types().collect(),
+      _ => Vec::new(),
+    };
+    for ty in std::iter::once(parent.name.as_str()).chain(implementations) {
+      self.usage.fields.insert(format!("{ty}.{name}"));
+      for arg in arguments {
+        self.usage.arguments.insert(format!("{ty}.{name}({arg}:)"));
+      }
+    }
+  }
+}
+
+fn operation_name<'a>(operation: &OperationDefinition<'a, &'a str>) -> String {
+  let name = match operation {
+    OperationDefinition::SelectionSet(_) => None,
+    OperationDefinition::Query(query) => query.name,
+    OperationDefinition::Mutation(mutation) => mutation.name,
+    OperationDefinition::Subscription(subscription) => subscription.name,
+  };
+  name.unwrap_or("anonymous").to_string()
+}
@@ -0,0 +1,373 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::{
+  path::{Path, PathBuf},
+  time::{Duration, SystemTime},
+};
+use time::OffsetDateTime;
+
+/// How a query uses the [`ResponseCache`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CachePolicy {
+  /// Return a cached response younger than `ttl`, otherwise fetch it.
+  CacheFirst { ttl: Duration },
+  /// Always fetch, fall back to any cached response if the request fails.
+  NetworkFirst,
+  /// Return any cached response right away and refresh it in the background once
+  /// it is older than `ttl`.
+  StaleWhileRevalidate { ttl: Duration },
+  /// Never read nor write the cache.
+  #[default]
+  NetworkOnly,
+}
+
+/// A cached response, stored as JSON in the cache directory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheEntry {
+  #[serde(with = "time::serde::rfc3339")]
+  pub fetched_at: OffsetDateTime,
+  #[serde(default)]
+  pub variables: Value,
+  pub data: Value,
+}
+
+impl CacheEntry {
+  pub fn is_fresh(&self, ttl: Duration) -> bool {
+    OffsetDateTime::now_utc() - self.fetched_at < ttl
+  }
+}
+
+/// Identifies a cached response by operation name, normalized variables and the user
+/// it was fetched for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheKey {
+  operation: &'static str,
+  variables: Value,
+  hash: String,
+}
+
+impl CacheKey {
+  pub fn new(operation: &'static str, variables: &Value) -> Self {
+    Self::scoped(operation, variables, None)
+  }
+
+  /// A key of the responses fetched for `user`, never served to another user.
+  pub fn scoped(operation: &'static str, variables: &Value, user: Option<&str>) -> Self {
+    let variables = normalize(variables.clone());
+    let mut hash = Sha256::new();
+    if let Some(user) = user {
+      hash.update(user.as_bytes());
+      hash.update([0]);
+    }
+    hash.update(variables.to_string().as_bytes());
+    Self {
+      operation,
+      variables,
+      hash: hex::encode(hash.finalize()),
+    }
+  }
+
+  pub fn operation(&self) -> &'static str {
+    self.operation
+  }
+
+  fn file_name(&self) -> String {
+    format!("{}-{}.json", self.operation, self.hash)
+  }
+}
+
+impl std::hash::Hash for CacheKey {
+  fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+    self.operation.hash(state);
+    self.hash.hash(state);
+  }
+}
+
+/// Sort object keys and drop `null` values, so that equivalent variables share an entry.
+fn normalize(value: Value) -> Value {
+  match value {
+    Value::Object(map) => {
+      let mut entries: Vec<_> = map
+        .into_iter()
+        .filter(|(_, value)| !value.is_null())
+        .map(|(key, value)| (key, normalize(value)))
+        .collect();
+      entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+      Value::Object(entries.into_iter().collect::<Map<_, _>>())
+    }
+    Value::Array(values) => Value::Array(values.into_iter().map(normalize).collect()),
+    value => value,
+  }
+}
+
+/// Persistent cache of GraphQL responses, one file per entry.
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+  dir: PathBuf,
+}
+
+impl ResponseCache {
+  pub fn new(dir: &Path) -> Result<Self> {
+    std::fs::create_dir_all(dir)?;
+    Ok(Self {
+      dir: dir.to_path_buf(),
+    })
+  }
+
+  pub fn dir(&self) -> &Path {
+    &self.dir
+  }
+
+  pub async fn get(&self, key: &CacheKey) -> Option<CacheEntry> {
+    let content = tokio::fs::read(self.dir.join(key.file_name())).await.ok()?;
+    match serde_json::from_slice(&content) {
+      Ok(entry) => Some(entry),
+      Err(err) => {
+        tracing::warn!(operation = key.operation, "invalid cache entry: {err}");
+        None
+      }
+    }
+  }
+
+  pub async fn put(&self, key: &CacheKey, data: Value) -> Result<()> {
+    let entry = CacheEntry {
+      fetched_at: OffsetDateTime::now_utc(),
+      variables: key.variables.clone(),
+      data,
+    };
+    write_entry(&self.dir.join(key.file_name()), &entry).await
+  }
+
+  /// Modify the cached responses of `operation` fetched for `user` in place, e.g. to
+  /// reflect a mutation which hasn't reached the server yet.
+  ///
+  /// `update` receives the variables and data of each entry and returns whether it
+  /// changed the data.
+  pub async fn update(
+    &self,
+    operation: &'static str,
+    user: Option<&str>,
+    mut update: impl FnMut(&Value, &mut Value) -> bool,
+  ) -> Result<()> {
+    for (path, mut entry) in self.entries_with_path(operation, user).await? {
+      if update(&entry.variables, &mut entry.data) {
+        write_entry(&path, &entry).await?;
+      }
+    }
+    Ok(())
+  }
+
+  /// All cached responses of `operation` fetched for `user`.
+  pub async fn entries(
+    &self,
+    operation: &'static str,
+    user: Option<&str>,
+  ) -> Result<Vec<CacheEntry>> {
+    Ok(
+      self
+        .entries_with_path(operation, user)
+        .await?
+        .into_iter()
+        .map(|(_, entry)| entry)
+        .collect(),
+    )
+  }
+
+  async fn entries_with_path(
+    &self,
+    operation: &'static str,
+    user: Option<&str>,
+  ) -> Result<Vec<(PathBuf, CacheEntry)>> {
+    let prefix = format!("{operation}-");
+    let mut entries = tokio::fs::read_dir(&self.dir).await?;
+    let mut result = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+      let file_name = entry.file_name();
+      if !file_name
+        .to_str()
+        .is_some_and(|file_name| file_name.starts_with(&prefix) && file_name.ends_with(".json"))
+      {
+        continue;
+      }
+      let content = tokio::fs::read(entry.path()).await?;
+      let Ok(cached) = serde_json::from_slice::<CacheEntry>(&content) else {
+        continue;
+      };
+      // the entries of other users are never read, nor written, on behalf of `user`
+      let key = CacheKey::scoped(operation, &cached.variables, user);
+      if file_name.to_str() == Some(key.file_name().as_str()) {
+        result.push((entry.path(), cached));
+      }
+    }
+    Ok(result)
+  }
+
+  /// Remove all cached responses of `operation`.
+  pub async fn invalidate(&self, operation: &str) -> Result<()> {
+    let prefix = format!("{operation}-");
+    self
+      .remove_where(|file_name| file_name.starts_with(&prefix))
+      .await
+  }
+
+  /// Remove all cached responses.
+  pub async fn clear(&self) -> Result<()> {
+    self.remove_where(|_| true).await
+  }
+
+  /// The size of the cached responses, in bytes.
+  pub async fn size(&self) -> Result<u64> {
+    Ok(self.files().await?.iter().map(|(_, size, _)| size).sum())
+  }
+
+  /// Remove the least recently written responses until they take at most `max_size` bytes.
+  pub async fn trim(&self, max_size: u64) -> Result<()> {
+    let mut files = self.files().await?;
+    let mut size: u64 = files.iter().map(|(_, size, _)| size).sum();
+    files.sort_unstable_by_key(|(_, _, modified)| *modified);
+    for (path, file_size, _) in files {
+      if size <= max_size {
+        break;
+      }
+      // being written, renamed to the entry once complete
+      if path.extension().is_some_and(|extension| extension == "tmp") {
+        continue;
+      }
+      tokio::fs::remove_file(path).await?;
+      size -= file_size;
+    }
+    Ok(())
+  }
+
+  async fn files(&self) -> Result<Vec<(PathBuf, u64, SystemTime)>> {
+    let mut entries = tokio::fs::read_dir(&self.dir).await?;
+    let mut files = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+      let metadata = entry.metadata().await?;
+      if metadata.is_file() {
+        let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+        files.push((entry.path(), metadata.len(), modified));
+      }
+    }
+    Ok(files)
+  }
+
+  async fn remove_where(&self, predicate: impl Fn(&str) -> bool) -> Result<()> {
+    let mut entries = tokio::fs::read_dir(&self.dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+      let file_name = entry.file_name();
+      let Some(file_name) = file_name.to_str() else {
+        continue;
+      };
+      if predicate(file_name) {
+        tokio::fs::remove_file(entry.path()).await?;
+      }
+    }
+    Ok(())
+  }
+}
+
+/// Write to a temporary file first, so readers never see a partial entry. The name is
+/// unique as revalidations and mutations may write the same entry at once.
+async fn write_entry(path: &Path, entry: &CacheEntry) -> Result<()> {
+  let tmp_path = path.with_extension(format!("{:016x}.tmp", fastrand::u64(..)));
+  tokio::fs::write(&tmp_path, serde_json::to_vec(entry)?).await?;
+  tokio::fs::rename(tmp_path, path).await?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn equivalent_variables_share_a_key() {
+    let a = CacheKey::new("Search", &json!({ "country": "US", "query": "alien" }));
+    let b = CacheKey::new(
+      "Search",
+      &json!({ "query": "alien", "after": null, "country": "US" }),
+    );
+    assert_eq!(a, b);
+  }
+
+  #[test]
+  fn different_variables_or_operations_do_not_share_a_key() {
+    let a = CacheKey::new("Search", &json!({ "country": "US" }));
+    assert_ne!(a, CacheKey::new("Search", &json!({ "country": "FR" })));
+    assert_ne!(a, CacheKey::new("Providers", &json!({ "country": "US" })));
+  }
+
+  #[test]
+  fn users_do_not_share_a_key() {
+    let variables = json!({ "country": "US" });
+    let a = CacheKey::scoped("Preferences", &variables, Some("a"));
+    assert_eq!(a, CacheKey::scoped("Preferences", &variables, Some("a")));
+    assert_ne!(a, CacheKey::scoped("Preferences", &variables, Some("b")));
+    assert_ne!(a, CacheKey::new("Preferences", &variables));
+  }
+
+  #[tokio::test]
+  async fn updates_only_the_entries_of_the_user() {
+    let dir = std::env::temp_dir().join(format!("popcorntime-cache-users-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let cache = ResponseCache::new(&dir).unwrap();
+    let variables = json!({});
+    let alice = CacheKey::scoped("Preferences", &variables, Some("alice"));
+    let bob = CacheKey::scoped("Preferences", &variables, Some("bob"));
+    cache.put(&alice, json!({ "country": "US" })).await.unwrap();
+    cache.put(&bob, json!({ "country": "US" })).await.unwrap();
+
+    cache
+      .update("Preferences", Some("alice"), |_, data| {
+        data["country"] = json!("FR");
+        true
+      })
+      .await
+      .unwrap();
+    assert_eq!(cache.get(&alice).await.unwrap().data["country"], "FR");
+    assert_eq!(cache.get(&bob).await.unwrap().data["country"], "US");
+    assert_eq!(
+      cache
+        .entries("Preferences", Some("bob"))
+        .await
+        .unwrap()
+        .len(),
+      1
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+  }
+
+  #[tokio::test]
+  async fn trim_removes_the_oldest_responses() {
+    let dir = std::env::temp_dir().join(format!("popcorntime-cache-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let cache = ResponseCache::new(&dir).unwrap();
+    let old = CacheKey::new("Search", &json!({ "country": "US" }));
+    let new = CacheKey::new("Search", &json!({ "country": "FR" }));
+    cache.put(&old, json!({ "medias": [] })).await.unwrap();
+    std::fs::File::options()
+      .write(true)
+      .open(dir.join(old.file_name()))
+      .and_then(|file| file.set_modified(SystemTime::UNIX_EPOCH))
+      .unwrap();
+    cache.put(&new, json!({ "medias": [] })).await.unwrap();
+    let tmp = dir.join("written.0000000000000000.tmp");
+    std::fs::write(&tmp, "{}").unwrap();
+    std::fs::File::options()
+      .write(true)
+      .open(&tmp)
+      .and_then(|file| file.set_modified(SystemTime::UNIX_EPOCH))
+      .unwrap();
+
+    let size = cache.size().await.unwrap();
+    cache.trim(size - 1).await.unwrap();
+    assert!(cache.get(&old).await.is_none());
+    assert!(cache.get(&new).await.is_some());
+    assert!(tmp.exists());
+    let _ = std::fs::remove_dir_all(&dir);
+  }
+}
@@ -1,83 +1,789 @@
-use crate::consts::GRAPHQL_SERVER;
-use anyhow::Result;
+use crate::{
+  cache::{CacheEntry, CacheKey, CachePolicy, ResponseCache},
+  coalesce::InFlight,
+  consts::{GRAPHQL_MIRRORS, GRAPHQL_SERVER},
+  credentials::CredentialsProvider,
+  endpoints::Endpoints,
+  error::{GraphqlResponse, Origin, is_unauthenticated},
+  limiter::{Limiter, OperationLimits, Permit, Priority},
+  metrics::{self, CacheStatus, Metrics},
+  offline::Connectivity,
+  optimistic,
+  outbox::{self, Outbox, OutboxPolicy},
+  persisted::{
+    Document, PersistedQueries, PersistedQuery, PersistedQueryError, persisted_query_error,
+  },
+  retry::{RetryPolicy, RetryReason, retry_after},
+  subscription::Subscriptions,
+  trace::TraceParent,
+};
+use anyhow::{Context, Result};
 use graphql_client::{QueryBody, Response};
-use reqwest::header;
+use popcorntime_error::{AnyhowContextExt, Code};
+use popcorntime_http::HttpConfig;
+use reqwest::{Method, StatusCode};
 use serde::{Serialize, de::DeserializeOwned};
-use std::{fmt::Debug, sync::Arc, time::Duration};
-use tokio::{runtime::Handle, sync::Mutex};
+use serde_json::Value;
+use std::{
+  collections::HashSet,
+  fmt::Debug,
+  sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+  },
+};
+use tracing::{Span, field, instrument};
 
-static USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"),);
+pub(crate) static USER_AGENT: &str =
+  concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"),);
 
 #[derive(Clone)]
 pub struct ApiClient {
-  client: Arc<Mutex<reqwest::Client>>,
-  url: String,
+  client: reqwest::Client,
+  pub(crate) credentials: Arc<dyn CredentialsProvider>,
+  pub(crate) endpoints: Endpoints,
+  pub(crate) retry_policy: RetryPolicy,
+  cache: Option<ResponseCache>,
+  connectivity: Connectivity,
+  outbox: Option<Outbox>,
+  in_flight: InFlight,
+  limiter: Limiter,
+  priority: Priority,
+  metrics: Metrics,
+  on_session_invalid: Option<Arc<dyn Fn() -> Result<()> + Send + Sync>>,
+  /// Held while the session is refreshed, see [`ApiClient::reauthenticate`].
+  refreshing: Arc<tokio::sync::Mutex<()>>,
+  /// Stale responses being revalidated in background.
+  revalidating: Arc<std::sync::Mutex<HashSet<CacheKey>>>,
+  persisted_queries: PersistedQueries,
+  /// Cleared once the server answered `PersistedQueryNotSupported`.
+  persisted_queries_supported: Arc<AtomicBool>,
+  pub(crate) subscriptions: Subscriptions,
+  pub(crate) http_config: Arc<HttpConfig>,
 }
 
-impl Debug for ApiClient {
-  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-    write!(f, "ApiClient")
+/// The kind of GraphQL operation sent with [`ApiClient::query`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OperationKind {
+  #[default]
+  Query,
+  /// Mutations are only retried when applying them twice has the same effect as once.
+  Mutation { idempotent: bool },
+}
+
+impl OperationKind {
+  pub fn is_retryable(&self) -> bool {
+    matches!(self, Self::Query | Self::Mutation { idempotent: true })
   }
 }
 
-pub fn build_client(access_token: Option<String>) -> Result<reqwest::Client> {
-  let mut headers = header::HeaderMap::new();
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequestOptions {
+  pub kind: OperationKind,
+  /// Ask intermediate HTTP caches to revalidate the response.
+  pub disable_cache: bool,
+  /// How the response cache is used, ignored unless the client has a cache.
+  pub cache: CachePolicy,
+  /// Operations whose cached responses are removed once this operation succeeded.
+  pub invalidates: &'static [&'static str],
+  /// Queue the mutation in the outbox when the API is unreachable.
+  pub outbox: Option<OutboxPolicy>,
+  /// Limits on top of the maximum number of requests in flight of the client.
+  pub limits: OperationLimits,
+  /// Send the hash of the document rather than the document, see [`PersistedQueries`].
+  pub persisted: Option<PersistedQuery>,
+}
 
-  if let Some(access_token) = access_token {
-    let mut auth_value = header::HeaderValue::from_str(&format!("Bearer {}", access_token))?;
-    auth_value.set_sensitive(true);
-    headers.insert(header::AUTHORIZATION, auth_value);
+impl Debug for ApiClient {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "ApiClient")
   }
+}
 
-  reqwest::ClientBuilder::new()
-    .default_headers(headers)
+pub fn build_client(config: &HttpConfig) -> Result<reqwest::Client> {
+  config
+    .client_builder()?
     .user_agent(USER_AGENT)
-    .timeout(Duration::from_secs(5))
     .build()
     .map_err(Into::into)
 }
 
 impl ApiClient {
-  pub fn new(access_token: Option<String>) -> Result<Self> {
-    let client = build_client(access_token)?;
+  pub fn new(credentials: impl CredentialsProvider + 'static) -> Result<Self> {
     Ok(Self {
-      url: GRAPHQL_SERVER.to_string(),
-      client: Arc::new(Mutex::new(client)),
+      endpoints: Endpoints::new(GRAPHQL_SERVER).with_mirrors(
+        GRAPHQL_MIRRORS
+          .unwrap_or_default()
+          .split(',')
+          .map(str::trim)
+          .filter(|url| !url.is_empty()),
+      ),
+      client: build_client(&HttpConfig::default())?,
+      credentials: Arc::new(credentials),
+      retry_policy: RetryPolicy::default(),
+      cache: None,
+      connectivity: Connectivity::default(),
+      outbox: None,
+      in_flight: InFlight::default(),
+      limiter: Limiter::default(),
+      priority: Priority::default(),
+      metrics: Metrics::default(),
+      on_session_invalid: None,
+      refreshing: Arc::default(),
+      revalidating: Arc::default(),
+      persisted_queries: PersistedQueries::default(),
+      persisted_queries_supported: Arc::new(AtomicBool::new(true)),
+      subscriptions: Subscriptions::default(),
+      http_config: Arc::default(),
     })
   }
 
+  /// Send the requests to `url` instead of `GRAPHQL_SERVER`, e.g. a local mock.
+  pub fn with_url(mut self, url: impl Into<String>) -> Self {
+    self.endpoints = Endpoints::new(url);
+    self
+  }
+
+  /// Send the requests to the first healthy endpoint of `endpoints`.
+  pub fn with_endpoints(mut self, endpoints: Endpoints) -> Self {
+    self.endpoints = endpoints;
+    self
+  }
+
+  pub fn endpoints(&self) -> &Endpoints {
+    &self.endpoints
+  }
+
+  /// Send the requests through the proxy and with the certificates of `config`.
+  pub fn with_http_config(mut self, config: &HttpConfig) -> Result<Self> {
+    self.client = build_client(config)?;
+    self.http_config = Arc::new(config.clone());
+    Ok(self)
+  }
+
+  /// Limit the number of requests sent at once, 6 by default.
+  pub fn with_max_in_flight(mut self, max_in_flight: usize) -> Self {
+    self.limiter = Limiter::new(max_in_flight);
+    self
+  }
+
+  /// Send the requests with `priority`, the clients are cheap to clone and share their
+  /// limits.
+  pub fn with_priority(mut self, priority: Priority) -> Self {
+    self.priority = priority;
+    self
+  }
+
+  /// Replace the policy used to retry transient failures.
+  pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+    self.retry_policy = retry_policy;
+    self
+  }
+
+  /// Change how the operations are sent as persisted queries.
+  pub fn with_persisted_queries(mut self, persisted_queries: PersistedQueries) -> Self {
+    self.persisted_queries = persisted_queries;
+    self
+  }
+
+  /// Persist responses in `cache` according to the [`CachePolicy`] of each operation.
+  pub fn with_cache(mut self, cache: ResponseCache) -> Self {
+    self.cache = Some(cache);
+    self
+  }
+
+  pub fn cache(&self) -> Option<&ResponseCache> {
+    self.cache.as_ref()
+  }
+
+  /// Queue mutations in `outbox` while the API is unreachable.
+  pub fn with_outbox(mut self, outbox: Outbox) -> Self {
+    self.outbox = Some(outbox);
+    self
+  }
+
+  pub fn outbox(&self) -> Option<&Outbox> {
+    self.outbox.as_ref()
+  }
+
+  /// The recent operations, see [`Metrics`].
+  pub fn metrics(&self) -> &Metrics {
+    &self.metrics
+  }
+
+  pub fn connectivity(&self) -> &Connectivity {
+    &self.connectivity
+  }
+
+  /// Register a callback invoked when the server rejected the access token and
+  /// the session could not be refreshed.
+  pub fn with_on_session_invalid(
+    mut self,
+    on_session_invalid: impl Fn() -> Result<()> + Send + Sync + 'static,
+  ) -> Self {
+    self.on_session_invalid = Some(Arc::new(on_session_invalid));
+    self
+  }
+
   pub async fn query<T: Serialize, R: DeserializeOwned>(
     &self,
     params: &QueryBody<T>,
-    disable_cache: bool,
-  ) -> anyhow::Result<Response<R>> {
-    let res = self.post(disable_cache).await.json(params).send().await?;
-    res.json().await.map_err(Into::into)
+    options: RequestOptions,
+  ) -> anyhow::Result<GraphqlResponse<R>> {
+    let body = serde_json::to_value(params).context(Code::GraphqlServerError)?;
+    let operation = params.operation_name;
+    let execute = async move {
+      if options.kind == OperationKind::Query {
+        // identical concurrent queries of the same user share one request
+        let user = self.credentials.user().await;
+        let key = CacheKey::scoped(operation, &body["variables"], user.as_deref());
+        self
+          .in_flight
+          .run(key, self.execute(operation, body, options))
+          .await
+      } else {
+        self.execute(operation, body, options).await
+      }
+    };
+    let response = self.metrics.measure(operation, execute).await?;
+    response
+      .try_map(serde_json::from_value)
+      .context(Code::GraphqlServerError)
   }
 
-  async fn post(&self, disable_cache: bool) -> reqwest::RequestBuilder {
-    if disable_cache {
-      self
-        .client
+  #[instrument(
+    skip(self, body, options),
+    fields(
+      cache = field::Empty,
+      attempts = field::Empty,
+      reauthenticated = field::Empty
+    )
+  )]
+  async fn execute(
+    &self,
+    operation: &'static str,
+    body: Value,
+    options: RequestOptions,
+  ) -> Result<GraphqlResponse<Value>> {
+    if let (Some(outbox), Some(policy)) = (&self.outbox, options.outbox) {
+      return self
+        .execute_with_outbox(outbox, policy, operation, body, options)
+        .await;
+    }
+
+    let (Some(cache), policy) = (&self.cache, options.cache) else {
+      return self.fetch_and_invalidate(&body, options).await;
+    };
+    if policy == CachePolicy::NetworkOnly {
+      return self.fetch_and_invalidate(&body, options).await;
+    }
+
+    let user = self.credentials.user().await;
+    let key = CacheKey::scoped(operation, &body["variables"], user.as_deref());
+    let entry = cache.get(&key).await;
+    match (policy, entry) {
+      (CachePolicy::CacheFirst { ttl }, Some(entry)) if entry.is_fresh(ttl) => {
+        record_cache(CacheStatus::Hit);
+        Ok(cached(entry, false))
+      }
+      (CachePolicy::StaleWhileRevalidate { ttl }, Some(entry)) => {
+        let stale = !entry.is_fresh(ttl);
+        if stale {
+          record_cache(CacheStatus::Stale);
+          self.revalidate_in_background(cache, key, body, options);
+        } else {
+          record_cache(CacheStatus::Hit);
+        }
+        Ok(cached(entry, stale))
+      }
+      (policy, entry) => {
+        record_cache(CacheStatus::Miss);
+        match self.fetch_and_store(cache, &key, &body, options).await {
+          Ok(response) => Ok(response.into()),
+          // serve the last known response when the API is unreachable
+          Err(err) if policy == CachePolicy::NetworkFirst || is_offline(&err) => match entry {
+            Some(entry) => {
+              tracing::warn!(operation, "serving cached response: {:?}", err);
+              record_cache(CacheStatus::Fallback);
+              Ok(cached(entry, true))
+            }
+            None => Err(err),
+          },
+          Err(err) => Err(err),
+        }
+      }
+    }
+  }
+
+  /// Fetch a stale response again, once however many times it is read meanwhile.
+  fn revalidate_in_background(
+    &self,
+    cache: &ResponseCache,
+    key: CacheKey,
+    body: Value,
+    options: RequestOptions,
+  ) {
+    let mut revalidating = self
+      .revalidating
+      .lock()
+      .unwrap_or_else(|err| err.into_inner());
+    if !revalidating.insert(key.clone()) {
+      return;
+    }
+    drop(revalidating);
+
+    let client = self.clone().with_priority(Priority::Background);
+    let cache = cache.clone();
+    tokio::spawn(async move {
+      if let Err(err) = client.fetch_and_store(&cache, &key, &body, options).await {
+        tracing::warn!(
+          operation = key.operation(),
+          "failed to revalidate cached response: {:?}",
+          err
+        );
+      }
+      client
+        .revalidating
         .lock()
-        .await
-        .post(&self.url)
-        .header("Cache-Control", "no-cache")
-    } else {
-      self.client.lock().await.post(&self.url)
+        .unwrap_or_else(|err| err.into_inner())
+        .remove(&key);
+    });
+  }
+
+  async fn execute_with_outbox(
+    &self,
+    outbox: &Outbox,
+    policy: OutboxPolicy,
+    operation: &'static str,
+    body: Value,
+    options: RequestOptions,
+  ) -> Result<GraphqlResponse<Value>> {
+    // mutations are applied in order, so earlier ones have to be sent first
+    if !outbox.is_empty().await {
+      self.replay_outbox().await?;
+    }
+    if outbox.is_empty().await {
+      match self.fetch_and_invalidate(&body, options).await {
+        Err(err) if outbox::can_queue(&err, options.kind) => {}
+        result => return result,
+      }
+    }
+
+    tracing::info!(operation, "API unavailable, queueing mutation");
+    let variables = body["variables"].clone();
+    let user = self.credentials.user().await;
+    outbox
+      .push(operation, body, options, policy, user.clone())
+      .await?;
+    if let Some(cache) = &self.cache
+      && let Err(err) = optimistic::apply(cache, operation, &variables, user.as_deref()).await
+    {
+      tracing::warn!(operation, "failed to update cached responses: {:?}", err);
+    }
+
+    Ok(GraphqlResponse {
+      data: optimistic::response(operation, &variables),
+      errors: Vec::new(),
+      origin: Origin::Queued,
+    })
+  }
+
+  async fn fetch_and_invalidate(
+    &self,
+    body: &Value,
+    options: RequestOptions,
+  ) -> Result<GraphqlResponse<Value>> {
+    let response = self.fetch(body, options).await?;
+    self.invalidate(&response, options).await;
+    Ok(response.into())
+  }
+
+  async fn fetch_and_store(
+    &self,
+    cache: &ResponseCache,
+    key: &CacheKey,
+    body: &Value,
+    options: RequestOptions,
+  ) -> Result<Response<Value>> {
+    let response = self.fetch(body, options).await?;
+    if let Some(data) = response.data.as_ref().filter(|_| is_complete(&response))
+      && let Err(err) = cache.put(key, data.clone()).await
+    {
+      tracing::warn!(
+        operation = key.operation(),
+        "failed to cache response: {:?}",
+        err
+      );
     }
+    Ok(response)
   }
 
-  // this run in the `watch_config_in_background` thread
-  pub fn update_access_token(&self, access_token: Option<String>) -> Result<()> {
-    tokio::task::block_in_place(move || {
-      Handle::current().block_on(async move {
-        let mut client = self.client.lock().await;
-        if let Ok(new_client) = build_client(access_token) {
-          *client = new_client;
+  /// Remove the cached responses made outdated by a successful mutation.
+  async fn invalidate(&self, response: &Response<Value>, options: RequestOptions) {
+    let Some(cache) = &self.cache else {
+      return;
+    };
+    if !is_complete(response) {
+      return;
+    }
+    for operation in options.invalidates {
+      if let Err(err) = cache.invalidate(operation).await {
+        tracing::warn!(
+          operation,
+          "failed to invalidate cached responses: {:?}",
+          err
+        );
+      }
+    }
+  }
+
+  /// Send the request, refreshing the session and replaying it once if the server
+  /// rejected the access token.
+  pub(crate) async fn fetch(
+    &self,
+    body: &Value,
+    options: RequestOptions,
+  ) -> Result<Response<Value>> {
+    let mut reauthenticated = false;
+    let mut document = self.document(options);
+    loop {
+      // boxed, the retry loop of `send` makes a large future
+      let sent = Box::pin(self.send(body, document, options)).await?;
+      let Sent {
+        response: res,
+        permit,
+        access_token,
+      } = sent;
+
+      // the token passed the local JWKS check but the server rejected it
+      // (revoked session, clock skew...), refresh the session and replay once
+      let unauthorized = res.status() == StatusCode::UNAUTHORIZED;
+      let response = if unauthorized {
+        None
+      } else {
+        let bytes = res.bytes().await.context(Code::GraphqlServerError)?;
+        metrics::record(|metrics| metrics.response_bytes += bytes.len() as u64);
+        let response: Response<Value> =
+          serde_json::from_slice(&bytes).context(Code::GraphqlServerError)?;
+        if let Document::Hash(persisted) = document
+          && let Some(err) = persisted_query_error(&response)
+        {
+          tracing::debug!(hash = persisted.hash, ?err, "sending the full document");
+          document = match err {
+            // the server stores the document sent along with its hash
+            PersistedQueryError::NotFound => Document::Register(persisted),
+            PersistedQueryError::NotSupported => {
+              self
+                .persisted_queries_supported
+                .store(false, Ordering::Relaxed);
+              Document::Full
+            }
+          };
+          continue;
         }
-        Ok(())
-      })
-    })
+        if !is_unauthenticated(response.errors.as_deref()) {
+          return Ok(response);
+        }
+        Some(response)
+      };
+      // the replay waits for a new permit, others may go while the session refreshes
+      drop(permit);
+
+      if reauthenticated {
+        // the refreshed token is rejected too
+        self.notify_session_invalid();
+        return match response {
+          Some(response) => Ok(response),
+          None => Err(anyhow::anyhow!("Access token rejected").context(Code::InvalidSession)),
+        };
+      }
+
+      self.reauthenticate(access_token.as_deref()).await?;
+      reauthenticated = true;
+      Span::current().record("reauthenticated", true);
+    }
+  }
+
+  /// Refresh the session after the server rejected `rejected`.
+  ///
+  /// Requests rejected at the same time share one refresh, as a refresh token is only
+  /// valid once: the others find the token already replaced and replay with it.
+  pub(crate) async fn reauthenticate(&self, rejected: Option<&str>) -> Result<()> {
+    let _refreshing = self.refreshing.lock().await;
+    if self.credentials.access_token().await.as_deref() != rejected {
+      tracing::debug!("session already refreshed");
+      return Ok(());
+    }
+
+    tracing::info!("access token rejected by the server, refreshing session");
+    if let Err(err) = self.credentials.refresh().await {
+      self.notify_session_invalid();
+      return Err(err.context(Code::InvalidSession));
+    }
+    Ok(())
+  }
+
+  pub(crate) fn notify_session_invalid(&self) {
+    if let Some(on_session_invalid) = &self.on_session_invalid
+      && let Err(err) = on_session_invalid()
+    {
+      tracing::error!("Failed to notify invalid session: {:?}", err);
+    }
+  }
+
+  /// What to send first for an operation.
+  fn document(&self, options: RequestOptions) -> Document {
+    match options.persisted {
+      Some(persisted)
+        if self.persisted_queries != PersistedQueries::Disabled
+          && self.persisted_queries_supported.load(Ordering::Relaxed) =>
+      {
+        Document::Hash(persisted)
+      }
+      _ => Document::Full,
+    }
+  }
+
+  /// Send the request, retrying transient failures according to the [`RetryPolicy`].
+  ///
+  /// Every attempt waits for a permit of the [`Limiter`], released once the returned
+  /// permit is dropped.
+  async fn send(&self, body: &Value, document: Document, options: RequestOptions) -> Result<Sent> {
+    let operation = body["operationName"].as_str().unwrap_or_default();
+    let query_string = match document {
+      Document::Hash(persisted)
+        if persisted.get
+          && self.persisted_queries == PersistedQueries::Get
+          && options.kind == OperationKind::Query =>
+      {
+        Some(Document::query_string(&persisted, body))
+      }
+      _ => None,
+    };
+    let body = document.body(body);
+    self.probe_primary_in_background();
+
+    let mut attempt = 1;
+    loop {
+      let permit = self
+        .limiter
+        .acquire(operation, options.limits, self.priority)
+        .await;
+      Span::current().record("attempts", attempt);
+      metrics::record(|metrics| metrics.requests += 1);
+      // every attempt goes to the current endpoint, retries may use a mirror
+      let url = self.endpoints.current();
+      let access_token = self.credentials.access_token().await;
+      let request = self.request(
+        if query_string.is_some() {
+          Method::GET
+        } else {
+          Method::POST
+        },
+        &url,
+        access_token.as_deref(),
+        options.disable_cache,
+      );
+      let request = match &query_string {
+        Some(query_string) => request.query(query_string),
+        None => request.json(&body),
+      };
+      let result = request.timeout(self.retry_policy.timeout).send().await;
+      self.record_endpoint_health(&url, &result);
+
+      let (reason, retry_after, err) = match result {
+        Ok(res) => {
+          self.connectivity.set_online(true);
+          let Some(reason) = RetryReason::from_status(res.status()) else {
+            return Ok(Sent {
+              response: res,
+              permit,
+              access_token,
+            });
+          };
+          let retry_after = retry_after(res.headers());
+          (reason, retry_after, anyhow::anyhow!(reason))
+        }
+        Err(err) => {
+          let Some(reason) = RetryReason::from_error(&err) else {
+            return Err(anyhow::Error::from(err).context(Code::GraphqlServerError));
+          };
+          (reason, None, err.into())
+        }
+      };
+
+      if !options.kind.is_retryable() || !self.retry_policy.should_retry(attempt) {
+        let code = match reason {
+          RetryReason::Status(StatusCode::TOO_MANY_REQUESTS) => Code::GraphqlRateLimited,
+          RetryReason::Status(_) => Code::GraphqlServerError,
+          RetryReason::Connect | RetryReason::Timeout => {
+            self.connectivity.set_online(false);
+            Code::Offline
+          }
+        };
+        // the reason tells the outbox the mutation may succeed later
+        return Err(err.context(reason).context(code));
+      }
+
+      let delay = self.retry_policy.delay(attempt, retry_after);
+      tracing::warn!(attempt, ?reason, ?delay, "retrying GraphQL request");
+      // don't hold back the other requests while waiting
+      drop(permit);
+      tokio::time::sleep(delay).await;
+      attempt += 1;
+    }
+  }
+
+  /// Send a minimal query to find out whether the API is reachable again.
+  pub(crate) async fn probe(&self) {
+    let result = self.probe_endpoint(&self.endpoints.current()).await;
+    self.connectivity.set_online(result.is_ok());
+  }
+
+  /// Check in background whether the primary endpoint is back, while using a mirror.
+  fn probe_primary_in_background(&self) {
+    let Some(url) = self.endpoints.primary_probe_due() else {
+      return;
+    };
+    let client = self.clone();
+    tokio::spawn(async move {
+      tracing::debug!(url, "probing the primary API endpoint");
+      let _ = client.probe_endpoint(&url).await;
+    });
+  }
+
+  async fn probe_endpoint(&self, url: &str) -> reqwest::Result<reqwest::Response> {
+    let result = self
+      .client
+      .post(url)
+      .timeout(self.retry_policy.timeout)
+      .json(&serde_json::json!({ "query": "{ __typename }" }))
+      .send()
+      .await;
+    self.record_endpoint_health(url, &result);
+    result
+  }
+
+  /// Count the failures of `url` that a mirror may not have, i.e. not a rate limit.
+  fn record_endpoint_health(&self, url: &str, result: &reqwest::Result<reqwest::Response>) {
+    let failed = match result {
+      Ok(res) => match RetryReason::from_status(res.status()) {
+        Some(RetryReason::Status(StatusCode::TOO_MANY_REQUESTS)) => return,
+        reason => reason.is_some(),
+      },
+      Err(err) => RetryReason::from_error(err).is_some(),
+    };
+    if failed {
+      self.endpoints.record_failure(url);
+    } else {
+      self.endpoints.record_success(url);
+    }
+  }
+
+  fn request(
+    &self,
+    method: Method,
+    url: &str,
+    access_token: Option<&str>,
+    disable_cache: bool,
+  ) -> reqwest::RequestBuilder {
+    let trace_parent = TraceParent::current();
+    let mut request = self
+      .client
+      .request(method, url)
+      .header("traceparent", trace_parent.header());
+    metrics::record(|metrics| metrics.trace_id = Some(trace_parent.trace_id));
+    if let Some(access_token) = access_token {
+      request = request.bearer_auth(access_token);
+    }
+    if disable_cache {
+      request = request.header("Cache-Control", "no-cache");
+    }
+    request
+  }
+}
+
+/// A response of [`ApiClient::send`].
+struct Sent {
+  response: reqwest::Response,
+  permit: Permit,
+  /// The token the request was sent with.
+  access_token: Option<String>,
+}
+
+fn record_cache(status: CacheStatus) {
+  Span::current().record("cache", status.as_str());
+  metrics::record(|metrics| metrics.cache = status);
+}
+
+fn cached(entry: CacheEntry, stale: bool) -> GraphqlResponse<Value> {
+  GraphqlResponse {
+    data: Some(entry.data),
+    errors: Vec::new(),
+    origin: Origin::Cache {
+      fetched_at: entry.fetched_at,
+      stale,
+    },
+  }
+}
+
+fn is_offline(err: &anyhow::Error) -> bool {
+  err
+    .custom_context()
+    .is_some_and(|ctx| ctx.code == Code::Offline)
+}
+
+/// Only responses without errors are cached or invalidate the cache.
+fn is_complete(response: &Response<Value>) -> bool {
+  response.data.is_some() && response.errors.as_ref().is_none_or(Vec::is_empty)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::credentials::StaticToken;
+  use async_trait::async_trait;
+  use std::sync::{Mutex, atomic::AtomicU32};
+
+  /// Rotates the token on every refresh, counting them.
+  #[derive(Default)]
+  struct RotatingToken {
+    token: Mutex<u32>,
+    refreshes: AtomicU32,
+  }
+
+  #[async_trait]
+  impl CredentialsProvider for RotatingToken {
+    async fn access_token(&self) -> Option<String> {
+      Some(self.token.lock().unwrap().to_string())
+    }
+
+    async fn refresh(&self) -> Result<()> {
+      self.refreshes.fetch_add(1, Ordering::Relaxed);
+      tokio::task::yield_now().await;
+      *self.token.lock().unwrap() += 1;
+      Ok(())
+    }
+  }
+
+  #[tokio::test]
+  async fn concurrent_rejections_share_one_refresh() {
+    let credentials = Arc::new(RotatingToken::default());
+    let client = ApiClient::new(StaticToken(None)).unwrap();
+    let client = ApiClient {
+      credentials: credentials.clone(),
+      ..client
+    };
+
+    let rejected = client.credentials.access_token().await;
+    let refreshes = (0..4).map(|_| client.reauthenticate(rejected.as_deref()));
+    for result in futures_util::future::join_all(refreshes).await {
+      result.unwrap();
+    }
+    assert_eq!(credentials.refreshes.load(Ordering::Relaxed), 1);
+    assert_eq!(
+      client.credentials.access_token().await.as_deref(),
+      Some("1")
+    );
   }
 }
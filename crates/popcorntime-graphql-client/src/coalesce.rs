@@ -0,0 +1,111 @@
+use crate::{cache::CacheKey, error::GraphqlResponse};
+use anyhow::Result;
+use popcorntime_error::AnyhowContextExt;
+use serde_json::Value;
+use std::{
+  collections::HashMap,
+  future::Future,
+  sync::{Arc, Mutex},
+};
+use tokio::sync::OnceCell;
+
+type SharedResult = std::result::Result<GraphqlResponse<Value>, SharedError>;
+
+/// Queries currently sent to the API, so that identical concurrent queries share one
+/// request and one response.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct InFlight {
+  queries: Arc<Mutex<HashMap<CacheKey, Arc<OnceCell<SharedResult>>>>>,
+}
+
+impl InFlight {
+  /// Run `execute` unless an identical query is in flight, in which case wait for its
+  /// response instead. If the caller running the request is dropped, a waiting one
+  /// takes over.
+  pub(crate) async fn run<F>(&self, key: CacheKey, execute: F) -> Result<GraphqlResponse<Value>>
+  where
+    F: Future<Output = Result<GraphqlResponse<Value>>>,
+  {
+    let cell = self.lock().entry(key.clone()).or_default().clone();
+    let result = cell
+      .get_or_init(|| async { execute.await.map_err(SharedError::from) })
+      .await
+      .clone();
+
+    // later calls must send a new request
+    let mut queries = self.lock();
+    if queries
+      .get(&key)
+      .is_some_and(|current| Arc::ptr_eq(current, &cell))
+    {
+      queries.remove(&key);
+    }
+
+    result.map_err(SharedError::into_error)
+  }
+
+  fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<CacheKey, Arc<OnceCell<SharedResult>>>> {
+    self.queries.lock().unwrap_or_else(|err| err.into_inner())
+  }
+}
+
+/// An error returned to every caller sharing the request, keeping its code.
+#[derive(Debug, Clone)]
+struct SharedError(Arc<anyhow::Error>);
+
+impl From<anyhow::Error> for SharedError {
+  fn from(err: anyhow::Error) -> Self {
+    Self(Arc::new(err))
+  }
+}
+
+impl SharedError {
+  fn into_error(self) -> anyhow::Error {
+    match self.0.custom_context() {
+      Some(ctx) => anyhow::Error::new(self).context(ctx),
+      None => anyhow::Error::new(self),
+    }
+  }
+}
+
+impl std::fmt::Display for SharedError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{:#}", self.0)
+  }
+}
+
+impl std::error::Error for SharedError {}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use popcorntime_error::Code;
+  use serde_json::json;
+  use std::sync::atomic::{AtomicUsize, Ordering};
+
+  #[tokio::test]
+  async fn identical_queries_share_one_request() {
+    let in_flight = InFlight::default();
+    let requests = AtomicUsize::new(0);
+    let execute = || async {
+      requests.fetch_add(1, Ordering::SeqCst);
+      tokio::task::yield_now().await;
+      Err::<GraphqlResponse<Value>, _>(anyhow::anyhow!("unreachable").context(Code::Offline))
+    };
+    let key = CacheKey::new("Media", &json!({ "slug": "alien" }));
+
+    let (a, b) = tokio::join!(
+      in_flight.run(key.clone(), execute()),
+      in_flight.run(key.clone(), execute())
+    );
+
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+    for err in [a.unwrap_err(), b.unwrap_err()] {
+      assert_eq!(
+        err.custom_context().map(|ctx| ctx.code),
+        Some(Code::Offline)
+      );
+    }
+    assert!(in_flight.lock().is_empty());
+  }
+}
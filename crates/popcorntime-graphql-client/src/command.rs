@@ -0,0 +1,217 @@
+//! Support for the commands calling the operations, e.g. the Tauri commands generated
+//! by `graphql_command!`.
+
+use crate::error::{GraphqlError, GraphqlResponse, Origin};
+use anyhow::Result;
+use futures_util::future::{AbortHandle, Abortable};
+use popcorntime_error::Code;
+use serde::Serialize;
+use std::{collections::HashMap, future::Future, sync::Mutex};
+
+/// The requests of the commands declared `cancellable`, by the key the frontend passed.
+///
+/// A request replaces the pending one with the same key, which fails with
+/// [`Code::Cancelled`], e.g. the search of the previous keystroke.
+#[derive(Debug, Default)]
+pub struct CancellableRequests {
+  next_id: std::sync::atomic::AtomicU64,
+  pending: Mutex<HashMap<String, (u64, AbortHandle)>>,
+}
+
+impl CancellableRequests {
+  /// Run `request`, cancelling the pending request with the same `key`.
+  pub async fn run<T>(&self, key: String, request: impl Future<Output = Result<T>>) -> Result<T> {
+    let id = self
+      .next_id
+      .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    let (handle, registration) = AbortHandle::new_pair();
+    if let Some((_, superseded)) = self.lock().insert(key.clone(), (id, handle)) {
+      superseded.abort();
+    }
+
+    let result = Abortable::new(request, registration).await;
+    let mut pending = self.lock();
+    if pending.get(&key).is_some_and(|(current, _)| *current == id) {
+      pending.remove(&key);
+    }
+    drop(pending);
+
+    result.map_err(|_| anyhow::anyhow!("Superseded by a newer request").context(Code::Cancelled))?
+  }
+
+  fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, (u64, AbortHandle)>> {
+    self.pending.lock().unwrap_or_else(|err| err.into_inner())
+  }
+}
+
+/// The value returned by a command, with where it comes from so the frontend can tell
+/// e.g. that it's showing cached data while offline.
+#[derive(Debug, Clone, Serialize, specta::Type)]
+pub struct Fetched<T> {
+  pub data: T,
+  /// The errors of a partial response, e.g. of the fields the server failed to resolve.
+  pub errors: Vec<GraphqlError>,
+  pub origin: Origin,
+}
+
+impl<T> Fetched<T> {
+  /// Unwrap the data of `response` with `unwrap`, failing like
+  /// [`GraphqlResponse::into_data`] but keeping the errors of a partial response.
+  pub fn from_response<D, F>(
+    response: GraphqlResponse<D>,
+    unwrap: impl FnOnce(D) -> F,
+  ) -> Result<Self>
+  where
+    T: FromField<F>,
+  {
+    let origin = response.origin;
+    let errors = if response.is_partial() {
+      response.errors.clone()
+    } else {
+      Vec::new()
+    };
+    let data = response.into_data()?;
+    Ok(Self {
+      data: T::from_field(data.map(unwrap)),
+      errors,
+      origin,
+    })
+  }
+}
+
+/// Build the value returned by a command from the unwrapped response field, which is
+/// `None` when the server returned no data.
+pub trait FromField<F> {
+  fn from_field(field: Option<F>) -> Self;
+}
+
+impl<T> FromField<T> for Option<T> {
+  fn from_field(field: Option<T>) -> Self {
+    field
+  }
+}
+
+impl<T> FromField<Option<T>> for Option<T> {
+  fn from_field(field: Option<Option<T>>) -> Self {
+    field.flatten()
+  }
+}
+
+impl<T> FromField<Vec<T>> for Vec<T> {
+  fn from_field(field: Option<Vec<T>>) -> Self {
+    field.unwrap_or_default()
+  }
+}
+
+impl FromField<bool> for bool {
+  fn from_field(field: Option<bool>) -> Self {
+    field.unwrap_or_default()
+  }
+}
+
+impl FromField<i64> for i64 {
+  fn from_field(field: Option<i64>) -> Self {
+    field.unwrap_or_default()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::*;
+  use popcorntime_error::{AnyhowContextExt, Code};
+  use specta_typescript::{BigIntExportBehavior, Typescript};
+
+  #[tokio::test]
+  async fn newer_requests_cancel_pending_ones() {
+    let requests = command::CancellableRequests::default();
+    let (release, released) = tokio::sync::oneshot::channel::<()>();
+    let first = requests.run("search".to_string(), async {
+      released.await.ok();
+      Ok("first")
+    });
+    tokio::pin!(first);
+    assert!(futures_util::poll!(first.as_mut()).is_pending());
+
+    let second = requests.run("search".to_string(), async { Ok("second") });
+    assert_eq!(second.await.unwrap(), "second");
+    let err = first.await.unwrap_err();
+    assert_eq!(err.custom_context_or_root_cause().code, Code::Cancelled);
+
+    // other keys are independent
+    let other = requests.run("home".to_string(), async { Ok("other") });
+    assert_eq!(other.await.unwrap(), "other");
+    drop(release);
+  }
+
+  #[test]
+  fn fetched_keeps_the_origin() {
+    let fetched_at = time::OffsetDateTime::UNIX_EPOCH;
+    let response = error::GraphqlResponse {
+      data: Some(count::ResponseData { count: 3 }),
+      errors: Vec::new(),
+      origin: error::Origin::Cache {
+        fetched_at,
+        stale: true,
+      },
+    };
+    let fetched =
+      command::Fetched::<i64>::from_response(response, |data: count::ResponseData| data.count)
+        .unwrap();
+    assert_eq!(fetched.data, 3);
+    assert_eq!(
+      serde_json::to_value(&fetched).unwrap(),
+      serde_json::json!({
+        "data": 3,
+        "errors": [],
+        "origin": { "source": "cache", "fetchedAt": "1970-01-01T00:00:00Z", "stale": true },
+      })
+    );
+  }
+
+  #[test]
+  fn fetched_keeps_the_errors_of_partial_responses() {
+    let error = error::GraphqlError {
+      message: "charts unavailable".to_string(),
+      path: vec!["media".to_string(), "charts".to_string()],
+      code: Some("NOT_FOUND".to_string()),
+    };
+    let response = error::GraphqlResponse {
+      data: Some(count::ResponseData { count: 3 }),
+      errors: vec![error.clone()],
+      origin: error::Origin::Network,
+    };
+    let fetched =
+      command::Fetched::<i64>::from_response(response, |data: count::ResponseData| data.count)
+        .unwrap();
+    assert_eq!((fetched.data, fetched.errors), (3, vec![error]));
+  }
+
+  #[test]
+  fn operation_types_export_with_unique_names() {
+    let mut types = specta::TypeCollection::default();
+    macro_rules! register {
+      ($($module:ident),*) => {
+        $(types.register::<$module::Variables>().register::<$module::ResponseData>();)*
+      };
+    }
+    register!(
+      search,
+      preferences,
+      update_preferences,
+      media,
+      providers,
+      add_favorite_provider,
+      remove_favorite_provider,
+      home_collection,
+      collections,
+      count
+    );
+
+    let bindings = Typescript::default()
+      .bigint(BigIntExportBehavior::Number)
+      .export(&types)
+      .unwrap();
+    assert!(bindings.contains("export type SearchVariables = "));
+    assert!(bindings.contains("export type MediaResponseData = "));
+  }
+}
@@ -1 +1,3 @@
 pub const GRAPHQL_SERVER: &str = env!("GRAPHQL_SERVER");
+/// Comma separated mirrors of `GRAPHQL_SERVER`, used in order while it is down.
+pub const GRAPHQL_MIRRORS: Option<&str> = option_env!("GRAPHQL_MIRRORS");
@@ -0,0 +1,67 @@
+use anyhow::Result;
+use async_trait::async_trait;
+
+/// Provides the bearer token attached to every request sent by `ApiClient`.
+///
+/// The token is resolved per request, so a token change never requires a new HTTP client.
+#[async_trait]
+pub trait CredentialsProvider: Send + Sync {
+  async fn access_token(&self) -> Option<String>;
+
+  /// The user the requests are sent for, scoping the cached responses.
+  async fn user(&self) -> Option<String> {
+    None
+  }
+
+  /// Obtain a new access token after the server rejected the current one.
+  async fn refresh(&self) -> Result<()> {
+    Err(anyhow::anyhow!("Credentials can't be refreshed"))
+  }
+}
+
+/// A fixed token, or anonymous requests with `StaticToken(None)`.
+#[derive(Debug, Clone, Default)]
+pub struct StaticToken(pub Option<String>);
+
+#[async_trait]
+impl CredentialsProvider for StaticToken {
+  async fn access_token(&self) -> Option<String> {
+    self.0.clone()
+  }
+}
+
+#[cfg(feature = "session")]
+mod session {
+  use super::CredentialsProvider;
+  use anyhow::Result;
+  use async_trait::async_trait;
+  use popcorntime_session::AuthorizationService;
+
+  /// Reads the access token of the current session.
+  #[derive(Debug, Clone)]
+  pub struct SessionCredentials(AuthorizationService);
+
+  impl SessionCredentials {
+    pub fn new(service: AuthorizationService) -> Self {
+      Self(service)
+    }
+  }
+
+  #[async_trait]
+  impl CredentialsProvider for SessionCredentials {
+    async fn access_token(&self) -> Option<String> {
+      self.0.access_token().await
+    }
+
+    async fn user(&self) -> Option<String> {
+      self.0.subject().await.map(|subject| subject.to_string())
+    }
+
+    async fn refresh(&self) -> Result<()> {
+      self.0.refresh().await
+    }
+  }
+}
+
+#[cfg(feature = "session")]
+pub use session::SessionCredentials;
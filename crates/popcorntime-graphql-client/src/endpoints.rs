@@ -0,0 +1,213 @@
+//! The endpoints of the API: the primary one, then the mirrors used while it is down.
+
+use serde::Serialize;
+use std::{
+  sync::{Arc, Mutex},
+  time::Duration,
+};
+use tokio::time::Instant;
+
+/// When `ApiClient` leaves an endpoint, and when it tries the primary endpoint again.
+#[derive(Debug, Clone)]
+pub struct FailoverPolicy {
+  /// Consecutive failed attempts (unreachable, 502, 503 or 504) before using the next
+  /// endpoint.
+  pub max_failures: u32,
+  /// Delay before probing the primary endpoint after failing over, and between probes.
+  pub probe_primary_after: Duration,
+}
+
+impl Default for FailoverPolicy {
+  fn default() -> Self {
+    Self {
+      max_failures: 3,
+      probe_primary_after: Duration::from_secs(60),
+    }
+  }
+}
+
+/// The health of an endpoint, for diagnostics.
+#[derive(Debug, Clone, Serialize, specta::Type)]
+#[serde(rename_all = "camelCase")]
+pub struct EndpointStatus {
+  pub url: String,
+  /// Whether the requests are sent to this endpoint.
+  pub current: bool,
+  pub consecutive_failures: u32,
+}
+
+/// The ordered endpoints of an `ApiClient`, shared by its clones.
+#[derive(Debug, Clone)]
+pub struct Endpoints {
+  state: Arc<Mutex<State>>,
+}
+
+#[derive(Debug)]
+struct State {
+  endpoints: Vec<Endpoint>,
+  current: usize,
+  policy: FailoverPolicy,
+  /// When the primary endpoint is probed next, set while using a mirror.
+  probe_primary_at: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct Endpoint {
+  url: String,
+  consecutive_failures: u32,
+}
+
+impl Endpoints {
+  pub fn new(primary: impl Into<String>) -> Self {
+    Self {
+      state: Arc::new(Mutex::new(State {
+        endpoints: vec![Endpoint::new(primary.into())],
+        current: 0,
+        policy: FailoverPolicy::default(),
+        probe_primary_at: None,
+      })),
+    }
+  }
+
+  /// Fail over to `mirrors`, in order, while the primary endpoint is down.
+  pub fn with_mirrors(self, mirrors: impl IntoIterator<Item = impl Into<String>>) -> Self {
+    self
+      .lock()
+      .endpoints
+      .extend(mirrors.into_iter().map(|url| Endpoint::new(url.into())));
+    self
+  }
+
+  pub fn with_policy(self, policy: FailoverPolicy) -> Self {
+    self.lock().policy = policy;
+    self
+  }
+
+  /// The url the requests are sent to.
+  pub fn current(&self) -> String {
+    let state = self.lock();
+    state.endpoints[state.current].url.clone()
+  }
+
+  pub fn status(&self) -> Vec<EndpointStatus> {
+    let state = self.lock();
+    state
+      .endpoints
+      .iter()
+      .enumerate()
+      .map(|(index, endpoint)| EndpointStatus {
+        url: endpoint.url.clone(),
+        current: index == state.current,
+        consecutive_failures: endpoint.consecutive_failures,
+      })
+      .collect()
+  }
+
+  /// `url` answered, use it again if it comes before the current endpoint.
+  pub(crate) fn record_success(&self, url: &str) {
+    let mut state = self.lock();
+    let Some(index) = state.position(url) else {
+      return;
+    };
+    state.endpoints[index].consecutive_failures = 0;
+    if index < state.current {
+      tracing::info!(url, "API endpoint is back, using it again");
+      state.current = index;
+      if index == 0 {
+        state.probe_primary_at = None;
+      }
+    }
+  }
+
+  /// `url` is unreachable or overloaded, use the next endpoint after too many failures.
+  pub(crate) fn record_failure(&self, url: &str) {
+    let state = &mut *self.lock();
+    let Some(index) = state.position(url) else {
+      return;
+    };
+    let endpoint = &mut state.endpoints[index];
+    endpoint.consecutive_failures += 1;
+    if index != state.current
+      || endpoint.consecutive_failures < state.policy.max_failures
+      || state.endpoints.len() == 1
+    {
+      return;
+    }
+
+    state.current = (index + 1) % state.endpoints.len();
+    let next = &mut state.endpoints[state.current];
+    next.consecutive_failures = 0;
+    tracing::warn!(from = url, to = %next.url, "failing over to another API endpoint");
+    state.probe_primary_at =
+      (state.current != 0).then(|| Instant::now() + state.policy.probe_primary_after);
+  }
+
+  /// The primary endpoint if it should be probed now, in which case the next probe is
+  /// scheduled.
+  pub(crate) fn primary_probe_due(&self) -> Option<String> {
+    let mut state = self.lock();
+    let now = Instant::now();
+    if state.probe_primary_at.is_none_or(|at| at > now) {
+      return None;
+    }
+    state.probe_primary_at = Some(now + state.policy.probe_primary_after);
+    Some(state.endpoints[0].url.clone())
+  }
+
+  fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+    self.state.lock().unwrap_or_else(|err| err.into_inner())
+  }
+}
+
+impl State {
+  fn position(&self, url: &str) -> Option<usize> {
+    self
+      .endpoints
+      .iter()
+      .position(|endpoint| endpoint.url == url)
+  }
+}
+
+impl Endpoint {
+  fn new(url: String) -> Self {
+    Self {
+      url,
+      consecutive_failures: 0,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test(start_paused = true)]
+  async fn fails_over_then_probes_the_primary() {
+    let endpoints = Endpoints::new("https://api").with_mirrors(["https://eu", "https://us"]);
+    for _ in 0..2 {
+      endpoints.record_failure("https://api");
+    }
+    assert_eq!(endpoints.current(), "https://api");
+    endpoints.record_failure("https://api");
+    assert_eq!(endpoints.current(), "https://eu");
+
+    // late failures of the previous endpoint don't move on again
+    endpoints.record_failure("https://api");
+    assert_eq!(endpoints.current(), "https://eu");
+
+    assert_eq!(endpoints.primary_probe_due(), None);
+    tokio::time::advance(Duration::from_secs(60)).await;
+    assert_eq!(
+      endpoints.primary_probe_due().as_deref(),
+      Some("https://api")
+    );
+    // one probe per interval
+    assert_eq!(endpoints.primary_probe_due(), None);
+
+    endpoints.record_success("https://api");
+    assert_eq!(endpoints.current(), "https://api");
+    assert_eq!(endpoints.status()[0].consecutive_failures, 0);
+    tokio::time::advance(Duration::from_secs(60)).await;
+    assert_eq!(endpoints.primary_probe_due(), None);
+  }
+}
@@ -0,0 +1,216 @@
+//! The enums of the schema, shared by all operations instead of being generated in
+//! each operation module.
+//!
+//! Values unknown to this client, e.g. added to the schema after a release, are
+//! deserialized as the `#[serde(other)]` variant. Every enum of `gql/schema.json` is
+//! defined here, the tests compare them with the schema.
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Type)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Genre {
+  Action,
+  Adventure,
+  Animation,
+  Comedy,
+  Crime,
+  Documentary,
+  Drama,
+  Family,
+  Fantasy,
+  History,
+  Horror,
+  Music,
+  Mystery,
+  Romance,
+  ScienceFiction,
+  TvMovie,
+  Thriller,
+  War,
+  Western,
+  #[serde(other)]
+  Other,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Type)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum MediaFormat {
+  #[serde(rename = "_3D")]
+  ThreeD,
+  #[serde(rename = "_4K")]
+  FourK,
+  Sd,
+  Hd,
+  Uhd,
+  #[serde(other)]
+  Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Type)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum MediaKind {
+  Movie,
+  TvShow,
+  #[serde(other)]
+  Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Type)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum MetaSource {
+  Imdb,
+  Tvdb,
+  Tmdb,
+  Trakt,
+  #[serde(other)]
+  Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Type)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RatingSource {
+  Imdb,
+  Tmdb,
+  #[serde(other)]
+  Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Type)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RoleType {
+  ExecutiveProducer,
+  Director,
+  FirstAssistantDirector,
+  SecondAssistantDirector,
+  SecondSecondAssistantDirector,
+  ScriptSupervisor,
+  Producer,
+  Writer,
+  Actor,
+  #[serde(other)]
+  Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Type)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SortKey {
+  Id,
+  ReleasedAt,
+  CreatedAt,
+  UpdatedAt,
+  Position,
+  #[serde(other)]
+  Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Type)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum VideoSource {
+  Rumble,
+  Youtube,
+  #[serde(other)]
+  Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Type)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum WatchPriceType {
+  Rent,
+  Buy,
+  Flatrate,
+  Free,
+  Cinema,
+  #[serde(other)]
+  Unknown,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde::de::DeserializeOwned;
+  use serde_json::{Value, json};
+  use specta_typescript::Typescript;
+  use std::collections::BTreeSet;
+
+  /// The values of the enums of `gql/schema.json`, by enum name.
+  fn schema_enums() -> Vec<(String, BTreeSet<String>)> {
+    let schema: Value =
+      serde_json::from_str(include_str!("../gql/schema.json")).expect("valid schema");
+    let schema = schema.get("data").unwrap_or(&schema);
+    schema["__schema"]["types"]
+      .as_array()
+      .expect("schema types")
+      .iter()
+      .filter(|ty| ty["kind"] == "ENUM")
+      .filter_map(|ty| {
+        let name = ty["name"].as_str()?;
+        let values = ty["enumValues"].as_array()?.iter();
+        let values = values.filter_map(|value| Some(value["name"].as_str()?.to_string()));
+        (!name.starts_with("__")).then(|| (name.to_string(), values.collect()))
+      })
+      .collect()
+  }
+
+  /// Every value of the schema is a variant of its own, and the only other variant is
+  /// the one unknown values are deserialized as.
+  fn assert_matches_schema<T: Type + Serialize + DeserializeOwned>(values: &BTreeSet<String>) {
+    let name = std::any::type_name::<T>();
+    for value in values {
+      let variant: T = serde_json::from_value(json!(value))
+        .unwrap_or_else(|err| panic!("{name} can't deserialize {value}: {err}"));
+      assert_eq!(
+        serde_json::to_value(variant).unwrap(),
+        json!(value),
+        "{name}"
+      );
+    }
+
+    let unknown: T = serde_json::from_value(json!("NOT_IN_THE_SCHEMA")).unwrap();
+    let unknown = serde_json::to_value(unknown).unwrap();
+    let variants = specta_typescript::inline::<T>(&Typescript::default()).unwrap();
+    for variant in variants.split(" | ") {
+      let variant: Value = serde_json::from_str(variant).unwrap();
+      assert!(
+        values.contains(variant.as_str().unwrap()) || variant == unknown,
+        "{name}::{variant} isn't in the schema"
+      );
+    }
+  }
+
+  #[test]
+  fn enums_match_the_schema() {
+    let schema = schema_enums();
+    let values = |name: &str| {
+      let (_, values) = schema
+        .iter()
+        .find(|(enum_name, _)| enum_name == name)
+        .unwrap_or_else(|| panic!("{name} isn't in the schema"));
+      values
+    };
+    macro_rules! check {
+      ($($name:ident),*) => {{
+        $(assert_matches_schema::<$name>(values(stringify!($name)));)*
+        [$(stringify!($name)),*]
+      }};
+    }
+    let checked = check!(
+      Genre,
+      MediaFormat,
+      MediaKind,
+      MetaSource,
+      RatingSource,
+      RoleType,
+      SortKey,
+      VideoSource,
+      WatchPriceType
+    );
+    for (name, _) in &schema {
+      assert!(
+        checked.contains(&name.as_str()),
+        "{name} is missing from enums.rs"
+      );
+    }
+  }
+}
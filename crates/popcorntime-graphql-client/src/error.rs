@@ -0,0 +1,251 @@
+use anyhow::Result;
+use popcorntime_error::Code;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+/// A single entry of the `errors` array returned by the GraphQL server.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, specta::Type)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphqlError {
+  pub message: String,
+  /// Path of the field the error applies to, e.g. `["media", "charts", "0"]`.
+  #[serde(default)]
+  pub path: Vec<String>,
+  /// The `extensions.code` set by the server, if any.
+  #[serde(default)]
+  pub code: Option<String>,
+}
+
+impl GraphqlError {
+  /// Map the server extension code to one of our known error codes.
+  pub fn error_code(&self) -> Code {
+    match self.code.as_deref() {
+      Some("NOT_FOUND") => Code::GraphqlNotFound,
+      Some("FORBIDDEN") => Code::GraphqlForbidden,
+      Some("RATE_LIMITED") | Some("TOO_MANY_REQUESTS") => Code::GraphqlRateLimited,
+      Some("UNAUTHENTICATED") | Some("UNAUTHORIZED") => Code::GraphqlUnauthenticated,
+      Some("BAD_USER_INPUT") | Some("GRAPHQL_VALIDATION_FAILED") | Some("GRAPHQL_PARSE_FAILED") => {
+        Code::GraphqlValidation
+      }
+      _ => Code::GraphqlServerError,
+    }
+  }
+}
+
+impl From<graphql_client::Error> for GraphqlError {
+  fn from(err: graphql_client::Error) -> Self {
+    let code = err
+      .extensions
+      .as_ref()
+      .and_then(|extensions| extensions.get("code"))
+      .and_then(|code| code.as_str())
+      .map(ToString::to_string);
+
+    Self {
+      message: err.message,
+      path: err
+        .path
+        .unwrap_or_default()
+        .iter()
+        .map(ToString::to_string)
+        .collect(),
+      code,
+    }
+  }
+}
+
+impl std::fmt::Display for GraphqlError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    if self.path.is_empty() {
+      f.write_str(&self.message)
+    } else {
+      write!(f, "{}: {}", self.path.join("/"), self.message)
+    }
+  }
+}
+
+/// Whether the server rejected the request because of the access token.
+pub fn is_unauthenticated(errors: Option<&[graphql_client::Error]>) -> bool {
+  errors.unwrap_or_default().iter().any(|err| {
+    err
+      .extensions
+      .as_ref()
+      .and_then(|extensions| extensions.get("code"))
+      .and_then(|code| code.as_str())
+      .is_some_and(|code| matches!(code, "UNAUTHENTICATED" | "UNAUTHORIZED"))
+  })
+}
+
+/// All errors of a single response, used as the source of an `anyhow::Error`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphqlErrors(pub Vec<GraphqlError>);
+
+impl GraphqlErrors {
+  /// The code of the first error we know how to handle, or [`Code::GraphqlServerError`].
+  pub fn error_code(&self) -> Code {
+    self
+      .0
+      .iter()
+      .map(GraphqlError::error_code)
+      .find(|code| *code != Code::GraphqlServerError)
+      .unwrap_or(Code::GraphqlServerError)
+  }
+}
+
+impl std::fmt::Display for GraphqlErrors {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self.0.first() {
+      Some(first) => first.fmt(f),
+      None => f.write_str("Unknown GraphQL error"),
+    }
+  }
+}
+
+impl std::error::Error for GraphqlErrors {}
+
+/// Where the data of a [`GraphqlResponse`] comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, specta::Type)]
+#[serde(rename_all = "camelCase", tag = "source")]
+pub enum Origin {
+  #[default]
+  Network,
+  /// Served from the response cache, `stale` when it's outdated or the API is unreachable.
+  #[serde(rename_all = "camelCase")]
+  Cache {
+    #[serde(with = "time::serde::rfc3339")]
+    #[specta(type = String)]
+    fetched_at: OffsetDateTime,
+    stale: bool,
+  },
+  /// The mutation waits in the outbox, the data is what the server is expected to return.
+  Queued,
+}
+
+/// The response of a GraphQL operation, keeping the errors next to the (possibly partial) data.
+#[derive(Debug, Clone, Serialize)]
+pub struct GraphqlResponse<T> {
+  pub data: Option<T>,
+  pub errors: Vec<GraphqlError>,
+  pub origin: Origin,
+}
+
+impl<T> From<graphql_client::Response<T>> for GraphqlResponse<T> {
+  fn from(response: graphql_client::Response<T>) -> Self {
+    Self {
+      data: response.data,
+      errors: response
+        .errors
+        .unwrap_or_default()
+        .into_iter()
+        .map(Into::into)
+        .collect(),
+      origin: Origin::Network,
+    }
+  }
+}
+
+impl<T> GraphqlResponse<T> {
+  /// Whether the data was served from an outdated cache entry.
+  pub fn is_stale(&self) -> bool {
+    matches!(self.origin, Origin::Cache { stale: true, .. })
+  }
+
+  /// Convert the data, keeping the errors and origin.
+  pub fn try_map<U, E>(self, f: impl FnOnce(T) -> Result<U, E>) -> Result<GraphqlResponse<U>, E> {
+    Ok(GraphqlResponse {
+      data: self.data.map(f).transpose()?,
+      errors: self.errors,
+      origin: self.origin,
+    })
+  }
+
+  /// Whether the server returned data along with errors.
+  pub fn is_partial(&self) -> bool {
+    self.data.is_some() && !self.errors.is_empty()
+  }
+
+  /// Return the data, failing with the mapped [`Code`] if the server only returned errors.
+  ///
+  /// Partial responses keep their data; the errors are logged.
+  pub fn into_data(self) -> Result<Option<T>> {
+    match (self.data, self.errors) {
+      (Some(data), errors) => {
+        if !errors.is_empty() {
+          tracing::warn!(?errors, "partial GraphQL response");
+        }
+        Ok(Some(data))
+      }
+      (None, errors) if errors.is_empty() => Ok(None),
+      (None, errors) => {
+        let errors = GraphqlErrors(errors);
+        let code = errors.error_code();
+        Err(anyhow::Error::new(errors).context(code))
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use popcorntime_error::AnyhowContextExt;
+
+  fn error(code: Option<&str>) -> GraphqlError {
+    GraphqlError {
+      message: "err msg".to_string(),
+      path: vec!["media".to_string()],
+      code: code.map(ToString::to_string),
+    }
+  }
+
+  #[test]
+  fn known_extension_codes_are_mapped() {
+    assert_eq!(error(Some("NOT_FOUND")).error_code(), Code::GraphqlNotFound);
+    assert_eq!(
+      error(Some("FORBIDDEN")).error_code(),
+      Code::GraphqlForbidden
+    );
+    assert_eq!(
+      error(Some("RATE_LIMITED")).error_code(),
+      Code::GraphqlRateLimited
+    );
+    assert_eq!(
+      error(Some("WHATEVER")).error_code(),
+      Code::GraphqlServerError
+    );
+    assert_eq!(error(None).error_code(), Code::GraphqlServerError);
+  }
+
+  #[test]
+  fn first_known_code_wins() {
+    let errors = GraphqlErrors(vec![error(None), error(Some("FORBIDDEN"))]);
+    assert_eq!(errors.error_code(), Code::GraphqlForbidden);
+  }
+
+  #[test]
+  fn errors_without_data_fail_with_code() {
+    let response = GraphqlResponse::<()> {
+      data: None,
+      errors: vec![error(Some("NOT_FOUND"))],
+      origin: Origin::Network,
+    };
+    let err = response.into_data().unwrap_err();
+    let ctx = err.custom_context_or_root_cause();
+    assert_eq!(ctx.code, Code::GraphqlNotFound);
+    assert_eq!(
+      format!("{:#}", err),
+      "errors.graphql.not_found: media: err msg"
+    );
+  }
+
+  #[test]
+  fn partial_data_is_kept() {
+    let response = GraphqlResponse {
+      data: Some(1),
+      errors: vec![error(None)],
+      origin: Origin::Network,
+    };
+    assert!(response.is_partial());
+    assert_eq!(response.into_data().unwrap(), Some(1));
+  }
+}
@@ -0,0 +1,101 @@
+use crate::{client::ApiClient, collections, count, home_collection};
+use anyhow::Result;
+use popcorntime_error::AnyhowContextExt;
+use serde::Serialize;
+use specta::Type;
+
+/// The sections of the home screen, each fetched with its own query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub enum HomeSection {
+  HomeCollection,
+  Collections,
+  Count,
+}
+
+/// A section which failed to load, in the `{ code, message }` shape of command errors.
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct HomeError {
+  pub section: HomeSection,
+  pub code: String,
+  pub message: String,
+}
+
+/// Everything the home screen shows. A section is `None` if it failed to load, the
+/// reason is listed in `errors`.
+#[derive(Debug, Serialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct Home {
+  pub home_collection: Option<home_collection::HomeCollectionHomeCollection>,
+  pub collections: Option<Vec<collections::CollectionsCollections>>,
+  pub count: Option<i64>,
+  pub errors: Vec<HomeError>,
+}
+
+impl ApiClient {
+  /// Fetch all sections of the home screen concurrently.
+  ///
+  /// Fails only if every section failed, e.g. when the API is unreachable.
+  pub async fn home(&self, vars: &home_collection::Variables) -> Result<Home> {
+    let (home_collection, collections, count) = tokio::join!(
+      async {
+        let data = self.home_collection(vars).await?.into_data()?;
+        Ok(data.and_then(|data| data.home_collection))
+      },
+      async {
+        let vars = collections::Variables {
+          country: vars.country,
+          language: vars.language.clone(),
+        };
+        let data = self.collections(&vars).await?.into_data()?;
+        Ok(data.map(|data| data.collections))
+      },
+      async {
+        let vars = count::Variables {
+          country: vars.country,
+        };
+        let data = self.count(&vars).await?.into_data()?;
+        Ok(data.map(|data| data.count))
+      },
+    );
+
+    let mut failures = Vec::new();
+    let home = Home {
+      home_collection: section(HomeSection::HomeCollection, home_collection, &mut failures),
+      collections: section(HomeSection::Collections, collections, &mut failures),
+      count: section(HomeSection::Count, count, &mut failures),
+      errors: failures.iter().map(|(_, error)| error.clone()).collect(),
+    };
+
+    if failures.len() == 3 {
+      let (err, _) = failures.remove(0);
+      return Err(err);
+    }
+    Ok(home)
+  }
+}
+
+/// Keep the data of a section, or record why it failed.
+fn section<T>(
+  section: HomeSection,
+  result: Result<Option<T>>,
+  failures: &mut Vec<(anyhow::Error, HomeError)>,
+) -> Option<T> {
+  match result {
+    Ok(data) => data,
+    Err(err) => {
+      tracing::warn!(?section, "failed to load home section: {:?}", err);
+      let ctx = err.custom_context_or_root_cause();
+      let error = HomeError {
+        section,
+        code: ctx.code.to_string(),
+        message: ctx
+          .message
+          .map(Into::into)
+          .unwrap_or_else(|| err.to_string()),
+      };
+      failures.push((err, error));
+      None
+    }
+  }
+}
@@ -1,23 +1,101 @@
-use anyhow::{Context, Result};
-use client::ApiClient;
+use anyhow::Result;
+use cache::CachePolicy;
+use client::{ApiClient, OperationKind, RequestOptions};
+use error::GraphqlResponse;
 use graphql_client::GraphQLQuery;
-use popcorntime_error::Code;
+use limiter::{OperationLimits, RateLimit};
+use outbox::OutboxPolicy;
+use persisted::PersistedQuery;
 use popcorntime_graphql_macros::define_graphql_query;
 
+pub mod cache;
 pub mod client;
+mod coalesce;
+pub mod command;
 pub mod consts;
+pub mod credentials;
+pub mod endpoints;
+pub mod enums;
+pub mod error;
+pub mod home;
+pub mod limiter;
+pub mod metrics;
+pub mod offline;
+mod optimistic;
+pub mod outbox;
+pub mod pagination;
+pub mod persisted;
+pub mod retry;
+pub mod scalars;
+pub mod subscription;
+pub mod trace;
 
-type DateTime = String;
-type Date = String;
-type Country = String;
-type Language = String;
+use enums::{Genre, MediaKind, RatingSource, RoleType, SortKey, VideoSource, WatchPriceType};
+use scalars::{Country, Date, DateTime, Language};
 type Tag = String;
 
-define_graphql_query!(Search, "gql/search.graphql");
-define_graphql_query!(Preferences, "gql/preferences.graphql");
-define_graphql_query!(UpdatePreferences, "gql/preferences.graphql");
-define_graphql_query!(Media, "gql/media.graphql");
+define_graphql_query!(
+  Search,
+  "gql/search.graphql",
+  cache = stale_while_revalidate(300),
+  get,
+  limit(per_second = 4, burst = 4, max_in_flight = 2)
+);
+define_graphql_query!(
+  Preferences,
+  "gql/preferences.graphql",
+  cache = network_first
+);
+define_graphql_query!(
+  UpdatePreferences,
+  "gql/preferences.graphql",
+  mutation(idempotent),
+  invalidates = [Preferences],
+  outbox = replace
+);
+define_graphql_query!(
+  Media,
+  "gql/media.graphql",
+  cache = stale_while_revalidate(3600),
+  get
+);
+// the `... on Movie` and `... on TVShow` fields, internally tagged by `__typename`
+impl specta::Flatten for media::MediaMediaOn {}
 
-define_graphql_query!(Providers, "gql/providers.graphql");
-define_graphql_query!(AddFavoriteProvider, "gql/providers.graphql");
-define_graphql_query!(RemoveFavoriteProvider, "gql/providers.graphql");
+define_graphql_query!(
+  Providers,
+  "gql/providers.graphql",
+  cache = cache_first(3600)
+);
+define_graphql_query!(
+  AddFavoriteProvider,
+  "gql/providers.graphql",
+  mutation,
+  invalidates = [Providers],
+  outbox = toggles(RemoveFavoriteProvider)
+);
+define_graphql_query!(
+  RemoveFavoriteProvider,
+  "gql/providers.graphql",
+  mutation,
+  invalidates = [Providers],
+  outbox = toggles(AddFavoriteProvider)
+);
+define_graphql_query!(
+  HomeCollection,
+  "gql/home.graphql",
+  cache = stale_while_revalidate(3600),
+  get
+);
+define_graphql_query!(
+  Collections,
+  "gql/home.graphql",
+  cache = stale_while_revalidate(3600),
+  get
+);
+define_graphql_query!(
+  Count,
+  "gql/home.graphql",
+  cache = stale_while_revalidate(3600),
+  get
+);
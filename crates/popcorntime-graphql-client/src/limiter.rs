@@ -0,0 +1,245 @@
+//! Client side limits on the requests sent to the API, so bursts of requests (fast
+//! typing, image heavy pages...) don't get rate limited by the server.
+
+use std::{
+  collections::HashMap,
+  sync::{Arc, Mutex},
+  time::Duration,
+};
+use tokio::{sync::Notify, time::Instant};
+
+/// Number of requests sent at once by default, like browsers per host.
+const DEFAULT_MAX_IN_FLIGHT: usize = 6;
+
+/// Who is waiting for a request. User-initiated requests overtake background ones,
+/// which never take the last free slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Priority {
+  /// Prefetches, revalidations and replays nobody is waiting for.
+  Background,
+  #[default]
+  User,
+}
+
+/// A token bucket: `burst` requests can be sent at once, then `per_second`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+  pub per_second: f64,
+  pub burst: u32,
+}
+
+/// The limits of an operation, on top of the limit of requests in flight of the client.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct OperationLimits {
+  pub rate: Option<RateLimit>,
+  pub max_in_flight: Option<usize>,
+}
+
+/// Hands out the permits to send requests, shared by the clones of an `ApiClient`.
+#[derive(Debug, Clone)]
+pub(crate) struct Limiter {
+  state: Arc<Mutex<State>>,
+  released: Arc<Notify>,
+}
+
+#[derive(Debug)]
+struct State {
+  max_in_flight: usize,
+  in_flight: usize,
+  /// Number of user-initiated requests waiting for a permit.
+  users_waiting: usize,
+  operations: HashMap<String, OperationState>,
+}
+
+#[derive(Debug, Default)]
+struct OperationState {
+  in_flight: usize,
+  /// The available tokens and when they were counted.
+  tokens: Option<(f64, Instant)>,
+}
+
+/// Allows sending one request, released when dropped.
+#[derive(Debug)]
+pub(crate) struct Permit {
+  limiter: Limiter,
+  operation: String,
+}
+
+impl Default for Limiter {
+  fn default() -> Self {
+    Self::new(DEFAULT_MAX_IN_FLIGHT)
+  }
+}
+
+impl Limiter {
+  pub fn new(max_in_flight: usize) -> Self {
+    Self {
+      state: Arc::new(Mutex::new(State {
+        max_in_flight: max_in_flight.max(1),
+        in_flight: 0,
+        users_waiting: 0,
+        operations: HashMap::new(),
+      })),
+      released: Arc::default(),
+    }
+  }
+
+  /// Wait until a request of `operation` can be sent.
+  pub async fn acquire(
+    &self,
+    operation: &str,
+    limits: OperationLimits,
+    priority: Priority,
+  ) -> Permit {
+    let _waiting = (priority == Priority::User).then(|| UserWaiting::new(self));
+    loop {
+      let released = self.released.notified();
+      tokio::pin!(released);
+      // register before checking, so a release in between isn't missed
+      released.as_mut().enable();
+
+      let wait = match self.lock().try_acquire(operation, limits, priority) {
+        Ok(()) => {
+          return Permit {
+            limiter: self.clone(),
+            operation: operation.to_string(),
+          };
+        }
+        Err(wait) => wait,
+      };
+      match wait {
+        Some(wait) => {
+          tokio::select! {
+            _ = released => {}
+            _ = tokio::time::sleep(wait) => {}
+          }
+        }
+        None => released.await,
+      }
+    }
+  }
+
+  fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+    self.state.lock().unwrap_or_else(|err| err.into_inner())
+  }
+}
+
+impl State {
+  /// Take a permit, or return how long to wait for a token, `None` to wait for a
+  /// request to complete.
+  fn try_acquire(
+    &mut self,
+    operation: &str,
+    limits: OperationLimits,
+    priority: Priority,
+  ) -> Result<(), Option<Duration>> {
+    let available = match priority {
+      Priority::User => self.max_in_flight,
+      // keep a slot for the user, who goes first
+      Priority::Background if self.users_waiting > 0 => return Err(None),
+      Priority::Background => (self.max_in_flight - 1).max(1),
+    };
+    if self.in_flight >= available {
+      return Err(None);
+    }
+
+    let state = self.operations.entry(operation.to_string()).or_default();
+    if limits
+      .max_in_flight
+      .is_some_and(|max_in_flight| state.in_flight >= max_in_flight)
+    {
+      return Err(None);
+    }
+    if let Some(rate) = limits.rate {
+      let now = Instant::now();
+      let (tokens, counted_at) = state.tokens.unwrap_or((rate.burst as f64, now));
+      let tokens = (tokens + now.duration_since(counted_at).as_secs_f64() * rate.per_second)
+        .min(rate.burst as f64);
+      if tokens < 1.0 {
+        state.tokens = Some((tokens, now));
+        return Err(Some(Duration::from_secs_f64(
+          (1.0 - tokens) / rate.per_second,
+        )));
+      }
+      state.tokens = Some((tokens - 1.0, now));
+    }
+
+    state.in_flight += 1;
+    self.in_flight += 1;
+    Ok(())
+  }
+}
+
+impl Drop for Permit {
+  fn drop(&mut self) {
+    let mut state = self.limiter.lock();
+    state.in_flight -= 1;
+    if let Some(operation) = state.operations.get_mut(&self.operation) {
+      operation.in_flight -= 1;
+    }
+    drop(state);
+    self.limiter.released.notify_waiters();
+  }
+}
+
+/// Counts a user-initiated request as waiting until dropped, including when the
+/// request is cancelled.
+struct UserWaiting<'a>(&'a Limiter);
+
+impl<'a> UserWaiting<'a> {
+  fn new(limiter: &'a Limiter) -> Self {
+    limiter.lock().users_waiting += 1;
+    Self(limiter)
+  }
+}
+
+impl Drop for UserWaiting<'_> {
+  fn drop(&mut self) {
+    self.0.lock().users_waiting -= 1;
+    // background requests may go now
+    self.0.released.notify_waiters();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test(start_paused = true)]
+  async fn user_requests_overtake_background_ones() {
+    let limiter = Limiter::new(2);
+    let limits = OperationLimits::default();
+    let first = limiter.acquire("Search", limits, Priority::User).await;
+    // background requests leave the last slot to the user
+    let background = limiter.acquire("Search", limits, Priority::Background);
+    tokio::pin!(background);
+    assert!(futures_util::poll!(background.as_mut()).is_pending());
+    let second = limiter.acquire("Search", limits, Priority::User).await;
+
+    drop(first);
+    assert!(futures_util::poll!(background.as_mut()).is_pending());
+    drop(second);
+    background.await;
+  }
+
+  #[tokio::test(start_paused = true)]
+  async fn operations_are_rate_limited() {
+    let limiter = Limiter::new(10);
+    let limits = OperationLimits {
+      rate: Some(RateLimit {
+        per_second: 2.0,
+        burst: 2,
+      }),
+      max_in_flight: None,
+    };
+    let start = Instant::now();
+    for _ in 0..4 {
+      limiter.acquire("Search", limits, Priority::User).await;
+    }
+    // 2 at once, then one every 500ms
+    assert_eq!(start.elapsed(), Duration::from_secs(1));
+    // other operations have their own bucket
+    limiter.acquire("Media", limits, Priority::User).await;
+    assert_eq!(start.elapsed(), Duration::from_secs(1));
+  }
+}
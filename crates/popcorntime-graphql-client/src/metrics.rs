@@ -0,0 +1,172 @@
+//! Metrics of the operations sent by `ApiClient`, kept in memory for diagnostics.
+
+use anyhow::Result;
+use popcorntime_error::AnyhowContextExt;
+use serde::Serialize;
+use std::{
+  cell::RefCell,
+  collections::{BTreeMap, VecDeque},
+  future::Future,
+  sync::{Arc, Mutex},
+  time::Instant,
+};
+use time::OffsetDateTime;
+
+/// Number of operations kept, the oldest are dropped first.
+const MAX_RECENT: usize = 200;
+
+tokio::task_local! {
+  static CURRENT: RefCell<OperationMetrics>;
+}
+
+/// How the response cache answered an operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, specta::Type)]
+#[serde(rename_all = "camelCase")]
+pub enum CacheStatus {
+  /// The cache wasn't used, e.g. for mutations or clients without cache.
+  #[default]
+  Bypass,
+  Hit,
+  /// Served from an outdated entry, revalidated in background.
+  Stale,
+  Miss,
+  /// Served from the cache because the API was unreachable.
+  Fallback,
+}
+
+impl CacheStatus {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      CacheStatus::Bypass => "bypass",
+      CacheStatus::Hit => "hit",
+      CacheStatus::Stale => "stale",
+      CacheStatus::Miss => "miss",
+      CacheStatus::Fallback => "fallback",
+    }
+  }
+}
+
+/// One operation sent with `ApiClient::query`.
+#[derive(Debug, Clone, Serialize, specta::Type)]
+#[serde(rename_all = "camelCase")]
+pub struct OperationMetrics {
+  pub operation: String,
+  /// The trace id sent in the `traceparent` header, `None` if no request was sent.
+  pub trace_id: Option<String>,
+  #[serde(with = "time::serde::rfc3339")]
+  #[specta(type = String)]
+  pub started_at: OffsetDateTime,
+  pub latency_ms: u64,
+  /// Size of the response bodies received, 0 if served from the cache.
+  pub response_bytes: u64,
+  pub cache: CacheStatus,
+  /// Number of HTTP requests sent, including retries and replays.
+  pub requests: u32,
+  /// The error code, if the operation failed.
+  pub error: Option<String>,
+}
+
+/// Aggregates of the recent operations with the same name.
+#[derive(Debug, Clone, Serialize, specta::Type)]
+#[serde(rename_all = "camelCase")]
+pub struct OperationSummary {
+  pub operation: String,
+  pub count: u32,
+  pub errors: u32,
+  pub cache_hits: u32,
+  pub p50_latency_ms: u64,
+  pub p95_latency_ms: u64,
+  pub max_latency_ms: u64,
+  pub response_bytes: u64,
+}
+
+/// The recent operations of an `ApiClient`.
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+  recent: Arc<Mutex<VecDeque<OperationMetrics>>>,
+}
+
+impl Metrics {
+  /// Run `operation`, keeping what [`record`] collected meanwhile.
+  pub(crate) async fn measure<T>(
+    &self,
+    operation: &str,
+    execute: impl Future<Output = Result<T>>,
+  ) -> Result<T> {
+    let started = Instant::now();
+    let metrics = RefCell::new(OperationMetrics {
+      operation: operation.to_string(),
+      trace_id: None,
+      started_at: OffsetDateTime::now_utc(),
+      latency_ms: 0,
+      response_bytes: 0,
+      cache: CacheStatus::default(),
+      requests: 0,
+      error: None,
+    });
+    let (mut metrics, result) = CURRENT
+      .scope(metrics, async {
+        let result = execute.await;
+        (CURRENT.with(|metrics| metrics.borrow().clone()), result)
+      })
+      .await;
+
+    metrics.latency_ms = started.elapsed().as_millis() as u64;
+    if let Err(err) = &result {
+      metrics.error = Some(err.custom_context_or_root_cause().code.to_string());
+    }
+    let mut recent = self.lock();
+    if recent.len() == MAX_RECENT {
+      recent.pop_front();
+    }
+    recent.push_back(metrics);
+    result
+  }
+
+  /// The recent operations, oldest first.
+  pub fn recent(&self) -> Vec<OperationMetrics> {
+    self.lock().iter().cloned().collect()
+  }
+
+  /// The recent operations aggregated by name.
+  pub fn summary(&self) -> Vec<OperationSummary> {
+    let mut operations: BTreeMap<String, Vec<OperationMetrics>> = BTreeMap::new();
+    for metrics in self.lock().iter() {
+      operations
+        .entry(metrics.operation.clone())
+        .or_default()
+        .push(metrics.clone());
+    }
+
+    operations
+      .into_iter()
+      .map(|(operation, metrics)| {
+        let mut latencies: Vec<_> = metrics.iter().map(|metrics| metrics.latency_ms).collect();
+        latencies.sort_unstable();
+        let percentile = |p: usize| latencies[(latencies.len() - 1) * p / 100];
+        OperationSummary {
+          operation,
+          count: metrics.len() as u32,
+          errors: metrics.iter().filter(|m| m.error.is_some()).count() as u32,
+          cache_hits: metrics
+            .iter()
+            .filter(|m| m.cache == CacheStatus::Hit)
+            .count() as u32,
+          p50_latency_ms: percentile(50),
+          p95_latency_ms: percentile(95),
+          max_latency_ms: percentile(100),
+          response_bytes: metrics.iter().map(|m| m.response_bytes).sum(),
+        }
+      })
+      .collect()
+  }
+
+  fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<OperationMetrics>> {
+    self.recent.lock().unwrap_or_else(|err| err.into_inner())
+  }
+}
+
+/// Update the metrics of the operation being measured, if any.
+pub(crate) fn record(update: impl FnOnce(&mut OperationMetrics)) {
+  let _ = CURRENT.try_with(|metrics| update(&mut metrics.borrow_mut()));
+}
@@ -0,0 +1,66 @@
+use crate::client::ApiClient;
+use anyhow::Result;
+use std::{sync::Arc, time::Duration};
+use tokio::sync::watch;
+
+/// Tracks whether the API is reachable, updated by every request sent by `ApiClient`.
+#[derive(Debug, Clone)]
+pub struct Connectivity(Arc<watch::Sender<bool>>);
+
+impl Default for Connectivity {
+  fn default() -> Self {
+    Self(Arc::new(watch::channel(true).0))
+  }
+}
+
+impl Connectivity {
+  pub fn is_online(&self) -> bool {
+    *self.0.borrow()
+  }
+
+  pub(crate) fn set_online(&self, online: bool) {
+    self.0.send_if_modified(|current| {
+      if *current == online {
+        return false;
+      }
+      tracing::info!(online, "API connectivity changed");
+      *current = online;
+      true
+    });
+  }
+
+  pub fn subscribe(&self) -> watch::Receiver<bool> {
+    self.0.subscribe()
+  }
+}
+
+impl ApiClient {
+  /// Call `on_change` whenever the API becomes reachable or unreachable, and probe
+  /// it every `interval` while it's unreachable.
+  pub fn watch_connectivity(
+    &self,
+    interval: Duration,
+    on_change: impl Fn(bool) -> Result<()> + Send + Sync + 'static,
+  ) {
+    let client = self.clone();
+    let mut changes = self.connectivity().subscribe();
+    tokio::spawn(async move {
+      loop {
+        tokio::select! {
+          changed = changes.changed() => {
+            if changed.is_err() {
+              break;
+            }
+            let online = *changes.borrow_and_update();
+            if let Err(err) = on_change(online) {
+              tracing::error!("Failed to send connectivity change: {:?}", err);
+            }
+          }
+          _ = tokio::time::sleep(interval), if !client.connectivity().is_online() => {
+            client.probe().await;
+          }
+        }
+      }
+    });
+  }
+}
@@ -0,0 +1,98 @@
+//! Optimistic results of the mutations kept in the outbox, so the UI reflects them
+//! before they reach the server.
+
+use crate::{
+  add_favorite_provider, cache::ResponseCache, preferences, providers, remove_favorite_provider,
+  update_preferences,
+};
+use anyhow::Result;
+use serde_json::{Value, json};
+
+/// The response the server is expected to return for the mutation.
+pub(crate) fn response(operation: &str, variables: &Value) -> Option<Value> {
+  match operation {
+    add_favorite_provider::OPERATION_NAME => Some(json!({ "addFavoriteProvider": true })),
+    remove_favorite_provider::OPERATION_NAME => Some(json!({ "removeFavoriteProvider": true })),
+    update_preferences::OPERATION_NAME => Some(json!({
+      "updatePreferences": {
+        "country": variables["country"],
+        "language": variables["language"],
+      }
+    })),
+    _ => None,
+  }
+}
+
+/// Apply the mutation of `user` to their cached reads it affects.
+pub(crate) async fn apply(
+  cache: &ResponseCache,
+  operation: &str,
+  variables: &Value,
+  user: Option<&str>,
+) -> Result<()> {
+  let country = &variables["country"];
+  let provider_key = &variables["providerKey"];
+  let is_favorites_of_country =
+    |vars: &Value| vars["country"] == *country && vars["favorites"] == Value::Bool(true);
+
+  match operation {
+    add_favorite_provider::OPERATION_NAME => {
+      // the favourites list only holds keys we know about from any other list
+      let provider = cache
+        .entries(providers::OPERATION_NAME, user)
+        .await?
+        .into_iter()
+        .filter(|entry| entry.variables["country"] == *country)
+        .find_map(|entry| {
+          entry.data["providers"]
+            .as_array()?
+            .iter()
+            .find(|provider| provider["key"] == *provider_key)
+            .cloned()
+        });
+      let Some(provider) = provider else {
+        return Ok(());
+      };
+
+      cache
+        .update(providers::OPERATION_NAME, user, |vars, data| {
+          let Some(list) = data.get_mut("providers").and_then(Value::as_array_mut) else {
+            return false;
+          };
+          if !is_favorites_of_country(vars) || list.iter().any(|p| p["key"] == *provider_key) {
+            return false;
+          }
+          list.push(provider.clone());
+          true
+        })
+        .await
+    }
+    remove_favorite_provider::OPERATION_NAME => {
+      cache
+        .update(providers::OPERATION_NAME, user, |vars, data| {
+          let Some(list) = data.get_mut("providers").and_then(Value::as_array_mut) else {
+            return false;
+          };
+          if !is_favorites_of_country(vars) {
+            return false;
+          }
+          let len = list.len();
+          list.retain(|p| p["key"] != *provider_key);
+          list.len() != len
+        })
+        .await
+    }
+    update_preferences::OPERATION_NAME => {
+      cache
+        .update(preferences::OPERATION_NAME, user, |_, data| {
+          data["preferences"] = json!({
+            "country": variables["country"],
+            "language": variables["language"],
+          });
+          true
+        })
+        .await
+    }
+    _ => Ok(()),
+  }
+}
@@ -0,0 +1,490 @@
+use crate::{
+  client::{ApiClient, OperationKind, RequestOptions},
+  error::GraphqlResponse,
+  limiter::Priority,
+  retry::RetryReason,
+};
+use anyhow::Result;
+use popcorntime_error::{AnyhowContextExt, Code};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+  path::{Path, PathBuf},
+  sync::Arc,
+};
+use time::OffsetDateTime;
+use tokio::sync::Mutex;
+
+/// How a mutation is kept in the [`Outbox`] while the API is unreachable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutboxPolicy {
+  /// Every call is queued.
+  Queue,
+  /// Only the latest call of the operation is kept.
+  Replace,
+  /// A call cancels a pending call of the `opposite` operation with the same variables,
+  /// e.g. adding a favourite provider and removing it again.
+  Toggle { opposite: &'static str },
+}
+
+/// A mutation waiting to be sent to the API.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingMutation {
+  pub id: u64,
+  pub operation: String,
+  /// The complete GraphQL request body, with the query document and variables.
+  pub body: Value,
+  pub idempotent: bool,
+  #[serde(default)]
+  pub invalidates: Vec<String>,
+  #[serde(with = "time::serde::rfc3339")]
+  pub queued_at: OffsetDateTime,
+  /// The user who made the mutation, it's never replayed for another one.
+  #[serde(default)]
+  pub user: Option<String>,
+}
+
+impl PendingMutation {
+  pub fn variables(&self) -> &Value {
+    &self.body["variables"]
+  }
+}
+
+/// What happened to a queued mutation, reported to the frontend.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase", tag = "status")]
+pub enum OutboxEvent {
+  Queued {
+    operation: String,
+    variables: Value,
+  },
+  Replayed {
+    operation: String,
+    variables: Value,
+  },
+  /// The server refused the mutation because the data changed in the meantime.
+  Conflict {
+    operation: String,
+    variables: Value,
+    message: String,
+  },
+  /// The server refused the mutation, it won't be retried.
+  Failed {
+    operation: String,
+    variables: Value,
+    message: String,
+  },
+}
+
+/// Durable queue of mutations made while the API was unreachable, replayed in order
+/// once it's reachable again.
+#[derive(Clone)]
+pub struct Outbox {
+  path: PathBuf,
+  pending: Arc<Mutex<Vec<PendingMutation>>>,
+  on_event: Option<Arc<dyn Fn(OutboxEvent) -> Result<()> + Send + Sync>>,
+}
+
+impl std::fmt::Debug for Outbox {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "Outbox({:?})", self.path)
+  }
+}
+
+impl Outbox {
+  /// Load the pending mutations stored at `path`, if any.
+  ///
+  /// An unreadable file is moved aside to `*.corrupt`, starting with an empty outbox
+  /// rather than preventing the app from starting.
+  pub fn new(path: &Path) -> Result<Self> {
+    let pending = match std::fs::read(path) {
+      Ok(content) => match serde_json::from_slice(&content) {
+        Ok(pending) => pending,
+        Err(err) => {
+          let corrupt_path = path.with_extension("json.corrupt");
+          tracing::error!("Invalid outbox, moving it to {:?}: {:?}", corrupt_path, err);
+          std::fs::rename(path, corrupt_path)?;
+          Vec::new()
+        }
+      },
+      Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+      Err(err) => return Err(err.into()),
+    };
+
+    Ok(Self {
+      path: path.to_path_buf(),
+      pending: Arc::new(Mutex::new(pending)),
+      on_event: None,
+    })
+  }
+
+  /// Register a callback invoked whenever a mutation is queued or replayed.
+  pub fn with_on_event(
+    mut self,
+    on_event: impl Fn(OutboxEvent) -> Result<()> + Send + Sync + 'static,
+  ) -> Self {
+    self.on_event = Some(Arc::new(on_event));
+    self
+  }
+
+  pub async fn pending(&self) -> Vec<PendingMutation> {
+    self.pending.lock().await.clone()
+  }
+
+  pub async fn is_empty(&self) -> bool {
+    self.pending.lock().await.is_empty()
+  }
+
+  /// Drop the pending mutations, e.g. when the user logs out.
+  pub async fn clear(&self) -> Result<()> {
+    let mut pending = self.pending.lock().await;
+    pending.clear();
+    self.save(&pending).await
+  }
+
+  pub(crate) async fn push(
+    &self,
+    operation: &'static str,
+    body: Value,
+    options: RequestOptions,
+    policy: OutboxPolicy,
+    user: Option<String>,
+  ) -> Result<()> {
+    let mut pending = self.pending.lock().await;
+    let mutation = PendingMutation {
+      id: pending.iter().map(|m| m.id + 1).max().unwrap_or_default(),
+      operation: operation.to_string(),
+      body,
+      idempotent: matches!(options.kind, OperationKind::Mutation { idempotent: true }),
+      invalidates: options
+        .invalidates
+        .iter()
+        .map(ToString::to_string)
+        .collect(),
+      queued_at: OffsetDateTime::now_utc(),
+      user,
+    };
+    let event = OutboxEvent::Queued {
+      operation: mutation.operation.clone(),
+      variables: mutation.variables().clone(),
+    };
+
+    merge(&mut pending, mutation, policy);
+    self.save(&pending).await?;
+    self.notify(event);
+    Ok(())
+  }
+
+  fn notify(&self, event: OutboxEvent) {
+    if let Some(on_event) = &self.on_event
+      && let Err(err) = on_event(event)
+    {
+      tracing::error!("Failed to send outbox event: {:?}", err);
+    }
+  }
+
+  async fn save(&self, pending: &[PendingMutation]) -> Result<()> {
+    let tmp_path = self.path.with_extension("tmp");
+    tokio::fs::write(&tmp_path, serde_json::to_vec(pending)?).await?;
+    tokio::fs::rename(tmp_path, &self.path).await?;
+    Ok(())
+  }
+}
+
+/// Add `mutation` to the queue according to `policy`.
+fn merge(pending: &mut Vec<PendingMutation>, mutation: PendingMutation, policy: OutboxPolicy) {
+  match policy {
+    OutboxPolicy::Queue => pending.push(mutation),
+    OutboxPolicy::Replace => {
+      pending.retain(|m| m.operation != mutation.operation);
+      pending.push(mutation);
+    }
+    OutboxPolicy::Toggle { opposite } => {
+      let same_variables = |m: &PendingMutation| m.variables() == mutation.variables();
+      if let Some(index) = pending
+        .iter()
+        .position(|m| m.operation == opposite && same_variables(m))
+      {
+        // both calls cancel each other out
+        pending.remove(index);
+      } else if !pending
+        .iter()
+        .any(|m| m.operation == mutation.operation && same_variables(m))
+      {
+        pending.push(mutation);
+      }
+    }
+  }
+}
+
+impl ApiClient {
+  /// Send the queued mutations in order, stopping at the first one that can't reach
+  /// the API. Mutations refused by the server are dropped and reported.
+  pub async fn replay_outbox(&self) -> Result<()> {
+    let Some(outbox) = self.outbox() else {
+      return Ok(());
+    };
+
+    let user = self.credentials.user().await;
+    let mut pending = outbox.pending.lock().await;
+    while let Some(mutation) = pending.first().cloned() {
+      if mutation.user != user {
+        tracing::warn!(
+          operation = mutation.operation,
+          "dropping a mutation queued by another user"
+        );
+        pending.remove(0);
+        outbox.save(&pending).await?;
+        continue;
+      }
+
+      let options = RequestOptions {
+        kind: OperationKind::Mutation {
+          idempotent: mutation.idempotent,
+        },
+        ..Default::default()
+      };
+
+      let operation = mutation.operation.clone();
+      let variables = mutation.variables().clone();
+      let event = match self.fetch(&mutation.body, options).await {
+        Err(err) if is_transient(&err) => {
+          tracing::info!(operation, "API unreachable, keeping queued mutations");
+          return Ok(());
+        }
+        Err(err) => OutboxEvent::Failed {
+          operation,
+          variables,
+          message: format!("{err:#}"),
+        },
+        Ok(response) => {
+          let errors = GraphqlResponse::from(response).errors;
+          match errors.first() {
+            None => OutboxEvent::Replayed {
+              operation,
+              variables,
+            },
+            Some(err) if errors.iter().any(|e| e.code.as_deref() == Some("CONFLICT")) => {
+              OutboxEvent::Conflict {
+                operation,
+                variables,
+                message: err.message.clone(),
+              }
+            }
+            Some(err) => OutboxEvent::Failed {
+              operation,
+              variables,
+              message: err.message.clone(),
+            },
+          }
+        }
+      };
+
+      // refresh the reads that were updated optimistically, whatever the outcome
+      if let Some(cache) = self.cache() {
+        for operation in &mutation.invalidates {
+          if let Err(err) = cache.invalidate(operation).await {
+            tracing::warn!(
+              operation,
+              "failed to invalidate cached responses: {:?}",
+              err
+            );
+          }
+        }
+      }
+
+      pending.remove(0);
+      outbox.save(&pending).await?;
+      outbox.notify(event);
+    }
+
+    Ok(())
+  }
+
+  /// Replay the outbox now and every time the API becomes reachable again.
+  pub fn replay_outbox_in_background(&self) {
+    let client = self.clone().with_priority(Priority::Background);
+    let mut changes = self.connectivity().subscribe();
+    tokio::spawn(async move {
+      loop {
+        if let Err(err) = client.replay_outbox().await {
+          tracing::error!("Failed to replay outbox: {:?}", err);
+        }
+        loop {
+          if changes.changed().await.is_err() {
+            return;
+          }
+          if *changes.borrow_and_update() {
+            break;
+          }
+        }
+      }
+    });
+  }
+}
+
+/// Errors after which a new mutation is queued. A mutation which isn't idempotent is
+/// only queued when it surely didn't reach the server, it may have been applied when
+/// the request timed out or the server failed.
+pub(crate) fn can_queue(err: &anyhow::Error, kind: OperationKind) -> bool {
+  match kind {
+    OperationKind::Mutation { idempotent: false } => {
+      err.downcast_ref::<RetryReason>() == Some(&RetryReason::Connect)
+    }
+    _ => is_transient(err),
+  }
+}
+
+/// Errors after which the mutation is kept, to be replayed later: the API or the
+/// session is unavailable for now, as opposed to the mutation being refused.
+fn is_transient(err: &anyhow::Error) -> bool {
+  err.downcast_ref::<RetryReason>().is_some()
+    || err.custom_context().is_some_and(|ctx| {
+      matches!(
+        ctx.code,
+        Code::Offline | Code::InvalidSession | Code::GraphqlRateLimited
+      )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn mutation(id: u64, operation: &str, variables: Value) -> PendingMutation {
+    PendingMutation {
+      id,
+      operation: operation.to_string(),
+      body: json!({ "variables": variables }),
+      idempotent: false,
+      invalidates: Vec::new(),
+      queued_at: OffsetDateTime::UNIX_EPOCH,
+      user: None,
+    }
+  }
+
+  const ADD: OutboxPolicy = OutboxPolicy::Toggle {
+    opposite: "RemoveFavoriteProvider",
+  };
+  const REMOVE: OutboxPolicy = OutboxPolicy::Toggle {
+    opposite: "AddFavoriteProvider",
+  };
+
+  #[test]
+  fn opposite_toggles_cancel_out() {
+    let netflix = json!({ "country": "US", "providerKey": "netflix" });
+    let hulu = json!({ "country": "US", "providerKey": "hulu" });
+    let mut pending = Vec::new();
+
+    merge(
+      &mut pending,
+      mutation(0, "AddFavoriteProvider", netflix.clone()),
+      ADD,
+    );
+    merge(
+      &mut pending,
+      mutation(1, "AddFavoriteProvider", hulu.clone()),
+      ADD,
+    );
+    merge(
+      &mut pending,
+      mutation(2, "RemoveFavoriteProvider", netflix),
+      REMOVE,
+    );
+
+    assert_eq!(pending, vec![mutation(1, "AddFavoriteProvider", hulu)]);
+  }
+
+  #[test]
+  fn repeated_toggles_are_deduplicated() {
+    let netflix = json!({ "country": "US", "providerKey": "netflix" });
+    let mut pending = Vec::new();
+
+    merge(
+      &mut pending,
+      mutation(0, "AddFavoriteProvider", netflix.clone()),
+      ADD,
+    );
+    merge(
+      &mut pending,
+      mutation(1, "AddFavoriteProvider", netflix.clone()),
+      ADD,
+    );
+
+    assert_eq!(pending, vec![mutation(0, "AddFavoriteProvider", netflix)]);
+  }
+
+  #[test]
+  fn server_failures_are_transient() {
+    let unavailable = anyhow::anyhow!("unavailable")
+      .context(RetryReason::Status(
+        reqwest::StatusCode::SERVICE_UNAVAILABLE,
+      ))
+      .context(Code::GraphqlServerError);
+    assert!(is_transient(&unavailable));
+    assert!(is_transient(
+      &anyhow::anyhow!("slow down").context(Code::GraphqlRateLimited)
+    ));
+    assert!(!is_transient(
+      &anyhow::anyhow!("invalid response").context(Code::GraphqlServerError)
+    ));
+  }
+
+  #[test]
+  fn only_unsent_mutations_are_queued_unless_idempotent() {
+    let failure =
+      |reason: RetryReason, code: Code| anyhow::anyhow!("failed").context(reason).context(code);
+    let refused = failure(RetryReason::Connect, Code::Offline);
+    let timed_out = failure(RetryReason::Timeout, Code::Offline);
+    let unavailable = failure(
+      RetryReason::Status(reqwest::StatusCode::BAD_GATEWAY),
+      Code::GraphqlServerError,
+    );
+    let idempotent = OperationKind::Mutation { idempotent: true };
+    let not_idempotent = OperationKind::Mutation { idempotent: false };
+
+    assert!(can_queue(&refused, idempotent));
+    assert!(can_queue(&timed_out, idempotent));
+    assert!(can_queue(&unavailable, idempotent));
+    assert!(can_queue(&refused, not_idempotent));
+    assert!(!can_queue(&timed_out, not_idempotent));
+    assert!(!can_queue(&unavailable, not_idempotent));
+  }
+
+  #[tokio::test]
+  async fn corrupt_outbox_is_moved_aside() {
+    let dir = std::env::temp_dir().join(format!("popcorntime-outbox-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("outbox.json");
+    std::fs::write(&path, b"[{\"id\":").unwrap();
+
+    let outbox = Outbox::new(&path).unwrap();
+    assert!(outbox.is_empty().await);
+    assert!(dir.join("outbox.json.corrupt").exists());
+    let _ = std::fs::remove_dir_all(&dir);
+  }
+
+  #[test]
+  fn replace_keeps_the_latest_call() {
+    let mut pending = Vec::new();
+
+    merge(
+      &mut pending,
+      mutation(0, "UpdatePreferences", json!({ "country": "US" })),
+      OutboxPolicy::Replace,
+    );
+    merge(
+      &mut pending,
+      mutation(1, "UpdatePreferences", json!({ "country": "FR" })),
+      OutboxPolicy::Replace,
+    );
+
+    assert_eq!(
+      pending,
+      vec![mutation(1, "UpdatePreferences", json!({ "country": "FR" }))]
+    );
+  }
+}
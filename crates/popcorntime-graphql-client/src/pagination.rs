@@ -0,0 +1,202 @@
+use crate::{client::ApiClient, limiter::Priority, search};
+use anyhow::{Context, Result};
+use futures_util::{Stream, StreamExt, stream};
+use tokio::task::JoinHandle;
+
+/// The largest page requested from the API, whatever the caller asks for.
+pub const MAX_PAGE_SIZE: i64 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageOptions {
+  /// Number of nodes requested per page, capped to [`MAX_PAGE_SIZE`].
+  pub page_size: i64,
+  /// Fetch the next page in the background as soon as a page is returned.
+  pub prefetch: bool,
+}
+
+impl Default for PageOptions {
+  fn default() -> Self {
+    Self {
+      page_size: 24,
+      prefetch: true,
+    }
+  }
+}
+
+type Page = (Vec<search::SearchSearchNodes>, Option<String>);
+
+/// A page being fetched in the background, aborted when the stream is dropped.
+struct Prefetch(JoinHandle<Result<Page>>);
+
+impl Drop for Prefetch {
+  fn drop(&mut self) {
+    self.0.abort();
+  }
+}
+
+/// The pages of a search, fetched one at a time with [`SearchPages::next`].
+pub struct SearchPages {
+  client: ApiClient,
+  vars: search::Variables,
+  options: PageOptions,
+  /// `None` once the last page was returned.
+  cursor: Option<Option<String>>,
+  prefetched: Option<Prefetch>,
+}
+
+impl SearchPages {
+  /// The nodes of the next page, `None` after the last one.
+  ///
+  /// After an error the cursor is kept, so calling it again retries the same page.
+  pub async fn next(&mut self) -> Option<Result<Vec<search::SearchSearchNodes>>> {
+    let cursor = self.cursor.take()?;
+    let page = match self.prefetched.take() {
+      Some(mut prefetch) => (&mut prefetch.0)
+        .await
+        .context("prefetch aborted")
+        .and_then(|p| p),
+      None => fetch_page(&self.client, self.page_vars(cursor.clone())).await,
+    };
+
+    match page {
+      Ok((nodes, next_cursor)) => {
+        if let Some(next_cursor) = next_cursor {
+          if self.options.prefetch {
+            let client = self.client.clone().with_priority(Priority::Background);
+            let vars = self.page_vars(Some(next_cursor.clone()));
+            self.prefetched = Some(Prefetch(tokio::spawn(async move {
+              fetch_page(&client, vars).await
+            })));
+          }
+          self.cursor = Some(Some(next_cursor));
+        }
+        Some(Ok(nodes))
+      }
+      Err(err) => {
+        self.cursor = Some(cursor);
+        Some(Err(err))
+      }
+    }
+  }
+
+  /// Whether the page returned last said another one follows, without fetching it.
+  pub fn has_next_page(&self) -> bool {
+    self.cursor.is_some()
+  }
+
+  /// The variables of the page after `cursor`, going backwards when the caller asked
+  /// for the `last` nodes.
+  fn page_vars(&self, cursor: Option<String>) -> search::Variables {
+    let page_size = Some(self.options.page_size.clamp(1, MAX_PAGE_SIZE));
+    let mut vars = self.vars.clone();
+    if vars.last.is_some() {
+      vars.last = page_size;
+      vars.before = cursor.or(vars.before);
+    } else {
+      vars.first = page_size;
+      vars.after = cursor.or(vars.after);
+    }
+    vars
+  }
+}
+
+/// Fetch a page, returning its nodes and the cursor of the next one, if any.
+async fn fetch_page(client: &ApiClient, vars: search::Variables) -> Result<Page> {
+  let Some(search) = client
+    .search(&vars)
+    .await?
+    .into_data()?
+    .map(|data| data.search)
+  else {
+    return Ok((Vec::new(), None));
+  };
+  let next_cursor = search
+    .page_info
+    .end_cursor
+    .filter(|_| search.page_info.has_next_page);
+  Ok((search.nodes, next_cursor))
+}
+
+impl ApiClient {
+  /// Paginate a search, dropping the pages cancels the page being prefetched.
+  pub fn search_pages(&self, vars: search::Variables, options: PageOptions) -> SearchPages {
+    SearchPages {
+      client: self.clone(),
+      vars,
+      options,
+      cursor: Some(None),
+      prefetched: None,
+    }
+  }
+
+  /// Stream the search results, fetching pages lazily as they are consumed.
+  ///
+  /// The stream ends after the last page or the first error. Dropping it cancels the
+  /// page being prefetched.
+  pub fn search_stream(
+    &self,
+    vars: search::Variables,
+    options: PageOptions,
+  ) -> impl Stream<Item = Result<search::SearchSearchNodes>> + Send + 'static {
+    stream::unfold(Some(self.search_pages(vars, options)), |pages| async move {
+      let mut pages = pages?;
+      let page = pages.next().await?;
+      // the pages would retry the failed one
+      let pages = page.is_ok().then_some(pages);
+      Some((page, pages))
+    })
+    .flat_map(|page| {
+      let nodes = match page {
+        Ok(nodes) => nodes.into_iter().map(Ok).collect(),
+        Err(err) => vec![Err(err)],
+      };
+      stream::iter(nodes)
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{credentials::StaticToken, enums::SortKey};
+
+  fn pages(first: Option<i64>, last: Option<i64>, page_size: i64) -> SearchPages {
+    let vars = search::Variables {
+      after: None,
+      before: Some("start".to_string()),
+      first,
+      last,
+      sort_key: Some(SortKey::Id),
+      country: "US".parse().unwrap(),
+      language: None,
+      query: None,
+      arguments: None,
+    };
+    let options = PageOptions {
+      page_size,
+      prefetch: false,
+    };
+    ApiClient::new(StaticToken(None))
+      .unwrap()
+      .search_pages(vars, options)
+  }
+
+  #[test]
+  fn pages_go_forward_with_the_page_size() {
+    let vars = pages(Some(10), None, 500).page_vars(Some("next".to_string()));
+    assert_eq!(vars.first, Some(MAX_PAGE_SIZE));
+    assert_eq!(vars.after.as_deref(), Some("next"));
+    assert_eq!(vars.last, None);
+  }
+
+  #[test]
+  fn pages_go_backwards_for_the_last_nodes() {
+    let pages = pages(None, Some(10), 0);
+    let vars = pages.page_vars(None);
+    assert_eq!(vars.last, Some(1));
+    assert_eq!(vars.before.as_deref(), Some("start"));
+    assert_eq!(vars.first, None);
+    let vars = pages.page_vars(Some("previous".to_string()));
+    assert_eq!(vars.before.as_deref(), Some("previous"));
+  }
+}
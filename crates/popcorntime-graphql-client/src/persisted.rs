@@ -0,0 +1,103 @@
+//! Automatic persisted queries: operations are sent as the sha256 hash of their
+//! document, computed by `define_graphql_query!`, and the document is only sent when
+//! the server doesn't know the hash yet.
+
+use graphql_client::Response;
+use serde_json::{Value, json};
+
+/// How the operations declared with `define_graphql_query!` are sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PersistedQueries {
+  /// Always send the full document.
+  Disabled,
+  /// Send the hash in POST requests.
+  #[default]
+  Post,
+  /// Send the hash of the queries declared with `get` in GET requests, which CDNs can
+  /// cache, and the others in POST requests.
+  Get,
+}
+
+/// The persisted query of an operation, see [`RequestOptions`](crate::client::RequestOptions).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PersistedQuery {
+  /// The hex encoded sha256 of the document.
+  pub hash: &'static str,
+  /// Whether the query can be sent with GET.
+  pub get: bool,
+}
+
+/// What a request carries in place of, or along with, the document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Document {
+  Full,
+  /// Only the hash, failing with `PersistedQueryNotFound` if the server doesn't know it.
+  Hash(PersistedQuery),
+  /// The document and its hash, for the server to store it.
+  Register(PersistedQuery),
+}
+
+/// Why the server didn't run a persisted query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PersistedQueryError {
+  NotFound,
+  NotSupported,
+}
+
+impl Document {
+  /// The request body for `body`, the full [`graphql_client::QueryBody`].
+  pub fn body(&self, body: &Value) -> Value {
+    let (persisted, keep_query) = match self {
+      Document::Full => return body.clone(),
+      Document::Hash(persisted) => (persisted, false),
+      Document::Register(persisted) => (persisted, true),
+    };
+    let mut body = body.clone();
+    if let Some(body) = body.as_object_mut() {
+      if !keep_query {
+        body.remove("query");
+      }
+      body.insert("extensions".to_string(), extensions(persisted));
+    }
+    body
+  }
+
+  /// The query string of a GET request, which only carries the hash.
+  pub fn query_string(persisted: &PersistedQuery, body: &Value) -> [(&'static str, String); 3] {
+    [
+      (
+        "operationName",
+        body["operationName"]
+          .as_str()
+          .unwrap_or_default()
+          .to_string(),
+      ),
+      ("variables", body["variables"].to_string()),
+      ("extensions", extensions(persisted).to_string()),
+    ]
+  }
+}
+
+fn extensions(persisted: &PersistedQuery) -> Value {
+  json!({ "persistedQuery": { "version": 1, "sha256Hash": persisted.hash } })
+}
+
+/// The error returned in place of the response when the server didn't run the query.
+pub(crate) fn persisted_query_error(response: &Response<Value>) -> Option<PersistedQueryError> {
+  response.errors.iter().flatten().find_map(|err| {
+    let code = err
+      .extensions
+      .as_ref()
+      .and_then(|extensions| extensions.get("code"))
+      .and_then(Value::as_str);
+    match (err.message.as_str(), code) {
+      ("PersistedQueryNotFound", _) | (_, Some("PERSISTED_QUERY_NOT_FOUND")) => {
+        Some(PersistedQueryError::NotFound)
+      }
+      ("PersistedQueryNotSupported", _) | (_, Some("PERSISTED_QUERY_NOT_SUPPORTED")) => {
+        Some(PersistedQueryError::NotSupported)
+      }
+      _ => None,
+    }
+  })
+}
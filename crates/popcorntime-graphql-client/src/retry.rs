@@ -0,0 +1,190 @@
+use reqwest::{StatusCode, header};
+use std::time::Duration;
+use time::{OffsetDateTime, PrimitiveDateTime, macros::format_description};
+
+/// How `ApiClient` retries requests that failed for a transient reason.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+  /// Total number of attempts, including the first one.
+  pub max_attempts: u32,
+  /// Delay before the first retry, doubled on every following attempt.
+  pub base_delay: Duration,
+  /// Upper bound for a single delay, including a server provided `Retry-After`.
+  pub max_delay: Duration,
+  /// Timeout of a single attempt.
+  pub timeout: Duration,
+}
+
+impl Default for RetryPolicy {
+  fn default() -> Self {
+    Self {
+      max_attempts: 3,
+      base_delay: Duration::from_millis(250),
+      max_delay: Duration::from_secs(10),
+      timeout: Duration::from_secs(5),
+    }
+  }
+}
+
+/// Why an attempt should be retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryReason {
+  Connect,
+  Timeout,
+  Status(StatusCode),
+}
+
+impl std::fmt::Display for RetryReason {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::Connect => f.write_str("connection failed"),
+      Self::Timeout => f.write_str("timed out"),
+      Self::Status(status) => write!(f, "server responded with {status}"),
+    }
+  }
+}
+
+impl RetryReason {
+  /// Classify a transport error, returning `None` if retrying would not help.
+  pub fn from_error(err: &reqwest::Error) -> Option<Self> {
+    if err.is_timeout() {
+      Some(Self::Timeout)
+    } else if err.is_connect() {
+      Some(Self::Connect)
+    } else {
+      None
+    }
+  }
+
+  /// Classify a response status, returning `None` if retrying would not help.
+  pub fn from_status(status: StatusCode) -> Option<Self> {
+    match status {
+      StatusCode::BAD_GATEWAY
+      | StatusCode::SERVICE_UNAVAILABLE
+      | StatusCode::GATEWAY_TIMEOUT
+      | StatusCode::TOO_MANY_REQUESTS => Some(Self::Status(status)),
+      _ => None,
+    }
+  }
+}
+
+impl RetryPolicy {
+  /// A policy making a single attempt.
+  pub fn none() -> Self {
+    Self {
+      max_attempts: 1,
+      ..Default::default()
+    }
+  }
+
+  /// Whether another attempt is allowed after `attempt` (starting at 1) failed.
+  pub fn should_retry(&self, attempt: u32) -> bool {
+    attempt < self.max_attempts
+  }
+
+  /// The delay before retrying after `attempt` (starting at 1) failed.
+  ///
+  /// Uses exponential backoff with "full jitter", unless the server asked for a
+  /// specific delay with `Retry-After`.
+  pub fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+    if let Some(retry_after) = retry_after {
+      return retry_after.min(self.max_delay);
+    }
+
+    let exponential = self
+      .base_delay
+      .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+      .min(self.max_delay);
+    exponential.mul_f64(fastrand::f64())
+  }
+}
+
+/// Parse the `Retry-After` header, either delay-seconds or an HTTP-date.
+pub fn retry_after(headers: &header::HeaderMap) -> Option<Duration> {
+  parse_retry_after(
+    headers.get(header::RETRY_AFTER)?.to_str().ok()?,
+    OffsetDateTime::now_utc(),
+  )
+}
+
+fn parse_retry_after(value: &str, now: OffsetDateTime) -> Option<Duration> {
+  let value = value.trim();
+  if let Ok(seconds) = value.parse::<u64>() {
+    return Some(Duration::from_secs(seconds));
+  }
+  // the IMF-fixdate of RFC 9110, e.g. `Wed, 21 Oct 2015 07:28:00 GMT`
+  let date = PrimitiveDateTime::parse(
+    value,
+    format_description!(
+      "[weekday repr:short], [day] [month repr:short] [year] [hour]:[minute]:[second] GMT"
+    ),
+  )
+  .ok()?
+  .assume_utc();
+  // a date in the past means retrying right away
+  Some((date - now).try_into().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use time::macros::datetime;
+
+  #[test]
+  fn delay_is_capped() {
+    let policy = RetryPolicy::default();
+    for attempt in 1..20 {
+      assert!(policy.delay(attempt, None) <= policy.max_delay);
+    }
+    assert!(policy.delay(1, None) <= policy.base_delay);
+    assert_eq!(
+      policy.delay(1, Some(Duration::from_secs(60))),
+      policy.max_delay
+    );
+    assert_eq!(
+      policy.delay(1, Some(Duration::from_secs(2))),
+      Duration::from_secs(2)
+    );
+  }
+
+  #[test]
+  fn parses_retry_after_seconds_and_dates() {
+    let now = datetime!(2015-10-21 07:27:30 UTC);
+    assert_eq!(
+      parse_retry_after(" 120 ", now),
+      Some(Duration::from_secs(120))
+    );
+    assert_eq!(
+      parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT", now),
+      Some(Duration::from_secs(30))
+    );
+    assert_eq!(
+      parse_retry_after("Wed, 21 Oct 2015 07:00:00 GMT", now),
+      Some(Duration::ZERO)
+    );
+    assert_eq!(parse_retry_after("soon", now), None);
+  }
+
+  #[test]
+  fn classifies_statuses() {
+    for status in [
+      StatusCode::BAD_GATEWAY,
+      StatusCode::SERVICE_UNAVAILABLE,
+      StatusCode::GATEWAY_TIMEOUT,
+      StatusCode::TOO_MANY_REQUESTS,
+    ] {
+      assert_eq!(
+        RetryReason::from_status(status),
+        Some(RetryReason::Status(status))
+      );
+    }
+    for status in [
+      StatusCode::OK,
+      StatusCode::BAD_REQUEST,
+      StatusCode::UNAUTHORIZED,
+      StatusCode::INTERNAL_SERVER_ERROR,
+    ] {
+      assert_eq!(RetryReason::from_status(status), None);
+    }
+  }
+}
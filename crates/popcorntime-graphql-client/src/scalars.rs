@@ -0,0 +1,242 @@
+//! Custom GraphQL scalars, validated locally and serialized in the server's format.
+
+use isocountry::CountryCode;
+use language_tags::LanguageTag;
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+use specta::Type;
+use std::{fmt, str::FromStr};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339, macros::format_description};
+
+/// A scalar value which doesn't have the expected format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidScalar {
+  pub scalar: &'static str,
+  pub value: String,
+}
+
+impl fmt::Display for InvalidScalar {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "invalid {} `{}`", self.scalar, self.value)
+  }
+}
+
+impl std::error::Error for InvalidScalar {}
+
+impl InvalidScalar {
+  fn new(scalar: &'static str, value: &str) -> Self {
+    Self {
+      scalar,
+      value: value.to_string(),
+    }
+  }
+}
+
+/// Serialize a scalar with its `Display` form and deserialize it with `FromStr`.
+macro_rules! string_scalar {
+  ($name:ident) => {
+    impl Serialize for $name {
+      fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+      }
+    }
+
+    impl<'de> Deserialize<'de> for $name {
+      fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(de::Error::custom)
+      }
+    }
+  };
+}
+
+/// An ISO 3166-1 alpha-2 country code, e.g. `US`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Type)]
+#[specta(transparent)]
+pub struct Country(#[specta(type = String)] CountryCode);
+
+impl Country {
+  pub fn code(&self) -> CountryCode {
+    self.0
+  }
+
+  /// The upper case alpha-2 code.
+  pub fn as_str(&self) -> &'static str {
+    self.0.alpha2()
+  }
+}
+
+impl FromStr for Country {
+  type Err = InvalidScalar;
+
+  fn from_str(value: &str) -> Result<Self, Self::Err> {
+    CountryCode::for_alpha2_caseless(value)
+      .map(Self)
+      .map_err(|_| InvalidScalar::new("country", value))
+  }
+}
+
+impl From<CountryCode> for Country {
+  fn from(code: CountryCode) -> Self {
+    Self(code)
+  }
+}
+
+impl fmt::Display for Country {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+string_scalar!(Country);
+
+/// A BCP-47 language tag whose primary language is an ISO 639 code, e.g. `en` or `pt-BR`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Type)]
+#[specta(transparent)]
+pub struct Language(#[specta(type = String)] LanguageTag);
+
+impl Language {
+  /// The primary language subtag, e.g. `pt` for `pt-BR`.
+  pub fn primary_language(&self) -> isolang::Language {
+    primary_language(&self.0).expect("validated when parsed")
+  }
+
+  pub fn as_str(&self) -> &str {
+    self.0.as_str()
+  }
+}
+
+fn primary_language(tag: &LanguageTag) -> Option<isolang::Language> {
+  let code = tag.primary_language().to_ascii_lowercase();
+  match code.len() {
+    2 => isolang::Language::from_639_1(&code),
+    3 => isolang::Language::from_639_3(&code),
+    _ => None,
+  }
+}
+
+impl FromStr for Language {
+  type Err = InvalidScalar;
+
+  fn from_str(value: &str) -> Result<Self, Self::Err> {
+    LanguageTag::parse(value)
+      .ok()
+      .filter(|tag| primary_language(tag).is_some())
+      .map(Self)
+      .ok_or_else(|| InvalidScalar::new("language", value))
+  }
+}
+
+impl fmt::Display for Language {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+string_scalar!(Language);
+
+/// A calendar date, `YYYY-MM-DD` on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Type)]
+#[specta(transparent)]
+pub struct Date(#[specta(type = String)] pub time::Date);
+
+const DATE_FORMAT: &[time::format_description::FormatItem<'static>] =
+  format_description!("[year]-[month]-[day]");
+
+impl FromStr for Date {
+  type Err = InvalidScalar;
+
+  fn from_str(value: &str) -> Result<Self, Self::Err> {
+    time::Date::parse(value, DATE_FORMAT)
+      .map(Self)
+      .map_err(|_| InvalidScalar::new("date", value))
+  }
+}
+
+impl From<time::Date> for Date {
+  fn from(date: time::Date) -> Self {
+    Self(date)
+  }
+}
+
+impl fmt::Display for Date {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let date = self.0.format(DATE_FORMAT).map_err(|_| fmt::Error)?;
+    f.write_str(&date)
+  }
+}
+
+string_scalar!(Date);
+
+/// A point in time, RFC 3339 on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Type)]
+#[specta(transparent)]
+pub struct DateTime(#[specta(type = String)] pub OffsetDateTime);
+
+impl FromStr for DateTime {
+  type Err = InvalidScalar;
+
+  fn from_str(value: &str) -> Result<Self, Self::Err> {
+    OffsetDateTime::parse(value, &Rfc3339)
+      .map(Self)
+      .map_err(|_| InvalidScalar::new("date time", value))
+  }
+}
+
+impl From<OffsetDateTime> for DateTime {
+  fn from(date_time: OffsetDateTime) -> Self {
+    Self(date_time)
+  }
+}
+
+impl fmt::Display for DateTime {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let date_time = self.0.format(&Rfc3339).map_err(|_| fmt::Error)?;
+    f.write_str(&date_time)
+  }
+}
+
+string_scalar!(DateTime);
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn country_is_an_upper_case_alpha2_code() {
+    let country: Country = serde_json::from_value(json!("fr")).unwrap();
+    assert_eq!(serde_json::to_value(country).unwrap(), json!("FR"));
+    assert!("XX".parse::<Country>().is_err());
+    assert!("FRA".parse::<Country>().is_err());
+  }
+
+  #[test]
+  fn language_needs_a_known_primary_language() {
+    for tag in ["en", "pt-BR", "zh-Hant-TW", "fil"] {
+      let language: Language = serde_json::from_value(json!(tag)).unwrap();
+      assert_eq!(serde_json::to_value(&language).unwrap(), json!(tag));
+    }
+    for tag in ["", "xx", "en_US", "english"] {
+      assert!(tag.parse::<Language>().is_err(), "{tag}");
+    }
+  }
+
+  #[test]
+  fn dates_round_trip() {
+    let date: Date = serde_json::from_value(json!("2024-02-29")).unwrap();
+    assert_eq!(serde_json::to_value(date).unwrap(), json!("2024-02-29"));
+    assert!("2023-02-29".parse::<Date>().is_err());
+
+    let date_time: DateTime = serde_json::from_value(json!("2024-02-29T10:00:00Z")).unwrap();
+    assert_eq!(
+      serde_json::to_value(date_time).unwrap(),
+      json!("2024-02-29T10:00:00Z")
+    );
+    assert_eq!(
+      serde_json::from_value::<DateTime>(json!("2024-02-29"))
+        .unwrap_err()
+        .to_string(),
+      "invalid date time `2024-02-29`"
+    );
+  }
+}
@@ -0,0 +1,619 @@
+//! GraphQL subscriptions over the `graphql-transport-ws` protocol.
+//!
+//! All the subscriptions of an [`ApiClient`] share one WebSocket, opened with the
+//! first subscription and closed once the last one completed. The access token is
+//! sent in the `connection_init` payload; when the connection drops, the client
+//! reconnects with the backoff of its [`RetryPolicy`](crate::retry::RetryPolicy)
+//! and subscribes again to every active operation.
+
+use crate::{
+  client::{ApiClient, USER_AGENT},
+  error::GraphqlResponse,
+};
+use anyhow::{Context, Result};
+use futures_util::{SinkExt, Stream, StreamExt};
+use graphql_client::{QueryBody, Response};
+use popcorntime_error::{AnyhowContextExt, Code};
+use reqwest::Url;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+use std::{
+  collections::BTreeMap,
+  marker::PhantomData,
+  pin::Pin,
+  sync::{
+    Arc, Mutex,
+    atomic::{AtomicU64, Ordering},
+  },
+  task::{Context as TaskContext, Poll},
+  time::Duration,
+};
+use tokio::sync::mpsc;
+use tokio_tungstenite::{
+  WebSocketStream,
+  tungstenite::{
+    Message,
+    client::IntoClientRequest,
+    http::{HeaderValue, header},
+    protocol::CloseFrame,
+  },
+};
+
+const PROTOCOL: &str = "graphql-transport-ws";
+/// How long the server has to acknowledge `connection_init`.
+const ACK_TIMEOUT: Duration = Duration::from_secs(10);
+
+type Socket = WebSocketStream<Box<dyn popcorntime_http::Stream>>;
+type Event = Result<Response<Value>>;
+/// The payload and event sender of the active subscriptions, by id.
+type Active = BTreeMap<u64, (Value, mpsc::UnboundedSender<Event>)>;
+
+/// The connection shared by the subscriptions of an [`ApiClient`].
+#[derive(Clone, Default)]
+pub(crate) struct Subscriptions {
+  next_id: Arc<AtomicU64>,
+  /// Commands of the task owning the WebSocket, `None` while no subscription is active.
+  commands: Arc<Mutex<Option<mpsc::UnboundedSender<Command>>>>,
+}
+
+enum Command {
+  Subscribe {
+    id: u64,
+    payload: Value,
+    events: mpsc::UnboundedSender<Event>,
+  },
+  Complete {
+    id: u64,
+  },
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage<'a> {
+  ConnectionInit {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    payload: Option<Value>,
+  },
+  Pong,
+  Subscribe {
+    id: String,
+    payload: &'a Value,
+  },
+  Complete {
+    id: String,
+  },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage {
+  ConnectionAck,
+  Ping,
+  Pong,
+  Next {
+    id: String,
+    payload: Response<Value>,
+  },
+  Error {
+    id: String,
+    payload: Vec<graphql_client::Error>,
+  },
+  Complete {
+    id: String,
+  },
+}
+
+/// The state kept between the connections of [`run`], reset once one is acknowledged.
+#[derive(Default)]
+struct Reconnect {
+  attempt: u32,
+  reauthenticated: bool,
+  /// The token of the last connection, not refreshed again if it was replaced since.
+  access_token: Option<String>,
+}
+
+/// Why the connection ended.
+enum Disconnect {
+  /// Every subscription completed.
+  Idle,
+  /// The connection dropped, it's opened again after a delay.
+  Retry(anyhow::Error),
+  /// The server rejected the access token, the session is refreshed before reconnecting.
+  Unauthorized,
+  /// Reconnecting would fail the same way, the active subscriptions are failed.
+  Fatal(anyhow::Error),
+}
+
+/// The events of an operation started with [`ApiClient::subscribe`].
+///
+/// The stream ends when the server completes the operation, dropping it completes
+/// the operation on the server.
+pub struct Subscription<T> {
+  id: u64,
+  events: mpsc::UnboundedReceiver<Event>,
+  commands: mpsc::UnboundedSender<Command>,
+  data: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> Stream for Subscription<T> {
+  type Item = Result<GraphqlResponse<T>>;
+
+  fn poll_next(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Self::Item>> {
+    self.events.poll_recv(cx).map(|event| {
+      event.map(|event| {
+        GraphqlResponse::from(event?)
+          .try_map(serde_json::from_value)
+          .context(Code::GraphqlServerError)
+      })
+    })
+  }
+}
+
+impl<T> Drop for Subscription<T> {
+  fn drop(&mut self) {
+    let _ = self.commands.send(Command::Complete { id: self.id });
+  }
+}
+
+impl ApiClient {
+  /// Start a subscription, connecting to the API if no other subscription is active.
+  ///
+  /// The WebSocket URL is the client URL with the `ws` or `wss` scheme.
+  pub fn subscribe<T: Serialize, R>(&self, params: &QueryBody<T>) -> Result<Subscription<R>> {
+    let payload = serde_json::to_value(params).context(Code::GraphqlServerError)?;
+    let subscriptions = &self.subscriptions;
+    let id = subscriptions.next_id.fetch_add(1, Ordering::Relaxed);
+    let (events, receiver) = mpsc::unbounded_channel();
+
+    let mut commands = subscriptions
+      .commands
+      .lock()
+      .unwrap_or_else(|err| err.into_inner());
+    let sender = match commands.as_ref() {
+      Some(sender) => sender.clone(),
+      None => {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(run(self.clone(), receiver));
+        *commands = Some(sender.clone());
+        sender
+      }
+    };
+    sender
+      .send(Command::Subscribe {
+        id,
+        payload,
+        events,
+      })
+      .context("subscription task stopped")?;
+
+    Ok(Subscription {
+      id,
+      events: receiver,
+      commands: sender,
+      data: PhantomData,
+    })
+  }
+}
+
+/// Own the WebSocket until no subscription is active.
+async fn run(client: ApiClient, mut commands: mpsc::UnboundedReceiver<Command>) {
+  let mut active = Active::new();
+  let mut reconnect = Reconnect::default();
+  loop {
+    while let Ok(command) = commands.try_recv() {
+      apply(&mut active, command);
+    }
+    if active.is_empty() {
+      // `subscribe` holds the lock while sending, so no command can be lost
+      let mut sender = client
+        .subscriptions
+        .commands
+        .lock()
+        .unwrap_or_else(|err| err.into_inner());
+      match commands.try_recv() {
+        Ok(command) => apply(&mut active, command),
+        Err(_) => {
+          *sender = None;
+          return;
+        }
+      }
+      continue;
+    }
+
+    match connect(&client, &mut active, &mut commands, &mut reconnect).await {
+      Disconnect::Idle => {}
+      Disconnect::Unauthorized if reconnect.reauthenticated => {
+        client.notify_session_invalid();
+        let err = anyhow::anyhow!("Access token rejected").context(Code::InvalidSession);
+        fail(&mut active, err);
+      }
+      Disconnect::Unauthorized => {
+        reconnect.reauthenticated = true;
+        if let Err(err) = client
+          .reauthenticate(reconnect.access_token.as_deref())
+          .await
+        {
+          fail(&mut active, err);
+        }
+      }
+      Disconnect::Fatal(err) => fail(&mut active, err),
+      Disconnect::Retry(err) => {
+        reconnect.attempt += 1;
+        let attempt = reconnect.attempt;
+        let delay = client.retry_policy.delay(attempt, None);
+        tracing::warn!(attempt, ?delay, "subscription connection lost: {:?}", err);
+        let sleep = tokio::time::sleep(delay);
+        tokio::pin!(sleep);
+        loop {
+          tokio::select! {
+            _ = &mut sleep => break,
+            Some(command) = commands.recv() => apply(&mut active, command),
+          }
+        }
+      }
+    }
+  }
+}
+
+fn apply(active: &mut Active, command: Command) {
+  match command {
+    Command::Subscribe {
+      id,
+      payload,
+      events,
+    } => {
+      active.insert(id, (payload, events));
+    }
+    Command::Complete { id } => {
+      active.remove(&id);
+    }
+  }
+}
+
+/// End every active subscription with `err`.
+fn fail(active: &mut Active, err: anyhow::Error) {
+  tracing::error!("subscriptions failed: {:?}", err);
+  let message = format!("{err:#}");
+  let code = err.custom_context_or_root_cause().code;
+  for (_, (_, events)) in std::mem::take(active) {
+    let _ = events.send(Err(anyhow::anyhow!(message.clone()).context(code)));
+  }
+}
+
+/// Open the WebSocket, subscribe to the active operations and forward their events
+/// until the connection ends.
+async fn connect(
+  client: &ApiClient,
+  active: &mut Active,
+  commands: &mut mpsc::UnboundedReceiver<Command>,
+  reconnect: &mut Reconnect,
+) -> Disconnect {
+  reconnect.access_token = client.credentials.access_token().await;
+  let mut socket = match open(client, reconnect.access_token.as_deref()).await {
+    Ok(socket) => socket,
+    Err(disconnect) => return disconnect,
+  };
+  *reconnect = Reconnect {
+    access_token: reconnect.access_token.take(),
+    ..Default::default()
+  };
+
+  for (id, (payload, _)) in active.iter() {
+    let subscribe = ClientMessage::Subscribe {
+      id: id.to_string(),
+      payload,
+    };
+    if let Err(err) = send(&mut socket, &subscribe).await {
+      return Disconnect::Retry(err);
+    }
+  }
+
+  loop {
+    let result = tokio::select! {
+      command = commands.recv() => match command {
+        Some(Command::Complete { id }) if active.remove(&id).is_some() => {
+          let complete = ClientMessage::Complete { id: id.to_string() };
+          send(&mut socket, &complete).await
+        }
+        Some(Command::Subscribe { id, payload, events }) => {
+          let subscribe = ClientMessage::Subscribe { id: id.to_string(), payload: &payload };
+          let result = send(&mut socket, &subscribe).await;
+          active.insert(id, (payload, events));
+          result
+        }
+        Some(Command::Complete { .. }) | None => Ok(()),
+      },
+      message = receive(&mut socket) => match message {
+        Ok(message) => handle(&mut socket, active, message).await,
+        Err(disconnect) => return disconnect,
+      },
+    };
+    if let Err(err) = result {
+      return Disconnect::Retry(err);
+    }
+    if active.is_empty() {
+      let _ = socket.close(None).await;
+      return Disconnect::Idle;
+    }
+  }
+}
+
+/// Connect and wait for the server to acknowledge the `connection_init`.
+async fn open(client: &ApiClient, access_token: Option<&str>) -> Result<Socket, Disconnect> {
+  let url = Url::parse(&ws_url(&client.endpoints.current()))
+    .context(Code::GraphqlServerError)
+    .map_err(Disconnect::Fatal)?;
+  let mut request = url
+    .as_str()
+    .into_client_request()
+    .context(Code::GraphqlServerError)
+    .map_err(Disconnect::Fatal)?;
+  let headers = request.headers_mut();
+  headers.insert(
+    header::SEC_WEBSOCKET_PROTOCOL,
+    HeaderValue::from_static(PROTOCOL),
+  );
+  headers.insert(header::USER_AGENT, HeaderValue::from_static(USER_AGENT));
+
+  // through the proxy and with the certificates of the http client
+  let connect = async {
+    let stream = client.http_config.connect(&url).await?;
+    let (socket, _) = tokio_tungstenite::client_async(request, stream).await?;
+    anyhow::Ok(socket)
+  };
+  let mut socket = tokio::time::timeout(client.retry_policy.timeout, connect)
+    .await
+    .context("timed out")
+    .and_then(|result| result)
+    .map_err(Disconnect::Retry)?;
+
+  let payload = access_token.map(|token| json!({ "Authorization": format!("Bearer {token}") }));
+  send(&mut socket, &ClientMessage::ConnectionInit { payload })
+    .await
+    .map_err(Disconnect::Retry)?;
+
+  let ack = async {
+    loop {
+      match receive(&mut socket).await? {
+        ServerMessage::ConnectionAck => return Ok(()),
+        ServerMessage::Ping => send(&mut socket, &ClientMessage::Pong)
+          .await
+          .map_err(Disconnect::Retry)?,
+        _ => {}
+      }
+    }
+  };
+  match tokio::time::timeout(ACK_TIMEOUT, ack).await {
+    Ok(Ok(())) => Ok(socket),
+    Ok(Err(disconnect)) => Err(disconnect),
+    Err(_) => Err(Disconnect::Retry(anyhow::anyhow!(
+      "connection not acknowledged"
+    ))),
+  }
+}
+
+/// Forward a server message to its subscription.
+async fn handle(socket: &mut Socket, active: &mut Active, message: ServerMessage) -> Result<()> {
+  match message {
+    ServerMessage::Ping => send(socket, &ClientMessage::Pong).await?,
+    ServerMessage::ConnectionAck | ServerMessage::Pong => {}
+    ServerMessage::Next { id, payload } => {
+      let Ok(id) = id.parse() else {
+        return Ok(());
+      };
+      let delivered = active
+        .get(&id)
+        .is_some_and(|(_, events)| events.send(Ok(payload)).is_ok());
+      // the stream was dropped, its `Complete` command may still be queued
+      if !delivered && active.remove(&id).is_some() {
+        send(socket, &ClientMessage::Complete { id: id.to_string() }).await?;
+      }
+    }
+    ServerMessage::Error { id, payload } => {
+      if let Some((_, events)) = id.parse().ok().and_then(|id| active.remove(&id)) {
+        let _ = events.send(Ok(Response {
+          data: None,
+          errors: Some(payload),
+          extensions: None,
+        }));
+      }
+    }
+    ServerMessage::Complete { id } => {
+      if let Ok(id) = id.parse() {
+        active.remove(&id);
+      }
+    }
+  }
+  Ok(())
+}
+
+async fn send(socket: &mut Socket, message: &ClientMessage<'_>) -> Result<()> {
+  let text = serde_json::to_string(message)?;
+  socket.send(Message::text(text)).await?;
+  Ok(())
+}
+
+/// Read the next protocol message, classifying the end of the connection.
+async fn receive(socket: &mut Socket) -> Result<ServerMessage, Disconnect> {
+  loop {
+    let message = match socket.next().await {
+      Some(Ok(message)) => message,
+      Some(Err(err)) => return Err(Disconnect::Retry(err.into())),
+      None => return Err(Disconnect::Retry(anyhow::anyhow!("connection closed"))),
+    };
+    match message {
+      Message::Text(text) => match serde_json::from_str(&text) {
+        Ok(message) => return Ok(message),
+        Err(err) => tracing::warn!("ignoring invalid subscription message: {:?}", err),
+      },
+      Message::Close(frame) => return Err(closed(frame)),
+      _ => {}
+    }
+  }
+}
+
+fn closed(frame: Option<CloseFrame>) -> Disconnect {
+  let Some(frame) = frame else {
+    return Disconnect::Retry(anyhow::anyhow!("connection closed"));
+  };
+  let err = anyhow::anyhow!("connection closed with {}: {}", frame.code, frame.reason);
+  match u16::from(frame.code) {
+    4401 => Disconnect::Unauthorized,
+    4403 => Disconnect::Fatal(err.context(Code::GraphqlForbidden)),
+    // bad request, duplicate subscription id or too many initialisation requests
+    4400 | 4409 | 4429 => Disconnect::Fatal(err.context(Code::GraphqlServerError)),
+    _ => Disconnect::Retry(err),
+  }
+}
+
+/// The WebSocket URL of a GraphQL endpoint served over HTTP.
+fn ws_url(url: &str) -> String {
+  if let Some(rest) = url.strip_prefix("https://") {
+    format!("wss://{rest}")
+  } else if let Some(rest) = url.strip_prefix("http://") {
+    format!("ws://{rest}")
+  } else {
+    url.to_string()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{credentials::StaticToken, retry::RetryPolicy};
+  use futures_util::TryStreamExt;
+  use tokio::net::{TcpListener, TcpStream};
+  use tokio_tungstenite::tungstenite::{
+    handshake::server::{ErrorResponse, Request, Response as HandshakeResponse},
+    protocol::frame::coding::CloseCode,
+  };
+
+  type ServerSocket = WebSocketStream<TcpStream>;
+
+  async fn read(socket: &mut ServerSocket) -> Value {
+    loop {
+      if let Message::Text(text) = socket.next().await.unwrap().unwrap() {
+        return serde_json::from_str(&text).unwrap();
+      }
+    }
+  }
+
+  async fn write(socket: &mut ServerSocket, message: Value) {
+    socket
+      .send(Message::text(message.to_string()))
+      .await
+      .unwrap();
+  }
+
+  // the callback signature is imposed by tungstenite
+  #[allow(clippy::result_large_err)]
+  fn accept_protocol(
+    request: &Request,
+    mut response: HandshakeResponse,
+  ) -> Result<HandshakeResponse, ErrorResponse> {
+    assert_eq!(request.headers()[header::SEC_WEBSOCKET_PROTOCOL], PROTOCOL);
+    let protocol = HeaderValue::from_static(PROTOCOL);
+    response
+      .headers_mut()
+      .insert(header::SEC_WEBSOCKET_PROTOCOL, protocol);
+    Ok(response)
+  }
+
+  /// Accept a connection and acknowledge its `connection_init`, returning the socket
+  /// and the `subscribe` message.
+  async fn accept(listener: &TcpListener) -> (ServerSocket, Value) {
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut socket = tokio_tungstenite::accept_hdr_async(stream, accept_protocol)
+      .await
+      .unwrap();
+    let init = read(&mut socket).await;
+    assert_eq!(init["type"], "connection_init");
+    assert_eq!(init["payload"]["Authorization"], "Bearer token");
+    write(&mut socket, json!({ "type": "connection_ack" })).await;
+    let subscribe = read(&mut socket).await;
+    assert_eq!(subscribe["type"], "subscribe");
+    (socket, subscribe)
+  }
+
+  async fn client(listener: &TcpListener) -> ApiClient {
+    let addr = listener.local_addr().unwrap();
+    ApiClient::new(StaticToken(Some("token".to_string())))
+      .unwrap()
+      .with_url(format!("http://{addr}"))
+      .with_retry_policy(RetryPolicy {
+        base_delay: Duration::from_millis(1),
+        ..RetryPolicy::default()
+      })
+  }
+
+  fn body() -> QueryBody<Value> {
+    QueryBody {
+      variables: json!({ "slug": "dark" }),
+      query: "subscription Availability($slug: String!) { availability(slug: $slug) }",
+      operation_name: "Availability",
+    }
+  }
+
+  #[tokio::test]
+  async fn resubscribes_after_reconnecting() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let client = client(&listener).await;
+    let subscription = client.subscribe::<_, Value>(&body()).unwrap();
+
+    let server = tokio::spawn(async move {
+      let (mut socket, subscribe) = accept(&listener).await;
+      assert_eq!(subscribe["payload"]["variables"]["slug"], "dark");
+      let id = subscribe["id"].clone();
+      write(
+        &mut socket,
+        json!({ "type": "next", "id": id, "payload": { "data": 1 } }),
+      )
+      .await;
+      drop(socket);
+
+      let (mut socket, subscribe) = accept(&listener).await;
+      assert_eq!(subscribe["id"], id);
+      write(&mut socket, json!({ "type": "ping" })).await;
+      assert_eq!(read(&mut socket).await["type"], "pong");
+      write(
+        &mut socket,
+        json!({ "type": "next", "id": id, "payload": { "data": 2 } }),
+      )
+      .await;
+      write(&mut socket, json!({ "type": "complete", "id": id })).await;
+      // the client closes the connection once no subscription is active
+      while socket.next().await.is_some_and(|message| message.is_ok()) {}
+    });
+
+    let data: Vec<_> = subscription
+      .map_ok(|response| response.data)
+      .try_collect()
+      .await
+      .unwrap();
+    assert_eq!(data, [Some(json!(1)), Some(json!(2))]);
+    server.await.unwrap();
+  }
+
+  #[tokio::test]
+  async fn fails_subscriptions_when_forbidden() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let client = client(&listener).await;
+    let mut subscription = client.subscribe::<_, Value>(&body()).unwrap();
+
+    let (mut socket, _) = accept(&listener).await;
+    socket
+      .close(Some(CloseFrame {
+        code: CloseCode::from(4403),
+        reason: "Forbidden".into(),
+      }))
+      .await
+      .unwrap();
+
+    let err = subscription.next().await.unwrap().unwrap_err();
+    assert_eq!(
+      err.custom_context_or_root_cause().code,
+      Code::GraphqlForbidden
+    );
+    assert!(subscription.next().await.is_none());
+  }
+}
@@ -0,0 +1,129 @@
+//! W3C trace context of the requests sent by `ApiClient`.
+
+use tracing::{
+  Span, Subscriber,
+  span::{Attributes, Id},
+};
+use tracing_subscriber::{
+  Layer, Registry,
+  layer::Context,
+  registry::{LookupSpan, SpanRef},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TraceId(u128);
+
+/// The W3C id of a span, unlike its [`Id`] it's random and never reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct SpanId(u64);
+
+/// A [`Layer`] assigning a trace id and a span id to every span: root spans start a
+/// trace, the others join the trace of their parent.
+///
+/// With it installed, the `traceparent` header of a request sent while handling a Tauri
+/// command carries the trace of the command span.
+pub struct TraceContextLayer;
+
+impl<S> Layer<S> for TraceContextLayer
+where
+  S: Subscriber + for<'a> LookupSpan<'a>,
+{
+  fn on_new_span(&self, _attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+    let Some(span) = ctx.span(id) else {
+      return;
+    };
+    let trace_id = span
+      .parent()
+      .and_then(|parent| trace_id(&parent))
+      .unwrap_or_else(|| TraceId(random_id()));
+    let mut extensions = span.extensions_mut();
+    extensions.insert(trace_id);
+    extensions.insert(SpanId(fastrand::u64(1..)));
+  }
+}
+
+fn trace_id<S: for<'a> LookupSpan<'a>>(span: &SpanRef<'_, S>) -> Option<TraceId> {
+  span.extensions().get::<TraceId>().copied()
+}
+
+fn span_id<S: for<'a> LookupSpan<'a>>(span: &SpanRef<'_, S>) -> Option<SpanId> {
+  span.extensions().get::<SpanId>().copied()
+}
+
+/// The trace context sent with a request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TraceParent {
+  /// The hex encoded trace id, which the server traces are grouped by.
+  pub trace_id: String,
+  pub span_id: String,
+}
+
+impl TraceParent {
+  /// The context of the current span, or of a new trace when the span has none
+  /// because [`TraceContextLayer`] is not installed.
+  pub fn current() -> Self {
+    let current = Span::current()
+      .with_subscriber(|(id, dispatch)| {
+        let registry = dispatch.downcast_ref::<Registry>()?;
+        let span = registry.span(id)?;
+        Some((trace_id(&span)?, span_id(&span)?))
+      })
+      .flatten();
+    let (TraceId(trace_id), SpanId(span_id)) =
+      current.unwrap_or_else(|| (TraceId(random_id()), SpanId(fastrand::u64(1..))));
+    Self {
+      trace_id: format!("{trace_id:032x}"),
+      span_id: format!("{span_id:016x}"),
+    }
+  }
+
+  /// The `traceparent` header value, always sampled.
+  pub fn header(&self) -> String {
+    format!("00-{}-{}-01", self.trace_id, self.span_id)
+  }
+}
+
+/// A random id, never zero as zero ids are invalid.
+fn random_id() -> u128 {
+  fastrand::u128(1..)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tracing_subscriber::layer::SubscriberExt;
+
+  #[test]
+  fn spans_share_the_trace_of_their_root() {
+    let subscriber = tracing_subscriber::registry().with(TraceContextLayer);
+    tracing::subscriber::with_default(subscriber, || {
+      let command = tracing::info_span!("command").entered();
+      let parent = TraceParent::current();
+      let request = tracing::info_span!("request").entered();
+      let child = TraceParent::current();
+      assert_eq!(parent.trace_id, child.trace_id);
+      assert_ne!(parent.span_id, child.span_id);
+      drop((request, command));
+
+      let _other = tracing::info_span!("other").entered();
+      assert_ne!(TraceParent::current().trace_id, parent.trace_id);
+    });
+
+    let header = TraceParent::current().header();
+    assert_eq!(header.len(), 55);
+    assert!(header.starts_with("00-") && header.ends_with("-01"));
+  }
+
+  #[test]
+  fn span_ids_are_random() {
+    let subscriber = tracing_subscriber::registry().with(TraceContextLayer);
+    tracing::subscriber::with_default(subscriber, || {
+      let request = tracing::info_span!("request").entered();
+      let span_id = TraceParent::current().span_id;
+      assert_eq!(TraceParent::current().span_id, span_id);
+      // not the index of the span in the registry, which is small and reused
+      let id = request.id().unwrap().into_u64();
+      assert_ne!(span_id, format!("{id:016x}"));
+    });
+  }
+}
@@ -0,0 +1,76 @@
+use std::{fmt, str::FromStr, time::Duration};
+
+/// A failure returned instead of the response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fault {
+  /// An HTTP error status, e.g. `503`.
+  Status(u16),
+  /// A GraphQL error without data, with this `extensions.code`, e.g. `RATE_LIMITED`.
+  Graphql(String),
+}
+
+impl FromStr for Fault {
+  type Err = String;
+
+  /// A status code, e.g. `503`, or an extension code, e.g. `NOT_FOUND`.
+  fn from_str(value: &str) -> Result<Self, Self::Err> {
+    if value.is_empty() {
+      return Err("empty fault".to_string());
+    }
+    Ok(match value.parse() {
+      Ok(status) => Self::Status(status),
+      Err(_) => Self::Graphql(value.to_string()),
+    })
+  }
+}
+
+impl fmt::Display for Fault {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Status(status) => write!(f, "{status}"),
+      Self::Graphql(code) => f.write_str(code),
+    }
+  }
+}
+
+/// The latency and errors added to every request.
+#[derive(Debug, Clone)]
+pub struct Faults {
+  /// Delay before responding.
+  pub latency: Duration,
+  /// Random delay added to `latency`, up to this duration.
+  pub jitter: Duration,
+  /// Probability, between 0 and 1, of answering with `fault`.
+  pub error_rate: f64,
+  /// Answer the next requests with `fault`, whatever the `error_rate`.
+  pub fail_next: u32,
+  pub fault: Fault,
+}
+
+impl Default for Faults {
+  fn default() -> Self {
+    Self {
+      latency: Duration::ZERO,
+      jitter: Duration::ZERO,
+      error_rate: 0.0,
+      fail_next: 0,
+      fault: Fault::Status(503),
+    }
+  }
+}
+
+impl Faults {
+  /// The delay of a request.
+  pub fn delay(&self) -> Duration {
+    self.latency + self.jitter.mul_f64(fastrand::f64())
+  }
+
+  /// The fault of a request, if it fails.
+  pub fn next_fault(&mut self) -> Option<Fault> {
+    if self.fail_next > 0 {
+      self.fail_next -= 1;
+      return Some(self.fault.clone());
+    }
+    (fastrand::f64() < self.error_rate).then(|| self.fault.clone())
+  }
+}
@@ -0,0 +1,137 @@
+use anyhow::{Context, Result};
+use serde_json::{Value, json};
+use std::{
+  collections::{BTreeMap, BTreeSet},
+  path::Path,
+  sync::Mutex,
+};
+
+/// The data served by the mock, as JSON objects whose keys are the schema's fields.
+///
+/// Medias are `Movie` or `TVShow` objects, tagged with `__typename`. Besides their
+/// fields, medias have the `collections` they're part of, and providers the
+/// `countries` where they're available.
+pub struct Fixtures {
+  pub medias: Vec<Value>,
+  pub providers: Vec<Value>,
+  pub collections: Vec<Value>,
+  pub preferences: Mutex<Value>,
+  /// The favorite provider keys, by country.
+  pub favorites: Mutex<BTreeMap<String, BTreeSet<String>>>,
+}
+
+impl Default for Fixtures {
+  /// The fixtures shipped with the crate.
+  fn default() -> Self {
+    Self::from_json(
+      include_str!("../fixtures/medias.json"),
+      include_str!("../fixtures/providers.json"),
+      include_str!("../fixtures/collections.json"),
+      include_str!("../fixtures/preferences.json"),
+    )
+    .expect("valid fixtures")
+  }
+}
+
+impl Fixtures {
+  /// Read `medias.json`, `providers.json`, `collections.json` and `preferences.json`
+  /// from `dir`.
+  pub fn from_dir(dir: &Path) -> Result<Self> {
+    let read = |name: &str| {
+      let path = dir.join(name);
+      std::fs::read_to_string(&path).with_context(|| format!("failed to read {path:?}"))
+    };
+    Self::from_json(
+      &read("medias.json")?,
+      &read("providers.json")?,
+      &read("collections.json")?,
+      &read("preferences.json")?,
+    )
+  }
+
+  fn from_json(
+    medias: &str,
+    providers: &str,
+    collections: &str,
+    preferences: &str,
+  ) -> Result<Self> {
+    Ok(Self {
+      medias: serde_json::from_str(medias).context("invalid medias")?,
+      providers: serde_json::from_str(providers).context("invalid providers")?,
+      collections: serde_json::from_str(collections).context("invalid collections")?,
+      preferences: Mutex::new(serde_json::from_str(preferences).context("invalid preferences")?),
+      favorites: Mutex::default(),
+    })
+  }
+
+  /// The medias available in `country`.
+  pub fn medias_in<'a>(&'a self, country: &'a str) -> impl Iterator<Item = &'a Value> {
+    self
+      .medias
+      .iter()
+      .filter(move |media| contains(&media["countries"], country))
+  }
+}
+
+/// Whether `list` is an array containing the string `value`.
+pub fn contains(list: &Value, value: &str) -> bool {
+  list
+    .as_array()
+    .is_some_and(|items| items.iter().any(|item| item == value))
+}
+
+/// The `MediaSearch` node of a media, as returned by `search` and the collections.
+pub fn search_node(media: &Value) -> Value {
+  let availabilities = media["availabilities"]
+    .as_array()
+    .cloned()
+    .unwrap_or_default();
+  let languages = |key: &str| {
+    let languages: BTreeSet<&str> = availabilities
+      .iter()
+      .flat_map(|availability| availability[key].as_array().into_iter().flatten())
+      .filter_map(Value::as_str)
+      .collect();
+    json!(languages)
+  };
+  let rank = media["ranking"].as_object().map(|ranking| {
+    json!({
+      "position": ranking["position"],
+      "score": ranking["score"],
+      "points": ranking["points"],
+      "change": 0.0,
+      "previousPosition": ranking["position"],
+      "previousPoints": ranking["points"],
+    })
+  });
+
+  json!({
+    "id": media["id"],
+    "rank": rank,
+    "kind": media["kind"],
+    "slug": media["slug"],
+    "title": media["title"],
+    "genres": media["genres"],
+    "providers": availabilities
+      .iter()
+      .map(|availability| json!({
+        "providerId": availability["providerId"],
+        "priceTypes": availability["pricesType"],
+      }))
+      .collect::<Vec<_>>(),
+    "offersLanguages": languages("audioLanguages"),
+    "offersSubtitles": languages("subtitleLanguages"),
+    "collections": media.get("collections").cloned().unwrap_or_else(|| json!([])),
+    "poster": media["poster"],
+    "backdrop": media["backdrop"],
+    "originalTitle": media["title"],
+    "year": media["year"],
+    "classification": media["classification"],
+    "country": media["country"],
+    "tagline": media["tagline"],
+    "overview": media["overview"],
+    "released": media["released"],
+    "featuredFrom": null,
+    "updatedAt": media["updatedAt"],
+  })
+}
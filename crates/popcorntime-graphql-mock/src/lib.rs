@@ -0,0 +1,15 @@
+//! A local GraphQL server implementing `popcorntime-graphql-client/gql/schema.json`
+//! from fixture data, to run the desktop app and integration tests without network.
+//!
+//! The desktop app uses it when built with `GRAPHQL_SERVER` pointing to the mock, e.g.
+//! `GRAPHQL_SERVER=http://127.0.0.1:8080 pnpm tauri dev`.
+
+pub mod faults;
+pub mod fixtures;
+mod resolvers;
+pub mod schema;
+pub mod server;
+
+pub use faults::{Fault, Faults};
+pub use fixtures::Fixtures;
+pub use server::{MockServer, RunningServer};
@@ -0,0 +1,42 @@
+use anyhow::{Context, Result, bail};
+use popcorntime_graphql_mock::{Faults, Fixtures, MockServer};
+use std::{net::SocketAddr, path::Path, time::Duration};
+
+const USAGE: &str = "usage: popcorntime-graphql-mock [--addr 127.0.0.1:8080] [--fixtures DIR] \
+                     [--latency-ms N] [--jitter-ms N] [--error-rate 0.1] [--fault 503|CODE]";
+
+#[tokio::main]
+async fn main() -> Result<()> {
+  tracing_subscriber::fmt()
+    .with_env_filter(
+      tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()),
+    )
+    .init();
+
+  let mut addr: SocketAddr = "127.0.0.1:8080".parse()?;
+  let mut fixtures = Fixtures::default();
+  let mut faults = Faults::default();
+
+  let mut args = std::env::args().skip(1);
+  while let Some(arg) = args.next() {
+    let mut value = || {
+      args
+        .next()
+        .with_context(|| format!("missing value of {arg}\n{USAGE}"))
+    };
+    match arg.as_str() {
+      "--addr" => addr = value()?.parse().context("invalid --addr")?,
+      "--fixtures" => fixtures = Fixtures::from_dir(Path::new(&value()?))?,
+      "--latency-ms" => faults.latency = Duration::from_millis(value()?.parse()?),
+      "--jitter-ms" => faults.jitter = Duration::from_millis(value()?.parse()?),
+      "--error-rate" => faults.error_rate = value()?.parse().context("invalid --error-rate")?,
+      "--fault" => faults.fault = value()?.parse().map_err(anyhow::Error::msg)?,
+      _ => bail!("unknown argument {arg}\n{USAGE}"),
+    }
+  }
+
+  MockServer::new(fixtures)?
+    .with_faults(faults)
+    .run(addr)
+    .await
+}
@@ -0,0 +1,371 @@
+//! Resolvers of the fields which aren't read as is from the fixtures, i.e. the root
+//! fields and the media fields taking arguments.
+
+use crate::fixtures::{Fixtures, contains, search_node};
+use async_graphql::{Error, ErrorExtensions, Result};
+use serde_json::{Value, json};
+use std::cmp::Reverse;
+
+/// Resolve a field from the fixtures, its arguments and the parent object.
+pub type Resolver = fn(&Fixtures, &Value, &Value) -> Result<Value>;
+
+pub fn resolver(ty: &str, field: &str) -> Option<Resolver> {
+  let resolver: Resolver = match (ty, field) {
+    ("QueryRoot", "search") => search,
+    ("QueryRoot", "media") => media,
+    ("QueryRoot", "homeCollection") => home_collection,
+    ("QueryRoot", "collections") => collections,
+    ("QueryRoot", "count") => count,
+    ("QueryRoot", "providers") => providers,
+    ("QueryRoot", "preferences") => preferences,
+    ("MutationRoot", "updatePreferences") => update_preferences,
+    ("MutationRoot", "addFavoriteProvider") => add_favorite_provider,
+    ("MutationRoot", "removeFavoriteProvider") => remove_favorite_provider,
+    ("Movie" | "TVShow", "similars") => similars,
+    ("Movie" | "TVShow", "charts") => charts,
+    ("Movie" | "TVShow", "availabilities") => availabilities,
+    ("Movie" | "TVShow", "pochoclinReview") => pochoclin_review,
+    _ => return None,
+  };
+  Some(resolver)
+}
+
+/// An error with the `extensions.code` understood by the client.
+pub fn error(code: &'static str, message: impl Into<String>) -> Error {
+  Error::new(message).extend_with(|_, extensions| extensions.set("code", code))
+}
+
+fn str_arg<'a>(args: &'a Value, name: &str) -> Option<&'a str> {
+  args.get(name).and_then(Value::as_str)
+}
+
+fn country(args: &Value) -> Result<&str> {
+  str_arg(args, "country").ok_or_else(|| error("BAD_USER_INPUT", "missing country"))
+}
+
+/// Whether a media matches the `SearchArguments` of `search` and `similars`.
+fn matches(media: &Value, arguments: &Value) -> bool {
+  let availabilities = media["availabilities"]
+    .as_array()
+    .cloned()
+    .unwrap_or_default();
+  let any_of = |name: &str, matches: &dyn Fn(&str) -> bool| match arguments[name].as_array() {
+    Some(values) => values.iter().filter_map(Value::as_str).any(matches),
+    // a single value is accepted where a list is expected
+    None => arguments[name].as_str().is_none_or(matches),
+  };
+  let offers = |key: &str, value: &str| {
+    availabilities
+      .iter()
+      .any(|availability| contains(&availability[key], value))
+  };
+
+  (arguments["kind"].is_null() || arguments["kind"] == media["kind"])
+    && (arguments["year"].is_null() || arguments["year"] == media["year"])
+    && (arguments["collection"].is_null() || {
+      let collections = media["collections"].as_array();
+      collections.is_some_and(|collections| collections.contains(&arguments["collection"]))
+    })
+    && (arguments["withPoster"] != true || !media["poster"].is_null())
+    && arguments["featured"] != true
+    && str_arg(arguments, "country").is_none_or(|country| contains(&media["countries"], country))
+    && any_of("genres", &|genre| contains(&media["genres"], genre))
+    && any_of("providers", &|provider| {
+      availabilities
+        .iter()
+        .any(|availability| availability["providerId"] == provider)
+    })
+    && any_of("priceTypes", &|price_type| offers("pricesType", price_type))
+    && str_arg(arguments, "audio").is_none_or(|language| offers("audioLanguages", language))
+    && str_arg(arguments, "subtitle").is_none_or(|language| offers("subtitleLanguages", language))
+}
+
+fn sort(medias: &mut [&Value], sort_key: &str) {
+  let string = |media: &&Value, key: &str| media[key].as_str().unwrap_or_default().to_string();
+  match sort_key {
+    "RELEASED_AT" => medias.sort_by_key(|media| Reverse(string(media, "released"))),
+    "CREATED_AT" => medias.sort_by_key(|media| Reverse(string(media, "createdAt"))),
+    "UPDATED_AT" => medias.sort_by_key(|media| Reverse(string(media, "updatedAt"))),
+    "POSITION" => {
+      medias.sort_by_key(|media| media["ranking"]["position"].as_i64().unwrap_or(i64::MAX))
+    }
+    _ => medias.sort_by_key(|media| media["id"].as_i64()),
+  }
+}
+
+fn cursor(index: usize) -> String {
+  format!("cursor:{index}")
+}
+
+fn parse_cursor(cursor: &str) -> Result<usize> {
+  cursor
+    .strip_prefix("cursor:")
+    .and_then(|index| index.parse().ok())
+    .ok_or_else(|| error("BAD_USER_INPUT", format!("invalid cursor `{cursor}`")))
+}
+
+fn page_size(args: &Value, name: &str) -> Result<Option<usize>> {
+  match args[name].as_i64() {
+    None => Ok(None),
+    Some(size) => usize::try_from(size)
+      .map(Some)
+      .map_err(|_| error("BAD_USER_INPUT", format!("negative `{name}`"))),
+  }
+}
+
+/// A relay connection over the medias of `country` matching the arguments.
+fn search(fixtures: &Fixtures, args: &Value, _: &Value) -> Result<Value> {
+  let query = str_arg(args, "query").map(str::to_lowercase);
+  let mut medias: Vec<_> = fixtures
+    .medias_in(country(args)?)
+    .filter(|media| {
+      query.as_deref().is_none_or(|query| {
+        let title = media["title"].as_str().unwrap_or_default();
+        title.to_lowercase().contains(query)
+      })
+    })
+    .filter(|media| matches(media, &args["arguments"]))
+    .collect();
+  sort(&mut medias, str_arg(args, "sortKey").unwrap_or("ID"));
+
+  let mut start = match str_arg(args, "after") {
+    Some(after) => parse_cursor(after)? + 1,
+    None => 0,
+  };
+  let mut end = match str_arg(args, "before") {
+    Some(before) => parse_cursor(before)?,
+    None => medias.len(),
+  }
+  .min(medias.len());
+  start = start.min(end);
+  let (after, before) = (start, end);
+  if let Some(first) = page_size(args, "first")? {
+    end = end.min(start + first);
+  }
+  if let Some(last) = page_size(args, "last")? {
+    start = start.max(end.saturating_sub(last));
+  }
+
+  let edges: Vec<_> = (start..end)
+    .map(|index| {
+      json!({
+        "node": search_node(medias[index]),
+        "cursor": cursor(index),
+      })
+    })
+    .collect();
+  Ok(json!({
+    "pageInfo": {
+      "hasPreviousPage": start > after,
+      "hasNextPage": end < before,
+      "startCursor": (start < end).then(|| cursor(start)),
+      "endCursor": (start < end).then(|| cursor(end - 1)),
+    },
+    "nodes": edges.iter().map(|edge| edge["node"].clone()).collect::<Vec<_>>(),
+    "edges": edges,
+  }))
+}
+
+fn media(fixtures: &Fixtures, args: &Value, _: &Value) -> Result<Value> {
+  let by = &args["by"];
+  let media = fixtures.medias.iter().find(|media| {
+    (!by["id"].is_null() && media["id"] == by["id"])
+      || (!by["slug"].is_null() && media["slug"] == by["slug"])
+  });
+  let media = media.filter(|media| {
+    str_arg(args, "country").is_none_or(|country| contains(&media["countries"], country))
+  });
+  Ok(media.cloned().unwrap_or(Value::Null))
+}
+
+fn ranked<'a>(medias: impl Iterator<Item = &'a Value>) -> Vec<Value> {
+  let mut medias: Vec<_> = medias.filter(|media| !media["ranking"].is_null()).collect();
+  sort(&mut medias, "POSITION");
+  medias.into_iter().map(search_node).collect()
+}
+
+fn latest<'a>(medias: impl Iterator<Item = &'a Value>) -> Vec<Value> {
+  let mut medias: Vec<_> = medias.collect();
+  sort(&mut medias, "RELEASED_AT");
+  medias.into_iter().map(search_node).collect()
+}
+
+fn home_collection(fixtures: &Fixtures, args: &Value, _: &Value) -> Result<Value> {
+  let country = country(args)?;
+  let of_kind = |kind: &'static str| {
+    fixtures
+      .medias_in(country)
+      .filter(move |media| media["kind"] == kind)
+  };
+  Ok(json!({
+    "id": country,
+    "topMovies": ranked(of_kind("MOVIE")),
+    "topSeries": ranked(of_kind("TV_SHOW")),
+    "newMovies": latest(of_kind("MOVIE")),
+    "newTvshows": latest(of_kind("TV_SHOW")),
+  }))
+}
+
+fn collections(fixtures: &Fixtures, args: &Value, _: &Value) -> Result<Value> {
+  let country = country(args)?;
+  let language = &args["language"];
+  let collections: Vec<_> = fixtures
+    .collections
+    .iter()
+    .filter(|collection| collection["country"] == country)
+    .filter(|collection| {
+      language.is_null() || collection["language"].is_null() || collection["language"] == *language
+    })
+    .cloned()
+    .collect();
+  Ok(json!(collections))
+}
+
+fn count(fixtures: &Fixtures, args: &Value, _: &Value) -> Result<Value> {
+  Ok(json!(fixtures.medias_in(country(args)?).count()))
+}
+
+fn providers(fixtures: &Fixtures, args: &Value, _: &Value) -> Result<Value> {
+  let country = country(args)?;
+  let favorites = fixtures
+    .favorites
+    .lock()
+    .unwrap_or_else(|err| err.into_inner());
+  let favorites = favorites.get(country).cloned().unwrap_or_default();
+  let query = str_arg(args, "query").map(str::to_lowercase);
+
+  let mut providers: Vec<_> = fixtures
+    .providers
+    .iter()
+    .filter(|provider| contains(&provider["countries"], country))
+    .filter(|provider| {
+      args["favorites"] != true
+        || provider["key"]
+          .as_str()
+          .is_some_and(|key| favorites.contains(key))
+    })
+    .filter(|provider| {
+      query.as_deref().is_none_or(|query| {
+        let name = provider["name"].as_str().unwrap_or_default();
+        name.to_lowercase().contains(query)
+      })
+    })
+    .collect();
+  providers.sort_by_key(|provider| Reverse(provider["weight"].as_i64()));
+  Ok(json!(providers))
+}
+
+fn preferences(fixtures: &Fixtures, _: &Value, _: &Value) -> Result<Value> {
+  let preferences = fixtures
+    .preferences
+    .lock()
+    .unwrap_or_else(|err| err.into_inner());
+  Ok(preferences.clone())
+}
+
+fn update_preferences(fixtures: &Fixtures, args: &Value, _: &Value) -> Result<Value> {
+  let mut preferences = fixtures
+    .preferences
+    .lock()
+    .unwrap_or_else(|err| err.into_inner());
+  preferences["country"] = args["country"].clone();
+  preferences["language"] = args["language"].clone();
+  Ok(preferences.clone())
+}
+
+fn provider_key<'a>(fixtures: &Fixtures, args: &'a Value) -> Result<(&'a str, &'a str)> {
+  let country = country(args)?;
+  let key = str_arg(args, "providerKey").unwrap_or_default();
+  let exists = fixtures
+    .providers
+    .iter()
+    .any(|provider| provider["key"] == key && contains(&provider["countries"], country));
+  if !exists {
+    return Err(error(
+      "NOT_FOUND",
+      format!("unknown provider `{key}` in {country}"),
+    ));
+  }
+  Ok((country, key))
+}
+
+fn add_favorite_provider(fixtures: &Fixtures, args: &Value, _: &Value) -> Result<Value> {
+  let (country, key) = provider_key(fixtures, args)?;
+  let mut favorites = fixtures
+    .favorites
+    .lock()
+    .unwrap_or_else(|err| err.into_inner());
+  favorites
+    .entry(country.to_string())
+    .or_default()
+    .insert(key.to_string());
+  Ok(json!(true))
+}
+
+fn remove_favorite_provider(fixtures: &Fixtures, args: &Value, _: &Value) -> Result<Value> {
+  let (country, key) = provider_key(fixtures, args)?;
+  let mut favorites = fixtures
+    .favorites
+    .lock()
+    .unwrap_or_else(|err| err.into_inner());
+  if let Some(favorites) = favorites.get_mut(country) {
+    favorites.remove(key);
+  }
+  Ok(json!(true))
+}
+
+/// The other medias of `country` sharing a genre with the parent.
+fn similars(fixtures: &Fixtures, args: &Value, parent: &Value) -> Result<Value> {
+  let similars: Vec<_> = fixtures
+    .medias_in(country(args)?)
+    .filter(|media| media["id"] != parent["id"])
+    .filter(|media| {
+      let genres = media["genres"].as_array().into_iter().flatten();
+      genres
+        .filter_map(Value::as_str)
+        .any(|genre| contains(&parent["genres"], genre))
+    })
+    .filter(|media| matches(media, &args["arguments"]))
+    .map(search_node)
+    .collect();
+  Ok(json!(similars))
+}
+
+fn charts(fixtures: &Fixtures, args: &Value, parent: &Value) -> Result<Value> {
+  let country = country(args)?;
+  let of_kind = fixtures
+    .medias_in(country)
+    .filter(|media| media["kind"] == parent["kind"]);
+  Ok(json!(ranked(of_kind)))
+}
+
+fn availabilities(_: &Fixtures, args: &Value, parent: &Value) -> Result<Value> {
+  if !contains(&parent["countries"], country(args)?) {
+    return Ok(json!([]));
+  }
+  let availabilities: Vec<_> = parent["availabilities"]
+    .as_array()
+    .into_iter()
+    .flatten()
+    .filter(|availability| {
+      str_arg(args, "priceType")
+        .is_none_or(|price_type| contains(&availability["pricesType"], price_type))
+    })
+    .filter(|availability| {
+      str_arg(args, "format").is_none_or(|format| contains(&availability["formats"], format))
+    })
+    .cloned()
+    .collect();
+  Ok(json!(availabilities))
+}
+
+fn pochoclin_review(_: &Fixtures, args: &Value, parent: &Value) -> Result<Value> {
+  let review = &parent["pochoclinReview"];
+  let matches_language =
+    str_arg(args, "language").is_none_or(|language| review["language"] == language);
+  Ok(if matches_language {
+    review.clone()
+  } else {
+    Value::Null
+  })
+}
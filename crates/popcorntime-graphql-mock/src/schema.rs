@@ -0,0 +1,206 @@
+//! The schema of the API, built from the introspection in `gql/schema.json` so it never
+//! drifts from what the client is generated against.
+
+use crate::{
+  fixtures::Fixtures,
+  resolvers::{Resolver, resolver},
+};
+use anyhow::{Context, Result, bail};
+use async_graphql::{
+  Name, Value as GqlValue,
+  dynamic::{
+    Enum, Field, FieldFuture, FieldValue, InputObject, InputValue, Interface, InterfaceField,
+    Object, ResolverContext, Scalar, Schema, TypeRef, Union,
+  },
+};
+use serde_json::Value;
+use std::{collections::HashMap, sync::Arc};
+
+/// The introspection the client is generated against.
+pub const INTROSPECTION: &str = include_str!("../../popcorntime-graphql-client/gql/schema.json");
+
+const BUILT_IN_SCALARS: &[&str] = &["Boolean", "Float", "ID", "Int", "String"];
+
+/// Build the schema, resolving every field from `fixtures`.
+pub fn build(fixtures: Arc<Fixtures>) -> Result<Schema> {
+  let introspection: Value = serde_json::from_str(INTROSPECTION).context("invalid schema.json")?;
+  let schema = &introspection["data"]["__schema"];
+  let root = |name: &str| schema[name]["name"].as_str().map(str::to_string);
+  let Some(query) = root("queryType") else {
+    bail!("schema.json has no query type");
+  };
+
+  let types = schema["types"]
+    .as_array()
+    .context("schema.json has no types")?;
+  let kinds: Arc<HashMap<String, String>> = Arc::new(
+    types
+      .iter()
+      .map(|ty| (string(&ty["name"]), string(&ty["kind"])))
+      .collect(),
+  );
+
+  let mut builder = Schema::build(
+    &query,
+    root("mutationType").as_deref(),
+    root("subscriptionType").as_deref(),
+  )
+  .data(fixtures);
+
+  for ty in types {
+    let name = string(&ty["name"]);
+    if name.starts_with("__") {
+      continue;
+    }
+    builder = match ty["kind"].as_str() {
+      Some("SCALAR") if BUILT_IN_SCALARS.contains(&name.as_str()) => builder,
+      Some("SCALAR") => builder.register(Scalar::new(name)),
+      Some("ENUM") => builder.register(
+        Enum::new(name).items(items(&ty["enumValues"]).map(|value| string(&value["name"]))),
+      ),
+      Some("INPUT_OBJECT") => {
+        let mut object = InputObject::new(name);
+        for field in items(&ty["inputFields"]) {
+          object = object.field(input_value(field));
+        }
+        builder.register(object)
+      }
+      Some("UNION") => {
+        let mut union = Union::new(name);
+        for possible_type in items(&ty["possibleTypes"]) {
+          union = union.possible_type(string(&possible_type["name"]));
+        }
+        builder.register(union)
+      }
+      Some("INTERFACE") => {
+        let mut interface = Interface::new(&name);
+        for field in items(&ty["fields"]) {
+          let mut interface_field =
+            InterfaceField::new(string(&field["name"]), type_ref(&field["type"]));
+          for arg in items(&field["args"]) {
+            interface_field = interface_field.argument(input_value(arg));
+          }
+          interface = interface.field(interface_field);
+        }
+        builder.register(interface)
+      }
+      Some("OBJECT") => {
+        let mut object = Object::new(&name);
+        for interface in items(&ty["interfaces"]) {
+          object = object.implement(string(&interface["name"]));
+        }
+        for field in items(&ty["fields"]) {
+          object = object.field(field_with_resolver(&name, field, kinds.clone()));
+        }
+        builder.register(object)
+      }
+      kind => bail!("unsupported kind {kind:?} of `{name}`"),
+    };
+  }
+
+  builder.finish().context("invalid schema")
+}
+
+fn string(value: &Value) -> String {
+  value.as_str().unwrap_or_default().to_string()
+}
+
+fn items(value: &Value) -> impl Iterator<Item = &Value> {
+  value.as_array().into_iter().flatten()
+}
+
+fn type_ref(ty: &Value) -> TypeRef {
+  match ty["kind"].as_str() {
+    Some("NON_NULL") => TypeRef::NonNull(Box::new(type_ref(&ty["ofType"]))),
+    Some("LIST") => TypeRef::List(Box::new(type_ref(&ty["ofType"]))),
+    _ => TypeRef::named(string(&ty["name"])),
+  }
+}
+
+fn input_value(value: &Value) -> InputValue {
+  let input_value = InputValue::new(string(&value["name"]), type_ref(&value["type"]));
+  match value["defaultValue"].as_str() {
+    Some(default) => input_value.default_value(literal(default)),
+    None => input_value,
+  }
+}
+
+/// Parse the GraphQL literal of a default value, e.g. `null`, `"text"` or `ID`.
+fn literal(literal: &str) -> GqlValue {
+  match serde_json::from_str::<Value>(literal) {
+    Ok(json) => GqlValue::from_json(json).unwrap_or(GqlValue::Null),
+    Err(_) => GqlValue::Enum(Name::new(literal)),
+  }
+}
+
+/// A field resolved with its resolver if it has one, or read from the parent object.
+fn field_with_resolver(ty: &str, field: &Value, kinds: Arc<HashMap<String, String>>) -> Field {
+  let name = string(&field["name"]);
+  let field_type = type_ref(&field["type"]);
+  let custom = resolver(ty, &name);
+
+  let mut resolved = Field::new(name.clone(), field_type.clone(), move |ctx| {
+    let name = name.clone();
+    let field_type = field_type.clone();
+    let kinds = kinds.clone();
+    FieldFuture::new(async move {
+      let value = resolve(&ctx, &name, custom)?;
+      Ok(to_field_value(value, &field_type, &kinds))
+    })
+  });
+  for arg in items(&field["args"]) {
+    resolved = resolved.argument(input_value(arg));
+  }
+  resolved
+}
+
+fn resolve(
+  ctx: &ResolverContext<'_>,
+  name: &str,
+  custom: Option<Resolver>,
+) -> async_graphql::Result<Value> {
+  let parent = ctx.parent_value.downcast_ref::<Value>();
+  let Some(custom) = custom else {
+    return Ok(
+      parent
+        .and_then(|parent| parent.get(name))
+        .cloned()
+        .unwrap_or(Value::Null),
+    );
+  };
+
+  let fixtures = ctx.data::<Arc<Fixtures>>()?;
+  let args = GqlValue::Object(ctx.args.as_index_map().clone()).into_json()?;
+  custom(fixtures, &args, parent.unwrap_or(&Value::Null))
+}
+
+/// Wrap JSON in what the dynamic schema expects for `ty`: objects are kept as JSON to
+/// resolve their fields, and abstract types are tagged with their `__typename`.
+fn to_field_value<'a>(
+  value: Value,
+  ty: &TypeRef,
+  kinds: &HashMap<String, String>,
+) -> Option<FieldValue<'a>> {
+  match (ty, value) {
+    // a required list the fixtures don't have is empty, not an error
+    (TypeRef::NonNull(ty), Value::Null) if matches!(**ty, TypeRef::List(_)) => {
+      Some(FieldValue::list(Vec::<FieldValue>::new()))
+    }
+    (TypeRef::NonNull(ty), value) => to_field_value(value, ty, kinds),
+    (_, Value::Null) => None,
+    (TypeRef::List(ty), Value::Array(items)) => {
+      Some(FieldValue::list(items.into_iter().map(|item| {
+        to_field_value(item, ty, kinds).unwrap_or(FieldValue::NULL)
+      })))
+    }
+    (TypeRef::List(_), value) => GqlValue::from_json(value).ok().map(FieldValue::value),
+    (TypeRef::Named(name), value) => match kinds.get(name.as_ref()).map(String::as_str) {
+      Some("OBJECT") => Some(FieldValue::owned_any(value)),
+      Some("INTERFACE" | "UNION") => {
+        let typename = string(&value["__typename"]);
+        Some(FieldValue::owned_any(value).with_type(typename))
+      }
+      _ => GqlValue::from_json(value).ok().map(FieldValue::value),
+    },
+  }
+}
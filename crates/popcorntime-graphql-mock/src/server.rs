@@ -0,0 +1,495 @@
+use crate::{
+  faults::{Fault, Faults},
+  fixtures::Fixtures,
+  schema,
+};
+use anyhow::{Context, Result};
+use async_graphql::dynamic::Schema;
+use poem::{
+  EndpointExt, IntoResponse, Response, Route, Server, get, handler,
+  http::{Method, StatusCode},
+  listener::{Acceptor, Listener, TcpListener},
+  web::{Data, Json, Query},
+};
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use std::{
+  collections::BTreeMap,
+  net::SocketAddr,
+  sync::{Arc, Mutex},
+};
+use tokio::task::JoinHandle;
+
+/// A GraphQL server answering from fixtures, with optional latency and errors.
+#[derive(Clone)]
+pub struct MockServer {
+  schema: Schema,
+  faults: Arc<Mutex<Faults>>,
+  /// The documents of the automatic persisted queries, by sha256 hash.
+  persisted_queries: Arc<Mutex<BTreeMap<String, String>>>,
+  /// The HTTP methods of the requests, in the order they were received.
+  methods: Arc<Mutex<Vec<Method>>>,
+}
+
+impl MockServer {
+  pub fn new(fixtures: Fixtures) -> Result<Self> {
+    Ok(Self {
+      schema: schema::build(Arc::new(fixtures))?,
+      faults: Arc::default(),
+      persisted_queries: Arc::default(),
+      methods: Arc::default(),
+    })
+  }
+
+  pub fn with_faults(self, faults: Faults) -> Self {
+    self.update_faults(|current| *current = faults);
+    self
+  }
+
+  /// Change the injected faults, including while the server is running.
+  pub fn update_faults(&self, update: impl FnOnce(&mut Faults)) {
+    let mut faults = self.faults.lock().unwrap_or_else(|err| err.into_inner());
+    update(&mut faults);
+  }
+
+  /// The hashes of the persisted queries sent so far.
+  pub fn persisted_queries(&self) -> Vec<String> {
+    let queries = self
+      .persisted_queries
+      .lock()
+      .unwrap_or_else(|err| err.into_inner());
+    queries.keys().cloned().collect()
+  }
+
+  /// The HTTP methods of the requests received so far, in order.
+  pub fn methods(&self) -> Vec<Method> {
+    self
+      .methods
+      .lock()
+      .unwrap_or_else(|err| err.into_inner())
+      .clone()
+  }
+
+  /// Serve on `addr` until the task is cancelled.
+  pub async fn run(self, addr: SocketAddr) -> Result<()> {
+    let acceptor = TcpListener::bind(addr)
+      .into_acceptor()
+      .await
+      .with_context(|| format!("failed to listen on {addr}"))?;
+    tracing::info!(%addr, "serving the GraphQL mock");
+    Server::new_with_acceptor(acceptor)
+      .run(self.endpoint())
+      .await
+      .context("server failed")
+  }
+
+  /// Serve on a free port of `127.0.0.1`, until the returned handle is dropped.
+  pub async fn spawn(&self) -> Result<RunningServer> {
+    let acceptor = TcpListener::bind("127.0.0.1:0")
+      .into_acceptor()
+      .await
+      .context("failed to listen")?;
+    let addr = acceptor
+      .local_addr()
+      .first()
+      .and_then(|addr| addr.as_socket_addr().copied())
+      .context("no local address")?;
+    let endpoint = self.endpoint();
+    let task = tokio::spawn(async move {
+      if let Err(err) = Server::new_with_acceptor(acceptor).run(endpoint).await {
+        tracing::error!("GraphQL mock failed: {:?}", err);
+      }
+    });
+    Ok(RunningServer {
+      url: format!("http://{addr}"),
+      task,
+    })
+  }
+
+  fn endpoint(&self) -> impl poem::Endpoint + 'static {
+    Route::new()
+      .at("/", get(graphql_get).post(graphql))
+      .at("/graphql", get(graphql_get).post(graphql))
+      .data(self.clone())
+  }
+}
+
+/// A server started with [`MockServer::spawn`], stopped when dropped.
+pub struct RunningServer {
+  pub url: String,
+  task: JoinHandle<()>,
+}
+
+impl Drop for RunningServer {
+  fn drop(&mut self) {
+    self.task.abort();
+  }
+}
+
+#[handler]
+async fn graphql(Data(server): Data<&MockServer>, Json(request): Json<Value>) -> Response {
+  server.respond(Method::POST, request).await
+}
+
+/// A query sent with GET, whose `variables` and `extensions` are JSON encoded.
+#[handler]
+async fn graphql_get(
+  Data(server): Data<&MockServer>,
+  Query(params): Query<BTreeMap<String, String>>,
+) -> Response {
+  let request = params
+    .into_iter()
+    .map(|(key, value)| {
+      let value = match key.as_str() {
+        "variables" | "extensions" => serde_json::from_str(&value).unwrap_or(Value::Null),
+        _ => Value::String(value),
+      };
+      (key, value)
+    })
+    .collect();
+  server.respond(Method::GET, Value::Object(request)).await
+}
+
+impl MockServer {
+  async fn respond(&self, method: Method, mut request: Value) -> Response {
+    self
+      .methods
+      .lock()
+      .unwrap_or_else(|err| err.into_inner())
+      .push(method);
+    let (delay, fault) = {
+      let mut faults = self.faults.lock().unwrap_or_else(|err| err.into_inner());
+      (faults.delay(), faults.next_fault())
+    };
+    tokio::time::sleep(delay).await;
+
+    match fault {
+      Some(Fault::Status(status)) => {
+        let status = StatusCode::from_u16(status).unwrap_or(StatusCode::SERVICE_UNAVAILABLE);
+        return Response::builder().status(status).finish();
+      }
+      Some(Fault::Graphql(code)) => return errors("injected fault", &code),
+      None => {}
+    }
+
+    if let Err((message, code)) = self.resolve_persisted_query(&mut request) {
+      return errors(message, code);
+    }
+    match serde_json::from_value::<async_graphql::Request>(request) {
+      Ok(request) => Json(self.schema.execute(request).await).into_response(),
+      Err(err) => Response::builder()
+        .status(StatusCode::BAD_REQUEST)
+        .body(err.to_string()),
+    }
+  }
+
+  /// Fill in the document of an automatic persisted query, or store the document sent
+  /// along with its hash, failing with the error message and code.
+  fn resolve_persisted_query(
+    &self,
+    request: &mut Value,
+  ) -> Result<(), (&'static str, &'static str)> {
+    let Some(hash) = request["extensions"]["persistedQuery"]["sha256Hash"].as_str() else {
+      return Ok(());
+    };
+    let hash = hash.to_string();
+    let mut queries = self
+      .persisted_queries
+      .lock()
+      .unwrap_or_else(|err| err.into_inner());
+    match request["query"].as_str() {
+      Some(query) if hex::encode(Sha256::digest(query)) != hash => {
+        Err(("provided sha does not match query", "BAD_USER_INPUT"))
+      }
+      Some(query) => {
+        queries.insert(hash, query.to_string());
+        Ok(())
+      }
+      None => match queries.get(&hash) {
+        Some(query) => {
+          request["query"] = Value::String(query.clone());
+          Ok(())
+        }
+        None => Err(("PersistedQueryNotFound", "PERSISTED_QUERY_NOT_FOUND")),
+      },
+    }
+  }
+}
+
+fn errors(message: &str, code: &str) -> Response {
+  Json(json!({
+    "data": null,
+    "errors": [{ "message": message, "extensions": { "code": code } }],
+  }))
+  .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use futures_util::TryStreamExt;
+  use popcorntime_error::{AnyhowContextExt, Code};
+  use popcorntime_graphql_client::{
+    add_favorite_provider, cache::ResponseCache, client::ApiClient, credentials::StaticToken,
+    enums::SortKey, error::Origin, media, pagination::PageOptions, persisted::PersistedQueries,
+    preferences, providers, retry::RetryPolicy, search,
+  };
+  use std::time::Duration;
+
+  async fn client(server: &RunningServer) -> ApiClient {
+    ApiClient::new(StaticToken(None))
+      .unwrap()
+      .with_url(&server.url)
+      .with_retry_policy(RetryPolicy {
+        base_delay: Duration::from_millis(1),
+        ..RetryPolicy::default()
+      })
+  }
+
+  fn search_vars() -> search::Variables {
+    search::Variables {
+      after: None,
+      before: None,
+      first: None,
+      last: None,
+      sort_key: Some(SortKey::Id),
+      country: "US".parse().unwrap(),
+      language: None,
+      query: None,
+      arguments: None,
+    }
+  }
+
+  #[tokio::test]
+  async fn serves_the_client_operations() {
+    let mock = MockServer::new(Fixtures::default()).unwrap();
+    let server = mock.spawn().await.unwrap();
+    let client = client(&server).await;
+
+    let nodes: Vec<_> = client
+      .search_stream(
+        search_vars(),
+        PageOptions {
+          page_size: 2,
+          prefetch: false,
+        },
+      )
+      .try_collect()
+      .await
+      .unwrap();
+    let slugs: Vec<_> = nodes.iter().map(|node| node.slug.as_str()).collect();
+    assert_eq!(
+      slugs,
+      [
+        "inception",
+        "night-of-the-living-dead",
+        "amelie",
+        "the-office",
+        "dark",
+        "bonanza"
+      ]
+    );
+
+    let vars = media::Variables {
+      slug: "the-office".to_string(),
+      country: "US".parse().unwrap(),
+      language: None,
+    };
+    let media = client.media(&vars).await.unwrap().into_data().unwrap();
+    let media = media.and_then(|data| data.media).unwrap();
+    assert_eq!(media.title, "The Office");
+
+    let vars = add_favorite_provider::Variables {
+      country: "US".parse().unwrap(),
+      provider_key: "tubi".to_string(),
+    };
+    client.add_favorite_provider(&vars).await.unwrap();
+    let vars = providers::Variables {
+      country: "US".parse().unwrap(),
+      query: None,
+      favorites: Some(true),
+    };
+    let providers = client.providers(&vars).await.unwrap().into_data().unwrap();
+    let keys: Vec<_> = providers
+      .unwrap()
+      .providers
+      .into_iter()
+      .map(|provider| provider.key)
+      .collect();
+    assert_eq!(keys, ["tubi"]);
+  }
+
+  #[tokio::test]
+  async fn injects_faults() {
+    let mock = MockServer::new(Fixtures::default()).unwrap();
+    let server = mock.spawn().await.unwrap();
+    let client = client(&server).await;
+
+    // a 503 is retried by the client
+    mock.update_faults(|faults| faults.fail_next = 1);
+    client.search(&search_vars()).await.unwrap();
+
+    mock.update_faults(|faults| {
+      faults.fail_next = 1;
+      faults.fault = Fault::Graphql("RATE_LIMITED".to_string());
+    });
+    let err = client
+      .search(&search_vars())
+      .await
+      .and_then(|response| response.into_data())
+      .unwrap_err();
+    assert_eq!(
+      err.custom_context_or_root_cause().code,
+      Code::GraphqlRateLimited
+    );
+  }
+
+  #[tokio::test]
+  async fn retries_wait_without_their_permit() {
+    let mock = MockServer::new(Fixtures::default()).unwrap();
+    let server = mock.spawn().await.unwrap();
+    let client = client(&server)
+      .await
+      .with_max_in_flight(1)
+      .with_retry_policy(RetryPolicy {
+        base_delay: Duration::from_secs(1),
+        ..RetryPolicy::default()
+      });
+    let vars = |slug: &str| media::Variables {
+      slug: slug.to_string(),
+      country: "US".parse().unwrap(),
+      language: None,
+    };
+
+    let media = |slug: &'static str| {
+      let client = client.clone();
+      tokio::spawn(async move { client.media(&vars(slug)).await.map(|_| ()) })
+    };
+
+    mock.update_faults(|faults| faults.fail_next = 1);
+    let retried = media("dark");
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    // the only permit is free while the first request waits to retry
+    tokio::time::timeout(Duration::from_millis(500), media("amelie"))
+      .await
+      .expect("blocked by a retrying request")
+      .unwrap()
+      .unwrap();
+    retried.await.unwrap().unwrap();
+  }
+
+  #[tokio::test]
+  async fn search_pages_retry_a_failed_page() {
+    let mock = MockServer::new(Fixtures::default()).unwrap();
+    let server = mock.spawn().await.unwrap();
+    let client = client(&server).await;
+    let mut pages = client.search_pages(
+      search_vars(),
+      PageOptions {
+        page_size: 2,
+        prefetch: false,
+      },
+    );
+    let slugs = |nodes: Vec<search::SearchSearchNodes>| -> Vec<String> {
+      nodes.into_iter().map(|node| node.slug).collect()
+    };
+
+    let first = pages.next().await.unwrap().unwrap();
+    assert_eq!(slugs(first), ["inception", "night-of-the-living-dead"]);
+    mock.update_faults(|faults| {
+      faults.fail_next = 1;
+      faults.fault = Fault::Graphql("INTERNAL_SERVER_ERROR".to_string());
+    });
+    assert!(pages.next().await.unwrap().is_err());
+    assert!(pages.has_next_page());
+
+    let second = pages.next().await.unwrap().unwrap();
+    assert_eq!(slugs(second), ["amelie", "the-office"]);
+    let third = pages.next().await.unwrap().unwrap();
+    assert_eq!(slugs(third), ["dark", "bonanza"]);
+    assert!(!pages.has_next_page());
+    assert!(pages.next().await.is_none());
+  }
+
+  #[tokio::test]
+  async fn serves_the_cache_while_offline() {
+    let mock = MockServer::new(Fixtures::default()).unwrap();
+    let server = mock.spawn().await.unwrap();
+    let dir = std::env::temp_dir().join(format!("popcorntime-mock-cache-{}", std::process::id()));
+    let client = client(&server)
+      .await
+      .with_cache(ResponseCache::new(&dir).unwrap());
+    let online = client
+      .preferences(&preferences::Variables {})
+      .await
+      .unwrap();
+    assert_eq!(online.origin, Origin::Network);
+
+    // nothing listens on the discard port
+    let offline = client
+      .with_url("http://127.0.0.1:9")
+      .preferences(&preferences::Variables {})
+      .await
+      .unwrap();
+    assert!(
+      matches!(offline.origin, Origin::Cache { stale: true, .. }),
+      "{:?}",
+      offline.origin
+    );
+    assert!(offline.into_data().unwrap().is_some());
+    std::fs::remove_dir_all(&dir).ok();
+  }
+
+  #[tokio::test]
+  async fn serves_persisted_queries() {
+    let mock = MockServer::new(Fixtures::default()).unwrap();
+    let server = mock.spawn().await.unwrap();
+    let client = client(&server)
+      .await
+      .with_persisted_queries(PersistedQueries::Get);
+    assert_eq!(media::QUERY_HASH, hex::encode(Sha256::digest(media::QUERY)));
+
+    let vars = media::Variables {
+      slug: "dark".to_string(),
+      country: "US".parse().unwrap(),
+      language: None,
+    };
+    // the first request registers the document, the second only sends its hash
+    for _ in 0..2 {
+      let media = client.media(&vars).await.unwrap().into_data().unwrap();
+      assert_eq!(media.and_then(|data| data.media).unwrap().title, "Dark");
+    }
+    assert_eq!(mock.persisted_queries(), [media::QUERY_HASH]);
+    // the unknown hash is sent with GET, then the document with POST
+    assert_eq!(mock.methods(), [Method::GET, Method::POST, Method::GET]);
+  }
+
+  #[tokio::test]
+  async fn records_operation_metrics() {
+    let mock = MockServer::new(Fixtures::default()).unwrap();
+    let server = mock.spawn().await.unwrap();
+    let client = client(&server).await;
+
+    let vars = media::Variables {
+      slug: "amelie".to_string(),
+      country: "US".parse().unwrap(),
+      language: None,
+    };
+    client.media(&vars).await.unwrap();
+    client.media(&vars).await.unwrap();
+
+    let recent = client.metrics().recent();
+    let requests: Vec<_> = recent.iter().map(|metrics| metrics.requests).collect();
+    // the persisted query is registered by the first operation
+    assert_eq!(requests, [2, 1]);
+    assert!(recent.iter().all(|metrics| metrics.response_bytes > 0));
+    assert_ne!(recent[0].trace_id, recent[1].trace_id);
+
+    let summary = client.metrics().summary();
+    assert_eq!(summary.len(), 1);
+    assert_eq!(
+      (summary[0].operation.as_str(), summary[0].count),
+      ("Media", 2)
+    );
+  }
+}
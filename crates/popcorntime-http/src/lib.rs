@@ -0,0 +1,511 @@
+//! HTTP configuration shared by the clients of the app (GraphQL API and its
+//! subscriptions, OAuth2 broker and JWKS), set from the `http` table of the settings.
+
+use anyhow::{Context, Result};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use percent_encoding::percent_decode_str;
+use rustls::{
+  DigitallySignedStruct, RootCertStore, SignatureScheme,
+  client::{
+    WebPkiServerVerifier,
+    danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+  },
+  pki_types::{CertificateDer, ServerName, UnixTime, pem::PemObject},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{path::PathBuf, sync::Arc};
+use tokio::{
+  io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+  net::{TcpStream, lookup_host},
+};
+use tokio_rustls::TlsConnector;
+use tokio_socks::{TargetAddr, tcp::Socks5Stream};
+use url::Url;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpConfig {
+  /// Send every request through this proxy. Without it, the `HTTP_PROXY`,
+  /// `HTTPS_PROXY` and `ALL_PROXY` environment variables are used.
+  #[serde(default)]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub proxy: Option<ProxyConfig>,
+  /// Hosts reached without the proxy, e.g. `localhost` or `.corp.example.com`.
+  #[serde(default)]
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub no_proxy: Vec<String>,
+  /// PEM files of certificates trusted on top of the built-in ones, e.g. the CA of an
+  /// intercepting proxy.
+  #[serde(default)]
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub root_certificates: Vec<PathBuf>,
+  /// Only accept the certificates with these keys for our own API domains.
+  #[serde(default)]
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub pinned_certificates: Vec<CertificatePin>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProxyConfig {
+  /// `http://`, `https://`, `socks5://` or `socks5h://` (DNS resolved by the proxy).
+  pub url: String,
+  #[serde(default)]
+  pub username: Option<String>,
+  #[serde(default)]
+  pub password: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CertificatePin {
+  /// The pinned domain, its subdomains are pinned too.
+  pub host: String,
+  /// Base64 encoded sha256 of the `SubjectPublicKeyInfo` of the leaf or of one of the
+  /// intermediates, as in `pin-sha256` of HPKP.
+  pub spki_sha256: Vec<String>,
+}
+
+impl HttpConfig {
+  pub fn is_default(&self) -> bool {
+    self == &Self::default()
+  }
+
+  /// Checks that the proxy and certificates can be used, as building the clients does.
+  pub fn validate(&self) -> Result<()> {
+    self.client_builder()?.build()?;
+    self.tls_config()?;
+    Ok(())
+  }
+
+  /// A builder configured with the proxy and certificates, the clients add their own
+  /// options (user agent, redirects...) on top.
+  pub fn client_builder(&self) -> Result<reqwest::ClientBuilder> {
+    let mut builder = reqwest::ClientBuilder::new();
+    if let Some(proxy) = &self.proxy {
+      builder = builder.proxy(proxy.build(&self.no_proxy)?);
+    }
+
+    let root_certificates = self.load_root_certificates()?;
+    if self.pinned_certificates.is_empty() {
+      for certificate in root_certificates {
+        builder = builder.add_root_certificate(reqwest::Certificate::from_der(&certificate)?);
+      }
+      return Ok(builder);
+    }
+
+    // pins are checked by our own verifier, which needs rustls
+    let mut tls = self.tls_config()?;
+    tls.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(builder.use_preconfigured_tls(tls))
+  }
+
+  /// The TLS configuration of the clients: the built-in and configured roots, and the
+  /// pinned keys.
+  pub fn tls_config(&self) -> Result<rustls::ClientConfig> {
+    let mut roots = RootCertStore::empty();
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    for certificate in self.load_root_certificates()? {
+      roots.add(certificate).context("invalid root certificate")?;
+    }
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let verifier = PinnedVerifier {
+      inner: WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+        .build()?,
+      pins: self.pinned_certificates.clone(),
+    };
+    Ok(
+      rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth(),
+    )
+  }
+
+  /// Opens a connection to `url` like the clients do, through the proxy and with TLS for
+  /// `https` and `wss`, for the protocols reqwest doesn't speak (websockets).
+  pub async fn connect(&self, url: &Url) -> Result<Box<dyn Stream>> {
+    let host = host(url)?;
+    let port = url.port_or_known_default().context("missing port")?;
+    let stream = match self.proxy_for(url)? {
+      Some(proxy) => tunnel(&proxy, host, port, self).await?,
+      None => Box::new(TcpStream::connect((host, port)).await?),
+    };
+    if matches!(url.scheme(), "https" | "wss") {
+      self.tls(host, stream).await
+    } else {
+      Ok(stream)
+    }
+  }
+
+  async fn tls(&self, host: &str, stream: Box<dyn Stream>) -> Result<Box<dyn Stream>> {
+    let server_name = ServerName::try_from(host.to_string())?;
+    let connector = TlsConnector::from(Arc::new(self.tls_config()?));
+    Ok(Box::new(connector.connect(server_name, stream).await?))
+  }
+
+  /// The proxy of `url`, from the settings or else from the environment as reqwest does.
+  fn proxy_for(&self, url: &Url) -> Result<Option<Url>> {
+    let (proxy, no_proxy) = match &self.proxy {
+      Some(proxy) => (Some(proxy.url()?), self.no_proxy.clone()),
+      None => {
+        let names = match url.scheme() {
+          "https" | "wss" => ["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"],
+          _ => ["HTTP_PROXY", "http_proxy", "ALL_PROXY", "all_proxy"],
+        };
+        let proxy = names
+          .iter()
+          .filter_map(|name| std::env::var(name).ok())
+          .find(|value| !value.is_empty())
+          .and_then(|value| {
+            Url::parse(&value)
+              .or_else(|_| Url::parse(&format!("http://{value}")))
+              .ok()
+          });
+        let no_proxy = std::env::var("NO_PROXY")
+          .or_else(|_| std::env::var("no_proxy"))
+          .unwrap_or_default()
+          .split(',')
+          .map(|host| host.trim().to_string())
+          .collect();
+        (proxy, no_proxy)
+      }
+    };
+    let host = host(url)?.to_ascii_lowercase();
+    let bypassed = no_proxy.iter().any(|entry| {
+      let entry = entry.trim_start_matches('.').to_ascii_lowercase();
+      entry == "*" || host == entry || host.ends_with(&format!(".{entry}"))
+    });
+    Ok(proxy.filter(|_| !bypassed))
+  }
+
+  fn load_root_certificates(&self) -> Result<Vec<CertificateDer<'static>>> {
+    let mut certificates = Vec::new();
+    for path in &self.root_certificates {
+      for certificate in CertificateDer::pem_file_iter(path)
+        .with_context(|| format!("failed to read root certificates from {path:?}"))?
+      {
+        certificates
+          .push(certificate.with_context(|| format!("invalid root certificate in {path:?}"))?);
+      }
+    }
+    Ok(certificates)
+  }
+}
+
+impl ProxyConfig {
+  fn build(&self, no_proxy: &[String]) -> Result<reqwest::Proxy> {
+    let proxy = reqwest::Proxy::all(self.url()?)?;
+    Ok(proxy.no_proxy(reqwest::NoProxy::from_string(&no_proxy.join(","))))
+  }
+
+  /// The url of the proxy with the credentials, `basic_auth` of reqwest only applies to
+  /// http proxies while the url ones are used for socks proxies too.
+  fn url(&self) -> Result<Url> {
+    let mut url = Url::parse(&self.url).context("invalid proxy url")?;
+    anyhow::ensure!(
+      matches!(url.scheme(), "http" | "https" | "socks5" | "socks5h"),
+      "unsupported proxy scheme `{}`",
+      url.scheme()
+    );
+    if let Some(username) = &self.username {
+      url
+        .set_username(username)
+        .and_then(|()| url.set_password(self.password.as_deref()))
+        .map_err(|()| anyhow::anyhow!("invalid proxy url"))?;
+    }
+    Ok(url)
+  }
+}
+
+/// A connection opened by [`HttpConfig::connect`].
+pub trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Stream for T {}
+
+/// Opens a tunnel to `host:port` through the proxy: `CONNECT` for http proxies, a socks5
+/// handshake for the others.
+async fn tunnel(
+  proxy: &Url,
+  host: &str,
+  port: u16,
+  config: &HttpConfig,
+) -> Result<Box<dyn Stream>> {
+  let proxy_host = self::host(proxy)?;
+  let proxy_port = proxy.port_or_known_default().unwrap_or(1080);
+  let username = percent_decode_str(proxy.username()).decode_utf8()?;
+  let password = percent_decode_str(proxy.password().unwrap_or_default()).decode_utf8()?;
+  let tcp = TcpStream::connect((proxy_host, proxy_port))
+    .await
+    .with_context(|| format!("failed to connect to the proxy {proxy_host}:{proxy_port}"))?;
+
+  if proxy.scheme().starts_with("socks5") {
+    let target = if proxy.scheme() == "socks5h" {
+      TargetAddr::Domain(host.to_string().into(), port)
+    } else {
+      // socks5 resolves the names locally, socks5h lets the proxy resolve them
+      let address = lookup_host((host, port))
+        .await?
+        .next()
+        .with_context(|| format!("failed to resolve {host}"))?;
+      TargetAddr::Ip(address)
+    };
+    let stream = if username.is_empty() {
+      Socks5Stream::connect_with_socket(tcp, target).await?
+    } else {
+      Socks5Stream::connect_with_password_and_socket(tcp, target, &username, &password).await?
+    };
+    return Ok(Box::new(stream));
+  }
+
+  let mut stream: Box<dyn Stream> = if proxy.scheme() == "https" {
+    config.tls(proxy_host, Box::new(tcp)).await?
+  } else {
+    Box::new(tcp)
+  };
+  let mut request = format!("CONNECT {host}:{port} HTTP/1.1\r\nHost: {host}:{port}\r\n");
+  if !username.is_empty() {
+    let credentials = BASE64_STANDARD.encode(format!("{username}:{password}"));
+    request.push_str(&format!("Proxy-Authorization: Basic {credentials}\r\n"));
+  }
+  request.push_str("\r\n");
+  stream.write_all(request.as_bytes()).await?;
+
+  // read the response byte by byte, what follows it belongs to the tunnel
+  let mut response = Vec::new();
+  while !response.ends_with(b"\r\n\r\n") {
+    anyhow::ensure!(response.len() < 8192, "invalid proxy response");
+    response.push(stream.read_u8().await?);
+  }
+  let status = String::from_utf8_lossy(&response);
+  let status = status.lines().next().unwrap_or_default();
+  anyhow::ensure!(
+    status.split_whitespace().nth(1) == Some("200"),
+    "the proxy refused the connection: {status}"
+  );
+  Ok(stream)
+}
+
+fn host(url: &Url) -> Result<&str> {
+  let host = url.host_str().context("missing host")?;
+  Ok(host.trim_start_matches('[').trim_end_matches(']'))
+}
+
+/// Checks the certificates as usual, then that the chain of pinned hosts contains one
+/// of their pinned keys.
+#[derive(Debug)]
+struct PinnedVerifier {
+  inner: Arc<WebPkiServerVerifier>,
+  pins: Vec<CertificatePin>,
+}
+
+impl PinnedVerifier {
+  fn pins(&self, server_name: &ServerName<'_>) -> Vec<&str> {
+    let ServerName::DnsName(name) = server_name else {
+      return Vec::new();
+    };
+    let name = name.as_ref().trim_end_matches('.').to_ascii_lowercase();
+    self
+      .pins
+      .iter()
+      .filter(|pin| {
+        let host = pin.host.trim_end_matches('.').to_ascii_lowercase();
+        name == host || name.ends_with(&format!(".{host}"))
+      })
+      .flat_map(|pin| pin.spki_sha256.iter().map(String::as_str))
+      .collect()
+  }
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+  fn verify_server_cert(
+    &self,
+    end_entity: &CertificateDer<'_>,
+    intermediates: &[CertificateDer<'_>],
+    server_name: &ServerName<'_>,
+    ocsp_response: &[u8],
+    now: UnixTime,
+  ) -> Result<ServerCertVerified, rustls::Error> {
+    let verified =
+      self
+        .inner
+        .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)?;
+    let pins = self.pins(server_name);
+    if pins.is_empty()
+      || std::iter::once(end_entity)
+        .chain(intermediates)
+        .filter_map(spki_sha256)
+        .any(|hash| pins.contains(&hash.as_str()))
+    {
+      return Ok(verified);
+    }
+    Err(rustls::Error::General(format!(
+      "no pinned key in the certificates of {}",
+      server_name.to_str()
+    )))
+  }
+
+  fn verify_tls12_signature(
+    &self,
+    message: &[u8],
+    cert: &CertificateDer<'_>,
+    dss: &DigitallySignedStruct,
+  ) -> Result<HandshakeSignatureValid, rustls::Error> {
+    self.inner.verify_tls12_signature(message, cert, dss)
+  }
+
+  fn verify_tls13_signature(
+    &self,
+    message: &[u8],
+    cert: &CertificateDer<'_>,
+    dss: &DigitallySignedStruct,
+  ) -> Result<HandshakeSignatureValid, rustls::Error> {
+    self.inner.verify_tls13_signature(message, cert, dss)
+  }
+
+  fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+    self.inner.supported_verify_schemes()
+  }
+}
+
+fn spki_sha256(certificate: &CertificateDer<'_>) -> Option<String> {
+  let certificate = webpki::EndEntityCert::try_from(certificate).ok()?;
+  let hash = Sha256::digest(certificate.subject_public_key_info().as_ref());
+  Some(BASE64_STANDARD.encode(hash))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn reads_the_settings() {
+    let config: HttpConfig = toml::from_str(
+      r#"
+        noProxy = ["localhost", ".corp.example.com"]
+
+        [proxy]
+        url = "socks5h://proxy.corp.example.com:1080"
+        username = "user"
+        password = "secret"
+
+        [[pinnedCertificates]]
+        host = "popcorntime.app"
+        spkiSha256 = ["bWFkZSB1cCBwaW4gZm9yIHRoZSB0ZXN0cyAhISEhISE="]
+      "#,
+    )
+    .unwrap();
+    assert!(config.client_builder().unwrap().build().is_ok());
+
+    let verifier = PinnedVerifier {
+      inner: WebPkiServerVerifier::builder_with_provider(
+        Arc::new(RootCertStore {
+          roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        }),
+        Arc::new(rustls::crypto::ring::default_provider()),
+      )
+      .build()
+      .unwrap(),
+      pins: config.pinned_certificates.clone(),
+    };
+    let pins = |name: &'static str| verifier.pins(&ServerName::try_from(name).unwrap()).len();
+    assert_eq!(pins("popcorntime.app"), 1);
+    assert_eq!(pins("api.popcorntime.app"), 1);
+    assert_eq!(pins("notpopcorntime.app"), 0);
+
+    assert!(HttpConfig::default().is_default());
+    assert!(
+      toml::to_string(&HttpConfig::default())
+        .unwrap()
+        .trim()
+        .is_empty()
+    );
+  }
+
+  #[test]
+  fn rejects_unsupported_proxies() {
+    let config = HttpConfig {
+      proxy: Some(ProxyConfig {
+        url: "ftp://proxy:21".to_string(),
+        username: None,
+        password: None,
+      }),
+      ..Default::default()
+    };
+    assert!(config.client_builder().is_err());
+    assert!(config.validate().is_err());
+    assert!(HttpConfig::default().validate().is_ok());
+  }
+
+  #[test]
+  fn puts_the_credentials_in_the_proxy_url() {
+    let proxy = ProxyConfig {
+      url: "socks5h://proxy.corp.example.com:1080".to_string(),
+      username: Some("jane doe".to_string()),
+      password: Some("p@ss:word".to_string()),
+    };
+    let url = proxy.url().unwrap();
+    assert_eq!(url.username(), "jane%20doe");
+    assert_eq!(url.password(), Some("p%40ss%3Aword"));
+    assert!(proxy.build(&[]).is_ok());
+
+    let anonymous = ProxyConfig {
+      username: None,
+      password: None,
+      ..proxy
+    };
+    assert_eq!(
+      anonymous.url().unwrap().as_str(),
+      "socks5h://proxy.corp.example.com:1080"
+    );
+  }
+
+  #[tokio::test]
+  async fn connects_through_the_proxy() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let proxy = tokio::spawn(async move {
+      let (mut stream, _) = listener.accept().await.unwrap();
+      let mut request = Vec::new();
+      while !request.ends_with(b"\r\n\r\n") {
+        request.push(stream.read_u8().await.unwrap());
+      }
+      stream.write_all(b"HTTP/1.1 200 OK\r\n\r\n").await.unwrap();
+      stream.write_all(b"tunnel").await.unwrap();
+      String::from_utf8(request).unwrap()
+    });
+
+    let config = HttpConfig {
+      proxy: Some(ProxyConfig {
+        url: format!("http://{address}"),
+        username: Some("jane".to_string()),
+        password: Some("secret".to_string()),
+      }),
+      ..Default::default()
+    };
+    let mut stream = config
+      .connect(&Url::parse("ws://api.popcorntime.app/graphql").unwrap())
+      .await
+      .unwrap();
+    let mut body = String::new();
+    stream.read_to_string(&mut body).await.unwrap();
+    assert_eq!(body, "tunnel");
+
+    let request = proxy.await.unwrap();
+    assert!(request.starts_with("CONNECT api.popcorntime.app:80 HTTP/1.1\r\n"));
+    assert!(request.contains(&format!(
+      "Proxy-Authorization: Basic {}\r\n",
+      BASE64_STANDARD.encode("jane:secret")
+    )));
+
+    // bypassed hosts are reached directly
+    let config = HttpConfig {
+      no_proxy: vec![".popcorntime.app".to_string()],
+      ..config
+    };
+    let url = Url::parse("wss://api.popcorntime.app/graphql").unwrap();
+    assert_eq!(config.proxy_for(&url).unwrap(), None);
+  }
+}
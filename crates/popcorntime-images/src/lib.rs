@@ -0,0 +1,608 @@
+//! Cache of the poster and backdrop images, served to the webview through the
+//! `ptimg://` protocol so every image is downloaded once and keeps working offline.
+//!
+//! `ptimg://localhost/o/ID.jpg?w=300` is `https://img.popcorntime.app/o/ID@300.jpg`, the
+//! variant 300px wide resized by the image server, and `ptimg://localhost/o/ID.jpg`
+//! the original. While offline, another cached size of the image is served.
+//!
+//! The [`Placeholder`] of every downloaded image is cached along with it.
+
+use anyhow::{Context, Result};
+use image::ImageFormat;
+use percent_encoding::percent_decode_str;
+pub use placeholder::Placeholder;
+use popcorntime_http::HttpConfig;
+use sha2::{Digest, Sha256};
+use std::{
+  collections::HashMap,
+  path::{Path, PathBuf},
+  sync::{Arc, Mutex},
+  time::SystemTime,
+};
+
+mod placeholder;
+
+pub const IMAGE_SERVER: &str = "https://img.popcorntime.app";
+/// The name of the URI scheme registered in the webview.
+pub const PROTOCOL: &str = "ptimg";
+
+const DEFAULT_MAX_SIZE: u64 = 256 * 1024 * 1024;
+/// Widths of the variants of the image server, requested widths are rounded up to one
+/// of them so a few variants are cached per image.
+const WIDTHS: [u32; 5] = [150, 300, 600, 960, 1280];
+
+/// An image of the image server, at its original size or the variant `width` wide.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageRequest {
+  path: String,
+  width: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Image {
+  pub bytes: Vec<u8>,
+  pub content_type: &'static str,
+}
+
+impl ImageRequest {
+  /// Parse the path and query string of a `ptimg://` uri.
+  pub fn parse(path: &str, query: Option<&str>) -> Result<Self> {
+    let path = percent_decode_str(path.trim_start_matches('/'))
+      .decode_utf8()
+      .context("invalid image path")?
+      .into_owned();
+    anyhow::ensure!(
+      !path.is_empty()
+        && !path.contains("..")
+        && path
+          .chars()
+          .all(|c| c.is_ascii_alphanumeric() || matches!(c, '/' | '.' | '-' | '_')),
+      "invalid image path `{path}`"
+    );
+    anyhow::ensure!(content_type(&path).is_some(), "unsupported image `{path}`");
+
+    let width = query
+      .into_iter()
+      .flat_map(|query| query.split('&'))
+      .find_map(|param| param.strip_prefix("w="))
+      .map(|width| width.parse::<u32>().context("invalid image width"))
+      .transpose()?
+      // wider than the widest variant is the original
+      .and_then(|width| WIDTHS.into_iter().find(|variant| *variant >= width));
+    Ok(Self { path, width })
+  }
+
+  /// The path of the requested size on the image server.
+  fn server_path(&self) -> String {
+    match (self.width, self.path.rsplit_once('.')) {
+      (Some(width), Some((stem, _))) => format!("{stem}@{width}.jpg"),
+      _ => self.path.clone(),
+    }
+  }
+
+  fn file_name(&self) -> String {
+    self.sized_file_name(self.width)
+  }
+
+  /// The cached sizes of the image, the original first and then the widest.
+  fn file_names(&self) -> impl Iterator<Item = String> + '_ {
+    std::iter::once(None)
+      .chain(WIDTHS.into_iter().rev().map(Some))
+      .map(|width| self.sized_file_name(width))
+  }
+
+  fn sized_file_name(&self, width: Option<u32>) -> String {
+    match width {
+      Some(width) => format!("{}@{width}.jpg", self.hash()),
+      None => {
+        let extension = self
+          .path
+          .rsplit_once('.')
+          .map(|(_, ext)| ext)
+          .unwrap_or_default();
+        format!("{}.{extension}", self.hash())
+      }
+    }
+  }
+
+  fn placeholder_file_name(&self) -> String {
+    format!("{}.placeholder.json", self.hash())
+  }
+
+  fn hash(&self) -> String {
+    hex::encode(&Sha256::digest(self.path.as_bytes())[..16])
+  }
+}
+
+/// Images stored in the cache directory, the least recently used are removed once
+/// they take more than the max size.
+#[derive(Debug, Clone)]
+pub struct ImageCache {
+  dir: PathBuf,
+  server: String,
+  client: reqwest::Client,
+  index: Arc<Mutex<Index>>,
+  /// The downloads in progress by file name, so an image is downloaded once.
+  downloads: Arc<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>>,
+}
+
+#[derive(Debug)]
+struct Index {
+  max_size: u64,
+  size: u64,
+  entries: HashMap<String, Entry>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+  size: u64,
+  used_at: SystemTime,
+}
+
+impl ImageCache {
+  pub fn new(dir: &Path, http_config: &HttpConfig) -> Result<Self> {
+    std::fs::create_dir_all(dir)?;
+    let mut index = Index {
+      max_size: DEFAULT_MAX_SIZE,
+      size: 0,
+      entries: HashMap::new(),
+    };
+    for entry in std::fs::read_dir(dir)? {
+      let entry = entry?;
+      let metadata = entry.metadata()?;
+      let Some(file_name) = entry.file_name().to_str().map(str::to_string) else {
+        continue;
+      };
+      if !metadata.is_file() {
+        continue;
+      }
+      // left by an interrupted write
+      if file_name.ends_with(".tmp") {
+        let _ = std::fs::remove_file(entry.path());
+        continue;
+      }
+      // the modification time is updated on use, see `touch`
+      let used_at = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+      index.insert(file_name, metadata.len(), used_at);
+    }
+
+    Ok(Self {
+      dir: dir.to_path_buf(),
+      server: IMAGE_SERVER.to_string(),
+      client: http_config.client_builder()?.build()?,
+      index: Arc::new(Mutex::new(index)),
+      downloads: Arc::default(),
+    })
+  }
+
+  /// Download the images from `server` instead of `IMAGE_SERVER`.
+  pub fn with_server(mut self, server: impl Into<String>) -> Self {
+    self.server = server.into();
+    self
+  }
+
+  /// Keep at most `max_size` bytes of images, 256 MiB by default.
+  pub fn with_max_size(self, max_size: u64) -> Self {
+    self.lock().max_size = max_size;
+    self
+  }
+
+  /// Change the max size, evicting the least recently used images which no longer fit.
+  pub async fn set_max_size(&self, max_size: u64) {
+    let evicted = {
+      let mut index = self.lock();
+      index.max_size = max_size;
+      index.evict(None)
+    };
+    self.remove(evicted).await;
+  }
+
+  pub fn max_size(&self) -> u64 {
+    self.lock().max_size
+  }
+
+  pub fn dir(&self) -> &Path {
+    &self.dir
+  }
+
+  /// The size of the cached images, in bytes.
+  pub fn size(&self) -> u64 {
+    self.lock().size
+  }
+
+  pub async fn get(&self, request: &ImageRequest) -> Result<Image> {
+    let file_name = request.file_name();
+    if let Some(image) = self.read(&file_name).await {
+      return Ok(image);
+    }
+
+    match self.download(request, &file_name).await {
+      Ok(image) => {
+        if !self
+          .lock()
+          .entries
+          .contains_key(&request.placeholder_file_name())
+        {
+          self.compute_placeholder_in_background(request, image.bytes.clone());
+        }
+        Ok(image)
+      }
+      // e.g. offline, another size is better than no image
+      Err(err) => match self.read_any(request).await {
+        Some(image) => {
+          tracing::debug!(path = request.path, "serving another size: {err:?}");
+          Ok(image)
+        }
+        None => Err(err),
+      },
+    }
+  }
+
+  /// Download `file_name` once, concurrent requests of the same image wait for it.
+  async fn download(&self, request: &ImageRequest, file_name: &str) -> Result<Image> {
+    let download = self
+      .lock_downloads()
+      .entry(file_name.to_string())
+      .or_default()
+      .clone();
+    let guard = download.lock().await;
+    // downloaded while waiting
+    let result = match self.read(file_name).await {
+      Some(image) => Ok(image),
+      None => self.fetch(request, file_name).await,
+    };
+    drop(guard);
+
+    // the last one out removes the entry, new waiters clone it under the same lock
+    let mut downloads = self.lock_downloads();
+    if Arc::strong_count(&download) == 2 {
+      downloads.remove(file_name);
+    }
+    result
+  }
+
+  async fn fetch(&self, request: &ImageRequest, file_name: &str) -> Result<Image> {
+    let url = format!(
+      "{}/{}",
+      self.server.trim_end_matches('/'),
+      request.server_path()
+    );
+    tracing::debug!(url, "downloading image");
+    let bytes = self
+      .client
+      .get(&url)
+      .send()
+      .await
+      .and_then(|response| response.error_for_status())
+      .with_context(|| format!("failed to download {url}"))?
+      .bytes()
+      .await?
+      .to_vec();
+    // don't cache error pages served with a success status
+    image::guess_format(&bytes).with_context(|| format!("{url} is not an image"))?;
+
+    self.write(file_name, &bytes).await?;
+    Ok(Image {
+      bytes,
+      content_type: content_type(file_name).unwrap_or("application/octet-stream"),
+    })
+  }
+
+  /// Any cached size of the image.
+  async fn read_any(&self, request: &ImageRequest) -> Option<Image> {
+    for file_name in request.file_names() {
+      if let Some(image) = self.read(&file_name).await {
+        return Some(image);
+      }
+    }
+    None
+  }
+
+  /// The placeholder of an image, computed from its smallest variant when no size of
+  /// it is cached yet. `None` if it can't be downloaded, e.g. offline.
+  pub async fn placeholder(&self, request: &ImageRequest) -> Option<Placeholder> {
+    let file_name = request.placeholder_file_name();
+    if let Some(placeholder) = self
+      .read_bytes(&file_name)
+      .await
+      .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+    {
+      return Some(placeholder);
+    }
+
+    // cached before its placeholder, the placeholder was evicted or never downloaded
+    let image = match self.read_any(request).await {
+      Some(image) => image,
+      None => {
+        let smallest = ImageRequest {
+          path: request.path.clone(),
+          width: Some(WIDTHS[0]),
+        };
+        match self.download(&smallest, &smallest.file_name()).await {
+          Ok(image) => image,
+          Err(err) => {
+            tracing::debug!(path = request.path, "no image for the placeholder: {err:?}");
+            return None;
+          }
+        }
+      }
+    };
+    match self.store_placeholder(request, image.bytes).await {
+      Ok(placeholder) => Some(placeholder),
+      Err(err) => {
+        tracing::warn!(
+          path = request.path,
+          "failed to compute placeholder: {err:?}"
+        );
+        None
+      }
+    }
+  }
+
+  fn compute_placeholder_in_background(&self, request: &ImageRequest, bytes: Vec<u8>) {
+    let cache = self.clone();
+    let request = request.clone();
+    tokio::spawn(async move {
+      if let Err(err) = cache.store_placeholder(&request, bytes).await {
+        tracing::warn!(
+          path = request.path,
+          "failed to compute placeholder: {err:?}"
+        );
+      }
+    });
+  }
+
+  async fn store_placeholder(&self, request: &ImageRequest, bytes: Vec<u8>) -> Result<Placeholder> {
+    let placeholder = tokio::task::spawn_blocking(move || Placeholder::compute(&bytes)).await??;
+    self
+      .write(
+        &request.placeholder_file_name(),
+        &serde_json::to_vec(&placeholder)?,
+      )
+      .await?;
+    Ok(placeholder)
+  }
+
+  async fn read(&self, file_name: &str) -> Option<Image> {
+    Some(Image {
+      content_type: content_type(file_name)?,
+      bytes: self.read_bytes(file_name).await?,
+    })
+  }
+
+  async fn read_bytes(&self, file_name: &str) -> Option<Vec<u8>> {
+    let path = self.dir.join(file_name);
+    let bytes = tokio::fs::read(&path).await.ok()?;
+    self.touch(file_name, path);
+    Some(bytes)
+  }
+
+  /// Remove all cached images and placeholders.
+  pub async fn clear(&self) -> Result<()> {
+    let file_names: Vec<_> = {
+      let mut index = self.lock();
+      index.size = 0;
+      index
+        .entries
+        .drain()
+        .map(|(file_name, _)| file_name)
+        .collect()
+    };
+    self.remove(file_names).await;
+    Ok(())
+  }
+
+  /// Mark `file_name` as used, in the index and in the file modification time used to
+  /// rebuild the index on start.
+  fn touch(&self, file_name: &str, path: PathBuf) {
+    let now = SystemTime::now();
+    if let Some(entry) = self.lock().entries.get_mut(file_name) {
+      entry.used_at = now;
+    }
+    tokio::task::spawn_blocking(move || {
+      if let Err(err) = std::fs::File::options()
+        .write(true)
+        .open(&path)
+        .and_then(|file| file.set_modified(now))
+      {
+        tracing::debug!("failed to touch {path:?}: {err}");
+      }
+    });
+  }
+
+  /// Write to a temporary file first, so readers never see a partial image.
+  async fn write(&self, file_name: &str, bytes: &[u8]) -> Result<()> {
+    let path = self.dir.join(file_name);
+    // unique, so concurrent writes of a file don't interleave
+    let tmp_path = path.with_extension(format!("{:016x}.tmp", fastrand::u64(..)));
+    tokio::fs::write(&tmp_path, bytes).await?;
+    tokio::fs::rename(tmp_path, path).await?;
+
+    let evicted = {
+      let mut index = self.lock();
+      index.insert(file_name.to_string(), bytes.len() as u64, SystemTime::now());
+      index.evict(Some(file_name))
+    };
+    self.remove(evicted).await;
+    Ok(())
+  }
+
+  async fn remove(&self, file_names: Vec<String>) {
+    for file_name in file_names {
+      if let Err(err) = tokio::fs::remove_file(self.dir.join(&file_name)).await {
+        tracing::warn!(file_name, "failed to remove image: {err}");
+      }
+    }
+  }
+
+  fn lock(&self) -> std::sync::MutexGuard<'_, Index> {
+    self.index.lock().unwrap_or_else(|err| err.into_inner())
+  }
+
+  fn lock_downloads(
+    &self,
+  ) -> std::sync::MutexGuard<'_, HashMap<String, Arc<tokio::sync::Mutex<()>>>> {
+    self.downloads.lock().unwrap_or_else(|err| err.into_inner())
+  }
+}
+
+impl Index {
+  fn insert(&mut self, file_name: String, size: u64, used_at: SystemTime) {
+    if let Some(previous) = self.entries.insert(file_name, Entry { size, used_at }) {
+      self.size -= previous.size;
+    }
+    self.size += size;
+  }
+
+  /// Remove the least recently used entries, except `keep`, until the size fits.
+  fn evict(&mut self, keep: Option<&str>) -> Vec<String> {
+    let mut entries: Vec<_> = self
+      .entries
+      .iter()
+      .filter(|(file_name, _)| Some(file_name.as_str()) != keep)
+      .map(|(file_name, entry)| (entry.used_at, file_name.clone()))
+      .collect();
+    entries.sort_unstable();
+
+    let mut evicted = Vec::new();
+    for (_, file_name) in entries {
+      if self.size <= self.max_size {
+        break;
+      }
+      if let Some(entry) = self.entries.remove(&file_name) {
+        self.size -= entry.size;
+        evicted.push(file_name);
+      }
+    }
+    evicted
+  }
+}
+
+fn content_type(file_name: &str) -> Option<&'static str> {
+  let (_, extension) = file_name.rsplit_once('.')?;
+  let format = ImageFormat::from_extension(extension)?;
+  matches!(
+    format,
+    ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::WebP
+  )
+  .then(|| format.to_mime_type())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use poem::{
+    Route, get, handler,
+    listener::{Acceptor, Listener, TcpListener},
+    web::Path as PathParam,
+  };
+  use std::{
+    io::Cursor,
+    sync::atomic::{AtomicUsize, Ordering},
+  };
+
+  static DOWNLOADS: AtomicUsize = AtomicUsize::new(0);
+
+  /// `ID.png` is a 600x900 original, `ID@WIDTH.jpg` its variant.
+  #[handler]
+  fn poster(PathParam(file_name): PathParam<String>) -> poem::Response {
+    DOWNLOADS.fetch_add(1, Ordering::SeqCst);
+    let (width, format) = match file_name.split_once('@') {
+      Some((_, variant)) => (
+        variant.trim_end_matches(".jpg").parse().unwrap(),
+        ImageFormat::Jpeg,
+      ),
+      None => (600, ImageFormat::Png),
+    };
+    let mut bytes = Vec::new();
+    image::RgbImage::from_pixel(width, width * 3 / 2, image::Rgb([200, 40, 40]))
+      .write_to(&mut Cursor::new(&mut bytes), format)
+      .unwrap();
+    poem::Response::builder()
+      .content_type(format.to_mime_type())
+      .body(bytes)
+  }
+
+  #[tokio::test]
+  async fn variants_are_downloaded_once_and_served_offline() {
+    let dir = std::env::temp_dir().join(format!("popcorntime-images-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let acceptor = TcpListener::bind("127.0.0.1:0")
+      .into_acceptor()
+      .await
+      .unwrap();
+    let addr = *acceptor.local_addr()[0].as_socket_addr().unwrap();
+    let server = tokio::spawn(
+      poem::Server::new_with_acceptor(acceptor).run(Route::new().at("/o/:file_name", get(poster))),
+    );
+    let cache = ImageCache::new(&dir, &HttpConfig::default())
+      .unwrap()
+      .with_server(format!("http://{addr}"));
+
+    // concurrent requests share the download of the variant
+    let request = ImageRequest::parse("/o/poster.png", Some("w=280")).unwrap();
+    let (first, second) = tokio::join!(cache.get(&request), cache.get(&request));
+    assert_eq!(DOWNLOADS.load(Ordering::SeqCst), 1);
+    assert_eq!(first.unwrap().bytes, second.unwrap().bytes);
+    let image = cache.get(&request).await.unwrap();
+    assert_eq!(image.content_type, "image/jpeg");
+    let variant = image::load_from_memory(&image.bytes).unwrap();
+    assert_eq!((variant.width(), variant.height()), (300, 450));
+    assert_eq!(DOWNLOADS.load(Ordering::SeqCst), 1);
+
+    // the placeholders of images not cached yet come from the smallest variant
+    let request = ImageRequest::parse("/o/backdrop.png", None).unwrap();
+    let placeholder = cache.placeholder(&request).await.unwrap();
+    assert_eq!(placeholder.color, "#c82828");
+    assert_eq!(DOWNLOADS.load(Ordering::SeqCst), 2);
+    assert!(dir.join(format!("{}@150.jpg", request.hash())).exists());
+
+    // offline, the sizes never downloaded fall back to a cached one
+    server.abort();
+    let _ = server.await;
+    // nothing listens on the discard port
+    let cache = ImageCache::new(&dir, &HttpConfig::default())
+      .unwrap()
+      .with_server("http://127.0.0.1:9");
+    let request = ImageRequest::parse("/o/poster.png", Some("w=150")).unwrap();
+    let image = cache.get(&request).await.unwrap();
+    assert_eq!(image::load_from_memory(&image.bytes).unwrap().width(), 300);
+    let placeholder = cache.placeholder(&request).await.unwrap();
+    assert_eq!(placeholder.color, "#c82828");
+    assert_eq!(placeholder.blurhash.len(), 4 + 3 * 4 * 2);
+
+    // the least recently used are evicted
+    let cache = ImageCache::new(&dir, &HttpConfig::default())
+      .unwrap()
+      .with_max_size(1);
+    cache.write("new.jpg", b"new").await.unwrap();
+    assert_eq!(cache.size(), 3);
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+    cache.clear().await.unwrap();
+    assert_eq!(cache.size(), 0);
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+    let _ = std::fs::remove_dir_all(&dir);
+  }
+
+  #[test]
+  fn rejects_paths_outside_the_image_server() {
+    assert!(ImageRequest::parse("/o/../../etc/passwd.jpg", None).is_err());
+    assert!(ImageRequest::parse("/o/poster.txt", None).is_err());
+    assert!(ImageRequest::parse("/o/poster.jpg", Some("w=abc")).is_err());
+    assert_eq!(
+      ImageRequest::parse("/o%2Fposter.jpg", Some("w=2000")).unwrap(),
+      ImageRequest {
+        path: "o/poster.jpg".to_string(),
+        width: None,
+      }
+    );
+  }
+
+  #[test]
+  fn variants_are_requested_from_the_image_server() {
+    let request = ImageRequest::parse("/o/poster.jpg", Some("w=200")).unwrap();
+    assert_eq!(request.server_path(), "o/poster@300.jpg");
+    let request = ImageRequest::parse("/o/poster.jpg", None).unwrap();
+    assert_eq!(request.server_path(), "o/poster.jpg");
+  }
+}
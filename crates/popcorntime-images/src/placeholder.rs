@@ -0,0 +1,71 @@
+//! Previews rendered by the UI while the images load.
+
+use anyhow::Result;
+use image::imageops::FilterType;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Width of the thumbnail the placeholder is computed from, larger only slows it down.
+const THUMBNAIL_WIDTH: u32 = 32;
+/// Components of the blurhash along the long and the short side.
+const BLURHASH_COMPONENTS: (u32, u32) = (4, 3);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, specta::Type)]
+#[serde(rename_all = "camelCase")]
+pub struct Placeholder {
+  pub blurhash: String,
+  /// The dominant colour, as `#rrggbb`.
+  pub color: String,
+}
+
+impl Placeholder {
+  pub fn compute(bytes: &[u8]) -> Result<Self> {
+    let thumbnail = image::load_from_memory(bytes)?
+      .resize(THUMBNAIL_WIDTH, u32::MAX, FilterType::Triangle)
+      .to_rgba8();
+    let (long, short) = BLURHASH_COMPONENTS;
+    // posters are portrait, backdrops landscape
+    let (components_x, components_y) = if thumbnail.width() >= thumbnail.height() {
+      (long, short)
+    } else {
+      (short, long)
+    };
+    let blurhash = blurhash::encode(
+      components_x,
+      components_y,
+      thumbnail.width(),
+      thumbnail.height(),
+      thumbnail.as_raw(),
+    )
+    .map_err(|err| anyhow::anyhow!("failed to encode blurhash: {err:?}"))?;
+    Ok(Self {
+      blurhash,
+      color: dominant_color(
+        thumbnail
+          .pixels()
+          .map(|pixel| [pixel[0], pixel[1], pixel[2]]),
+      ),
+    })
+  }
+}
+
+/// The average of the most common colour bucket, so a small bright title doesn't tint
+/// a dark poster like a plain average would.
+fn dominant_color(pixels: impl Iterator<Item = [u8; 3]>) -> String {
+  let mut buckets: BTreeMap<[u8; 3], (u32, [u32; 3])> = BTreeMap::new();
+  for pixel in pixels {
+    let (count, sum) = buckets
+      .entry(pixel.map(|channel| channel >> 5))
+      .or_default();
+    *count += 1;
+    for (sum, channel) in sum.iter_mut().zip(pixel) {
+      *sum += channel as u32;
+    }
+  }
+  let [r, g, b] = buckets
+    .into_values()
+    .max_by_key(|(count, _)| *count)
+    .map(|(count, sum)| sum.map(|sum| (sum / count) as u8))
+    .unwrap_or_default();
+  format!("#{r:02x}{g:02x}{b:02x}")
+}
@@ -1,10 +1,12 @@
+use crate::Unreachable;
 use anyhow::Result;
 use oauth2::basic::{BasicClient, BasicTokenType};
 use oauth2::{AuthUrl, ClientId, RedirectUrl, TokenUrl};
 use oauth2::{
-  AuthorizationCode, CsrfToken, EmptyExtraTokenFields, PkceCodeChallenge, RefreshToken, Scope,
-  StandardTokenResponse, TokenResponse, reqwest,
+  AuthorizationCode, CsrfToken, EmptyExtraTokenFields, PkceCodeChallenge, RefreshToken,
+  RequestTokenError, Scope, StandardTokenResponse, TokenResponse, reqwest,
 };
+use popcorntime_http::HttpConfig;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -79,7 +81,7 @@ impl Default for AuthorizationBrokerEvent {
 }
 
 impl AuthorizationBroker {
-  pub fn new(client_id: &str, uri: impl TryInto<Url>) -> Result<Self> {
+  pub fn new(client_id: &str, uri: impl TryInto<Url>, http_config: &HttpConfig) -> Result<Self> {
     tracing::info!("Creating new authorization broker for {}", client_id);
     let uri: Url = uri
       .try_into()
@@ -87,7 +89,8 @@ impl AuthorizationBroker {
     let auth_url = AuthUrl::new(uri.join("oauth2/auth").unwrap_or(uri.clone()).to_string())?;
     let token_url = TokenUrl::new(uri.join("oauth2/token").unwrap_or(uri).to_string())?;
 
-    let reqwest_client = reqwest::ClientBuilder::new()
+    let reqwest_client = http_config
+      .client_builder()?
       // following redirects opens the client up to SSRF vulnerabilities
       .redirect(reqwest::redirect::Policy::none())
       .build()?;
@@ -117,7 +120,10 @@ impl AuthorizationBroker {
         .request_async(self.reqwest_client.as_ref())
         .await
         .map(Into::into)
-        .map_err(Into::into),
+        .map_err(|err| match err {
+          RequestTokenError::Request(_) => anyhow::Error::new(err).context(Unreachable),
+          err => err.into(),
+        }),
       None => Err(anyhow::anyhow!("No refresh token found")),
     }
   }
@@ -1,13 +1,16 @@
-use anyhow::Result;
+use crate::Unreachable;
+use anyhow::{Context, Result};
 use jsonwebtoken::jwk::{AlgorithmParameters, Jwk};
 use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use popcorntime_http::HttpConfig;
 use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
 use uuid::Uuid;
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 struct Jwks {
   keys: Vec<Jwk>,
 }
@@ -40,17 +43,26 @@ pub struct JwksClient {
   issuer: String,
   cache: Arc<Mutex<Option<(Jwks, Instant)>>>,
   ttl: Duration,
+  /// The last keys fetched, used when the issuer is unreachable.
+  cache_file: Option<PathBuf>,
 }
 
 impl JwksClient {
-  pub fn new(issuer: &str) -> Self {
+  pub fn new(issuer: &str, http_config: &HttpConfig) -> Result<Self> {
     tracing::info!("Creating JwksClient for issuer: {}", issuer);
-    Self {
-      client: reqwest::Client::new(),
+    Ok(Self {
+      client: http_config.client_builder()?.build()?,
       issuer: issuer.trim_end_matches('/').to_string(),
       cache: Arc::new(Mutex::new(None)),
       ttl: Duration::from_secs(3600), // 1 hour cache
-    }
+      cache_file: None,
+    })
+  }
+
+  /// Persist the keys in `path`, to validate tokens when the issuer can't be reached.
+  pub fn with_cache_file(mut self, path: &Path) -> Self {
+    self.cache_file = Some(path.to_path_buf());
+    self
   }
 
   async fn refresh_jwks(&self) -> Result<Jwks> {
@@ -58,12 +70,20 @@ impl JwksClient {
       .client
       .get(&self.issuer)
       .send()
-      .await?
+      .await
+      .context(Unreachable)?
       .json::<Jwks>()
       .await?;
 
     let mut cache = self.cache.lock().await;
     *cache = Some((response.clone(), Instant::now()));
+    drop(cache);
+
+    if let Some(path) = &self.cache_file
+      && let Err(err) = write_jwks(path, &response)
+    {
+      tracing::warn!("Failed to persist the JWKS: {:?}", err);
+    }
 
     Ok(response)
   }
@@ -74,11 +94,32 @@ impl JwksClient {
       Some((jwks, time)) if time.elapsed() < self.ttl => Ok(jwks.clone()),
       _ => {
         drop(cache); // Release lock before await
-        self.refresh_jwks().await
+        match self.refresh_jwks().await {
+          Ok(jwks) => Ok(jwks),
+          Err(err) if err.is::<Unreachable>() => match self.last_jwks().await {
+            Some(jwks) => {
+              tracing::warn!("Using the last known JWKS: {:?}", err);
+              Ok(jwks)
+            }
+            None => Err(err),
+          },
+          Err(err) => Err(err),
+        }
       }
     }
   }
 
+  /// The outdated keys in memory, or else the ones persisted by a previous run.
+  async fn last_jwks(&self) -> Option<Jwks> {
+    if let Some((jwks, _)) = &*self.cache.lock().await {
+      return Some(jwks.clone());
+    }
+    let content = std::fs::read(self.cache_file.as_ref()?).ok()?;
+    serde_json::from_slice(&content)
+      .map_err(|err| tracing::warn!("Ignoring the persisted JWKS: {:?}", err))
+      .ok()
+  }
+
   async fn get_jwk(&self, kid: &str) -> Result<Jwk> {
     let jwks = self.get_jwks().await?;
     jwks
@@ -118,3 +159,39 @@ impl JwksClient {
       .map_err(Into::into)
   }
 }
+
+fn write_jwks(path: &Path, jwks: &Jwks) -> Result<()> {
+  let tmp = path.with_extension("tmp");
+  std::fs::write(&tmp, serde_json::to_vec(jwks)?)?;
+  std::fs::rename(tmp, path)?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const JWKS: &str = r#"{"keys":[{"kty":"RSA","kid":"main","alg":"RS256","n":"AQAB","e":"AQAB"}]}"#;
+
+  fn offline_client(cache_file: &Path) -> JwksClient {
+    // nothing listens on the discard port
+    JwksClient::new("http://127.0.0.1:9", &HttpConfig::default())
+      .unwrap()
+      .with_cache_file(cache_file)
+  }
+
+  #[tokio::test]
+  async fn falls_back_to_the_persisted_keys_offline() {
+    let dir = std::env::temp_dir().join(format!("popcorntime-jwks-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("jwks.json");
+
+    let err = offline_client(&path).get_jwks().await.unwrap_err();
+    assert!(err.is::<Unreachable>());
+
+    std::fs::write(&path, JWKS).unwrap();
+    let jwks = offline_client(&path).get_jwks().await.unwrap();
+    assert_eq!(jwks.keys[0].common.key_id.as_deref(), Some("main"));
+    std::fs::remove_dir_all(&dir).ok();
+  }
+}
@@ -2,9 +2,10 @@ use anyhow::{Context, Result};
 use authorization::{AuthorizationBroker, AuthorizationBrokerEvent, AuthorizationBrokerResponse};
 use consts::{AUTH_SERVER, CLIENT_ID};
 use popcorntime_error::Code;
+use popcorntime_http::HttpConfig;
 use session::AppSession;
-use std::{path::Path, sync::Arc};
-use storage::{InnerSessionStore, SessionStore};
+use std::{fmt, path::Path, sync::Arc};
+use storage::{InnerSessionStore, SessionStore, StorageLimits};
 use tokio::sync::RwLock;
 
 pub mod authorization;
@@ -14,6 +15,29 @@ mod server;
 pub mod session;
 pub mod storage;
 
+/// Context of the errors caused by the authorization server being unreachable, as
+/// opposed to it rejecting the session.
+#[derive(Debug, Clone, Copy)]
+pub struct Unreachable;
+
+impl fmt::Display for Unreachable {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("authorization server unreachable")
+  }
+}
+
+/// `config`, or the defaults when it can't be used (unsupported proxy, unreadable
+/// certificates...) so a broken setting doesn't keep the app from starting.
+fn valid_or_default(config: HttpConfig) -> HttpConfig {
+  match config.validate() {
+    Ok(()) => config,
+    Err(err) => {
+      tracing::error!("Invalid http settings, using the defaults: {:?}", err);
+      HttpConfig::default()
+    }
+  }
+}
+
 #[derive(Debug, Clone)]
 pub struct AuthorizationService {
   broker: Arc<AuthorizationBroker>,
@@ -24,10 +48,15 @@ pub struct AuthorizationService {
 impl AuthorizationService {
   pub fn new(storage_dir: &Path) -> Result<Self> {
     let store = SessionStore::new(storage_dir)?;
-    let broker = AuthorizationBroker::new(CLIENT_ID, AUTH_SERVER)?;
-    let mut current_session = AppSession::new(&format!("{}/.well-known/jwks.json", AUTH_SERVER))?;
-
     let current_store = store.get()?;
+    let http_config = valid_or_default(current_store.http.clone());
+    let broker = AuthorizationBroker::new(CLIENT_ID, AUTH_SERVER, &http_config)?;
+    let mut current_session = AppSession::new(
+      &format!("{}/.well-known/jwks.json", AUTH_SERVER),
+      &http_config,
+      &storage_dir.join("jwks.json"),
+    )?;
+
     current_session.with_access_token(current_store.access_token.clone());
     current_session.with_refresh_token(current_store.refresh_token.clone());
     current_session.with_expires_at(current_store.expires_at);
@@ -82,6 +111,22 @@ impl AuthorizationService {
       .await
   }
 
+  /// The HTTP configuration of the settings, shared by the other clients of the app.
+  /// Invalid settings are replaced by the defaults, see [`valid_or_default`].
+  pub fn http_config(&self) -> Result<HttpConfig> {
+    Ok(valid_or_default(self.store.get()?.http))
+  }
+
+  /// The size caps of the caches and logs, re-read on every check as the settings
+  /// file may be edited while the app runs.
+  pub fn storage_limits(&self) -> Result<StorageLimits> {
+    Ok(self.store.get()?.storage_limits)
+  }
+
+  pub fn set_storage_limits(&self, limits: StorageLimits) -> Result<()> {
+    self.store.update_storage_limits(limits)
+  }
+
   pub fn is_onboarded(&self) -> Result<bool> {
     let inner_settings = self.store.clone();
     Ok(
@@ -97,44 +142,80 @@ impl AuthorizationService {
     inner_settings.update_onboarding_complete(onboarded)
   }
 
+  /// Validate the access token, refreshing it when it's expired.
+  ///
+  /// While the authorization server is unreachable the session is kept as is, the
+  /// cached responses stay available and the API rejects the token if it's revoked.
   pub async fn validate(&self) -> Result<()> {
     let mut session = self.snapshot.write().await;
-    let inner_settings = self.store.clone();
 
-    match session.validate().await {
-      Ok(_) => Ok(()),
-      Err(err) => {
-        // probably expired token
-        if err.is::<Code>() {
-          tracing::info!("Refreshing token");
-          let AuthorizationBrokerResponse {
-            access_token,
-            expires_in,
-            refresh_token,
-          } = self
-            .broker
-            .exchange_refresh_token(&session)
-            .await
-            .context(Code::InvalidSession)?;
-
-          // update storage -- a `AppSession` will be updated in the background
-          if let Err(err) = inner_settings.update_access_token(
-            access_token.clone(),
-            Some(refresh_token),
-            expires_in,
-          ) {
-            tracing::error!("Failed to update access_token: {:?}", err);
-          };
-
-          // make sure the access token is updated
-          // we dont want to relay on the watch_in_background to update the session
-          session.with_access_token(Some(access_token));
-
-          return session.validate().await;
+    let result = match session.validate().await {
+      // probably expired token
+      Err(err) if err.is::<Code>() && !err.is::<Unreachable>() => {
+        tracing::info!("Refreshing token");
+        match self.refresh_session(&mut session).await {
+          Ok(()) => session.validate().await,
+          Err(err) => Err(err),
         }
-        Err(err)
       }
+      result => result,
+    };
+    match result {
+      Err(err) if err.is::<Unreachable>() && session.access_token().is_some() => {
+        tracing::warn!("Keeping the session while offline: {:?}", err);
+        Ok(())
+      }
+      result => result,
+    }
+  }
+
+  /// Exchange the refresh token for a new access token, even if the current one
+  /// looks valid locally (e.g. it was revoked on the server).
+  pub async fn refresh(&self) -> Result<()> {
+    let mut session = self.snapshot.write().await;
+    self.refresh_session(&mut session).await
+  }
+
+  async fn refresh_session(&self, session: &mut AppSession) -> Result<()> {
+    let AuthorizationBrokerResponse {
+      access_token,
+      expires_in,
+      refresh_token,
+    } = self
+      .broker
+      .exchange_refresh_token(session)
+      .await
+      .context(Code::InvalidSession)?;
+
+    // update storage -- a `AppSession` will be updated in the background
+    if let Err(err) = self.store.update_access_token(
+      access_token.clone(),
+      Some(refresh_token.clone()),
+      expires_in,
+    ) {
+      tracing::error!("Failed to update access_token: {:?}", err);
+    };
+
+    // make sure the session is updated
+    // we dont want to relay on the watch_in_background to update the session
+    session.with_access_token(Some(access_token));
+    // the refresh token may be rotated, the previous one is no longer valid
+    session.with_refresh_token(Some(refresh_token));
+    if let Some(expires_in) = expires_in {
+      session.with_expires_at(Some(time::OffsetDateTime::now_utc() + expires_in));
     }
+
+    Ok(())
+  }
+
+  /// The access token of the current session, if any.
+  pub async fn access_token(&self) -> Option<String> {
+    self.snapshot.read().await.access_token()
+  }
+
+  /// The user of the current session, if any.
+  pub async fn subject(&self) -> Option<uuid::Uuid> {
+    self.snapshot.read().await.subject()
   }
 
   pub async fn logout(&self) -> Result<()> {
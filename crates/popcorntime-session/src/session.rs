@@ -1,8 +1,11 @@
 use anyhow::{Context, Result};
 use popcorntime_error::Code;
-use std::sync::Arc;
+use popcorntime_http::HttpConfig;
+use std::{path::Path, sync::Arc};
 
-use crate::jwks::JwksClient;
+use crate::jwks::{Claims, JwksClient};
+use jsonwebtoken::{DecodingKey, Validation};
+use uuid::Uuid;
 #[derive(Debug, Clone)]
 pub struct AppSession {
   jwks_client: Arc<JwksClient>,
@@ -12,8 +15,9 @@ pub struct AppSession {
 }
 
 impl AppSession {
-  pub fn new(auth_server: &str) -> Result<Self> {
-    let jwks_client = JwksClient::new(auth_server);
+  /// `jwks_file` keeps the keys of `auth_server` to validate the token offline.
+  pub fn new(auth_server: &str, http_config: &HttpConfig, jwks_file: &Path) -> Result<Self> {
+    let jwks_client = JwksClient::new(auth_server, http_config)?.with_cache_file(jwks_file);
 
     Ok(Self {
       access_token: None,
@@ -27,6 +31,21 @@ impl AppSession {
     self.access_token.clone()
   }
 
+  /// The user of the access token, read without validating it.
+  pub fn subject(&self) -> Option<Uuid> {
+    let mut validation = Validation::default();
+    validation.insecure_disable_signature_validation();
+    validation.validate_exp = false;
+    validation.validate_aud = false;
+    jsonwebtoken::decode::<Claims>(
+      self.access_token.as_deref()?,
+      &DecodingKey::from_secret(&[]),
+      &validation,
+    )
+    .ok()
+    .map(|token| token.claims.sub)
+  }
+
   pub fn refresh_token(&self) -> Option<String> {
     self.refresh_token.clone()
   }
@@ -1,6 +1,7 @@
 use anyhow::Result;
 use config::{Config, File};
 use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher, event::ModifyKind};
+use popcorntime_http::HttpConfig;
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use std::{
   path::{Path, PathBuf},
@@ -32,6 +33,42 @@ pub struct InnerSessionStore {
   pub expires_at: Option<time::OffsetDateTime>,
   #[serde(default)]
   pub refresh_token: Option<String>,
+  /// Proxy and certificates of the HTTP clients, read on start.
+  #[serde(default)]
+  #[serde(skip_serializing_if = "HttpConfig::is_default")]
+  pub http: HttpConfig,
+  /// Size caps of the caches and logs, enforced by the storage janitor.
+  #[serde(default)]
+  #[serde(skip_serializing_if = "StorageLimits::is_default")]
+  pub storage_limits: StorageLimits,
+}
+
+/// The max size of each category of files written by the app, in bytes.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, specta::Type)]
+#[serde(default, rename_all = "camelCase")]
+pub struct StorageLimits {
+  pub api_cache: u64,
+  pub images: u64,
+  pub logs: u64,
+  pub console_recording: u64,
+}
+
+impl Default for StorageLimits {
+  fn default() -> Self {
+    const MIB: u64 = 1024 * 1024;
+    Self {
+      api_cache: 64 * MIB,
+      images: 256 * MIB,
+      logs: 128 * MIB,
+      console_recording: 64 * MIB,
+    }
+  }
+}
+
+impl StorageLimits {
+  pub fn is_default(&self) -> bool {
+    *self == Self::default()
+  }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
@@ -108,6 +145,20 @@ impl SessionStore<InnerSessionStore> {
     Ok(())
   }
 
+  pub fn update_storage_limits(&self, update: StorageLimits) -> Result<()> {
+    match self.snapshot.write() {
+      Ok(mut settings) => {
+        settings.storage_limits = update;
+      }
+      Err(err) => {
+        tracing::error!("Failed to update storage_limits: {:?}", err);
+        return Err(anyhow::anyhow!("Failed to update storage_limits"));
+      }
+    }
+    self.save()?;
+    Ok(())
+  }
+
   pub fn delete_access_token(&self) -> Result<()> {
     match self.snapshot.write() {
       Ok(mut settings) => {
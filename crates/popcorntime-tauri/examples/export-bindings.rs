@@ -0,0 +1,15 @@
+//! Export the TypeScript bindings of the commands, which the tests check are up to date.
+//! An example rather than a binary, so it isn't bundled with the app.
+//!
+//! ```sh
+//! cargo run -p popcorntime-tauri --example export-bindings
+//! ```
+
+use popcorntime_tauri::bindings;
+
+fn main() -> anyhow::Result<()> {
+  let path = bindings::path();
+  std::fs::write(&path, bindings::export()?)?;
+  println!("exported the bindings to {:?}", path);
+  Ok(())
+}
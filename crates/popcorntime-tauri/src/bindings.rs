@@ -0,0 +1,82 @@
+//! The commands of the app and their TypeScript bindings in
+//! `packages/popcorntime-graphql/src/bindings.ts`, exported by the `export-bindings`
+//! example and checked by the tests.
+
+use anyhow::Context;
+use specta_typescript::{BigIntExportBehavior, Typescript};
+use std::path::{Path, PathBuf};
+use tauri_specta::{collect_commands, Builder};
+
+/// The commands invoked by the frontend.
+pub fn builder() -> Builder<tauri::Wry> {
+  Builder::<tauri::Wry>::new().commands(collect_commands![
+    crate::window::show_main_window,
+    crate::operations::search_medias,
+    crate::graphql::search_paginate,
+    crate::graphql::search_next,
+    crate::graphql::search_close,
+    crate::operations::user_preferences,
+    crate::operations::update_user_preferences,
+    crate::operations::media,
+    crate::operations::providers,
+    crate::graphql::home,
+    crate::graphql::diagnostics,
+    crate::images::image_placeholders,
+    crate::storage::storage_usage,
+    crate::storage::clear_storage,
+    crate::storage::set_storage_limits,
+    crate::operations::home_collection,
+    crate::operations::collections,
+    crate::operations::count,
+    crate::session::is_onboarded,
+    crate::session::set_onboarded,
+    crate::session::validate,
+    crate::session::logout,
+    crate::session::initialize_session_authorization,
+    crate::operations::add_favorites_provider,
+    crate::operations::remove_favorites_provider
+  ])
+}
+
+/// Where the bindings are exported, by the `export-bindings` example.
+pub fn path() -> PathBuf {
+  Path::new(env!("CARGO_MANIFEST_DIR")).join("../../packages/popcorntime-graphql/src/bindings.ts")
+}
+
+/// The bindings of the commands, `u64` sizes are safe as numbers.
+///
+/// The globals of tauri-specta are replaced by the ones the commands use, the helpers
+/// of the events are unused as the app declares none and would fail the type checks.
+pub fn export() -> anyhow::Result<String> {
+  let bindings =
+    builder().export_str(Typescript::default().bigint(BigIntExportBehavior::Number))?;
+  let (bindings, _) = bindings
+    .split_once(GLOBALS)
+    .context("no tauri-specta globals in the bindings")?;
+  Ok(format!("{}{}", bindings, COMMAND_GLOBALS))
+}
+
+const GLOBALS: &str = "/** tauri-specta globals **/";
+
+const COMMAND_GLOBALS: &str = r#"/** tauri-specta globals **/
+
+import { invoke as TAURI_INVOKE } from "@tauri-apps/api/core";
+
+export type Result<T, E> =
+	| { status: "ok"; data: T }
+	| { status: "error"; error: E };
+"#;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn bindings_are_up_to_date() {
+    let bindings = export().expect("failed to export typescript bindings");
+    assert!(
+      std::fs::read_to_string(path()).ok().as_deref() == Some(bindings.as_str()),
+      "the bindings are outdated, run `cargo run -p popcorntime-tauri --example export-bindings`"
+    );
+  }
+}
@@ -1,7 +1,35 @@
 use popcorntime_error::AnyhowContextExt;
 use serde::{ser::SerializeMap, Serialize};
+use specta::{datatype::reference::Reference, DataType, Generics, Type, TypeCollection};
 use std::borrow::Cow;
 
+/// The serialized shape of [`Error`] and [`UnmarkedError`], for the TypeScript bindings.
+#[derive(Type)]
+#[allow(dead_code)]
+struct CommandError {
+  /// One of the [`popcorntime_error::Code`] strings, e.g. `errors.offline`.
+  code: String,
+  message: String,
+}
+
+/// Describe `$error` as a [`CommandError`] to specta.
+macro_rules! command_error_type {
+  ($error:ty) => {
+    impl Type for $error {
+      fn inline(types: &mut TypeCollection, generics: Generics) -> DataType {
+        CommandError::inline(types, generics)
+      }
+
+      fn reference(types: &mut TypeCollection, generics: &[DataType]) -> Reference {
+        CommandError::reference(types, generics)
+      }
+    }
+  };
+}
+
+command_error_type!(Error);
+command_error_type!(UnmarkedError);
+
 /// An error type for serialization which isn't expected to carry a code.
 #[derive(Debug)]
 pub struct UnmarkedError(anyhow::Error);
@@ -1,4 +1,6 @@
+use crate::graphql::SubscriptionEvent;
 use anyhow::{Context, Result};
+use popcorntime_graphql_client::outbox::OutboxEvent;
 use popcorntime_session::{authorization::AuthorizationBrokerEvent, storage::InnerSessionStore};
 use serde_json::Value;
 use tauri::Emitter;
@@ -6,6 +8,11 @@ use tauri_plugin_deep_link::OpenUrlEvent;
 
 const EVENT_SESSION_UPDATE: &str = "popcorntime://session_update";
 const EVENT_SESSION_SERVER_READY: &str = "popcorntime://session_server_ready";
+const EVENT_SESSION_INVALID: &str = "popcorntime://session_invalid";
+const EVENT_ONLINE: &str = "popcorntime://online";
+const EVENT_OFFLINE: &str = "popcorntime://offline";
+const EVENT_OUTBOX: &str = "popcorntime://outbox";
+const EVENT_SUBSCRIPTION: &str = "popcorntime://subscription";
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct FrontendEvent {
@@ -14,6 +21,22 @@ pub struct FrontendEvent {
 }
 
 impl FrontendEvent {
+  /// The server rejected the access token and the session could not be refreshed.
+  pub fn session_invalid() -> Self {
+    FrontendEvent {
+      name: EVENT_SESSION_INVALID.to_string(),
+      payload: Value::Null,
+    }
+  }
+
+  /// The API became reachable (`online`) or unreachable.
+  pub fn connectivity(online: bool) -> Self {
+    FrontendEvent {
+      name: if online { EVENT_ONLINE } else { EVENT_OFFLINE }.to_string(),
+      payload: Value::Null,
+    }
+  }
+
   pub fn send(&self, app_handle: &tauri::AppHandle) -> Result<()> {
     app_handle
       .emit(&self.name, Some(&self.payload))
@@ -41,6 +64,24 @@ impl From<AuthorizationBrokerEvent> for FrontendEvent {
   }
 }
 
+impl From<OutboxEvent> for FrontendEvent {
+  fn from(event: OutboxEvent) -> Self {
+    FrontendEvent {
+      name: EVENT_OUTBOX.to_string(),
+      payload: serde_json::json!(event),
+    }
+  }
+}
+
+impl From<SubscriptionEvent> for FrontendEvent {
+  fn from(event: SubscriptionEvent) -> Self {
+    FrontendEvent {
+      name: EVENT_SUBSCRIPTION.to_string(),
+      payload: serde_json::json!(event),
+    }
+  }
+}
+
 impl From<OpenUrlEvent> for FrontendEvent {
   fn from(_event: OpenUrlEvent) -> Self {
     // fixme: better URI parsing
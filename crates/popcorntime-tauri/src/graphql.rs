@@ -1,119 +1,261 @@
 use crate::error::Error;
+use crate::event::FrontendEvent;
+use anyhow::anyhow;
+use futures_util::StreamExt;
 use popcorntime_graphql_client::{
-  add_favorite_provider, client::ApiClient, media, preferences, providers,
-  remove_favorite_provider, search, update_preferences,
+  client::ApiClient,
+  endpoints::EndpointStatus,
+  error::GraphqlResponse,
+  home::Home,
+  home_collection,
+  metrics::{OperationMetrics, OperationSummary},
+  pagination::{PageOptions, SearchPages, MAX_PAGE_SIZE},
+  search,
+  subscription::Subscription,
 };
 use popcorntime_session::AuthorizationService;
-use tauri::State;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+  collections::BTreeMap,
+  sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+  },
+};
+use tauri::{async_runtime::JoinHandle, AppHandle, Manager, State};
+use tokio::sync::Mutex;
 use tracing::instrument;
 
-#[tauri::command(async)]
-#[instrument(skip(api_client, auth_service), err(Debug))]
-pub async fn search_medias(
-  api_client: State<'_, ApiClient>,
-  auth_service: State<'_, AuthorizationService>,
-  params: search::Variables,
-) -> Result<Option<search::SearchSearch>, Error> {
-  auth_service.validate().await?;
+/// Searches paginated on behalf of the frontend, which only holds their handle.
+#[derive(Default)]
+pub struct SearchCursors {
+  next_handle: AtomicU32,
+  cursors: std::sync::Mutex<BTreeMap<u32, Arc<Mutex<SearchCursor>>>>,
+}
 
-  Ok(api_client.search(&params).await?.map(|data| data.search))
+struct SearchCursor {
+  pages: SearchPages,
 }
 
-#[tauri::command(async)]
-#[instrument(skip(api_client, auth_service), err(Debug))]
-pub async fn user_preferences(
-  api_client: State<'_, ApiClient>,
-  auth_service: State<'_, AuthorizationService>,
-) -> Result<Option<preferences::PreferencesPreferences>, Error> {
-  auth_service.validate().await?;
+/// The oldest searches are dropped past this count, in case the frontend never closed them.
+const MAX_SEARCH_CURSORS: usize = 16;
 
-  let result = api_client
-    .preferences(&preferences::Variables {})
-    .await?
-    .and_then(|data| data.preferences);
+impl SearchCursors {
+  fn insert(&self, cursor: SearchCursor) -> u32 {
+    let handle = self.next_handle.fetch_add(1, Ordering::Relaxed);
+    let mut cursors = self.cursors.lock().unwrap_or_else(|err| err.into_inner());
+    cursors.insert(handle, Arc::new(Mutex::new(cursor)));
+    while cursors.len() > MAX_SEARCH_CURSORS {
+      let oldest = *cursors.keys().next().expect("not empty");
+      cursors.remove(&oldest);
+    }
+    handle
+  }
 
-  Ok(result)
+  fn get(&self, handle: u32) -> Option<Arc<Mutex<SearchCursor>>> {
+    let cursors = self.cursors.lock().unwrap_or_else(|err| err.into_inner());
+    cursors.get(&handle).cloned()
+  }
+
+  fn remove(&self, handle: u32) {
+    let mut cursors = self.cursors.lock().unwrap_or_else(|err| err.into_inner());
+    cursors.remove(&handle);
+  }
 }
 
+#[derive(Debug, Serialize, specta::Type)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchPage {
+  nodes: Vec<search::SearchSearchNodes>,
+  has_next_page: bool,
+}
+
+/// Start paginating a search, returning the handle to pass to [`search_next`].
+/// The page size is the `first` (or `last`) variable.
 #[tauri::command(async)]
-#[instrument(skip(api_client, auth_service), err(Debug))]
-pub async fn update_user_preferences(
+#[specta::specta]
+#[instrument(skip(api_client, auth_service, cursors), err(Debug))]
+pub async fn search_paginate(
   api_client: State<'_, ApiClient>,
   auth_service: State<'_, AuthorizationService>,
-  params: update_preferences::Variables,
-) -> Result<Option<update_preferences::UpdatePreferencesUpdatePreferences>, Error> {
+  cursors: State<'_, SearchCursors>,
+  params: search::Variables,
+) -> Result<u32, Error> {
   auth_service.validate().await?;
 
-  let result = api_client
-    .update_preferences(&params)
-    .await?
-    .and_then(|data| data.update_preferences);
+  let options = PageOptions {
+    page_size: params
+      .first
+      .or(params.last)
+      .unwrap_or_else(|| PageOptions::default().page_size)
+      .clamp(1, MAX_PAGE_SIZE),
+    ..Default::default()
+  };
+  let cursor = SearchCursor {
+    pages: api_client.search_pages(params, options),
+  };
 
-  Ok(result)
+  Ok(cursors.insert(cursor))
 }
 
+/// Return the next page of the search started with [`search_paginate`].
 #[tauri::command(async)]
-#[instrument(skip(api_client, auth_service), err(Debug))]
-pub async fn media(
-  api_client: State<'_, ApiClient>,
+#[specta::specta]
+#[instrument(skip(auth_service, cursors), err(Debug))]
+pub async fn search_next(
   auth_service: State<'_, AuthorizationService>,
-  params: media::Variables,
-) -> Result<Option<media::MediaMedia>, Error> {
+  cursors: State<'_, SearchCursors>,
+  handle: u32,
+) -> Result<SearchPage, Error> {
   auth_service.validate().await?;
 
-  Ok(api_client.media(&params).await?.and_then(|data| data.media))
+  let cursor = cursors
+    .get(handle)
+    .ok_or_else(|| anyhow!("Unknown search handle {handle}"))?;
+  let mut cursor = cursor.lock().await;
+  // a failed page is fetched again by the next call
+  let nodes = cursor.pages.next().await.transpose()?.unwrap_or_default();
+
+  Ok(SearchPage {
+    nodes,
+    has_next_page: cursor.pages.has_next_page(),
+  })
+}
+
+/// Release the search started with [`search_paginate`].
+#[tauri::command(async)]
+#[specta::specta]
+#[instrument(skip(cursors))]
+pub async fn search_close(cursors: State<'_, SearchCursors>, handle: u32) -> Result<(), Error> {
+  cursors.remove(handle);
+  Ok(())
 }
 
+/// Everything the home screen shows, sections which failed to load are listed in `errors`.
 #[tauri::command(async)]
+#[specta::specta]
 #[instrument(skip(api_client, auth_service), err(Debug))]
-pub async fn providers(
+pub async fn home(
   api_client: State<'_, ApiClient>,
   auth_service: State<'_, AuthorizationService>,
-  params: providers::Variables,
-) -> Result<Vec<providers::ProvidersProviders>, Error> {
+  params: home_collection::Variables,
+) -> Result<Home, Error> {
   auth_service.validate().await?;
 
-  let result = api_client
-    .providers(&params)
-    .await?
-    .map(|data| data.providers)
-    .unwrap_or_default();
+  Ok(api_client.home(&params).await?)
+}
 
-  Ok(result)
+#[derive(Debug, Serialize, specta::Type)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostics {
+  online: bool,
+  /// The endpoint the requests are sent to, a mirror while the primary one is down.
+  endpoint: String,
+  endpoints: Vec<EndpointStatus>,
+  operations: Vec<OperationSummary>,
+  /// The recent operations with their trace id, oldest first.
+  recent: Vec<OperationMetrics>,
 }
 
+/// The metrics of the recent GraphQL operations, to match slow interactions to the
+/// server traces.
 #[tauri::command(async)]
-#[instrument(skip(api_client, auth_service), err(Debug))]
-pub async fn remove_favorites_provider(
-  api_client: State<'_, ApiClient>,
-  auth_service: State<'_, AuthorizationService>,
-  params: remove_favorite_provider::Variables,
-) -> Result<bool, Error> {
-  auth_service.validate().await?;
+#[specta::specta]
+#[instrument(skip(api_client), err(Debug))]
+pub async fn diagnostics(api_client: State<'_, ApiClient>) -> Result<Diagnostics, Error> {
+  let metrics = api_client.metrics();
+  Ok(Diagnostics {
+    online: api_client.connectivity().is_online(),
+    endpoint: api_client.endpoints().current(),
+    endpoints: api_client.endpoints().status(),
+    operations: metrics.summary(),
+    recent: metrics.recent(),
+  })
+}
 
-  let result = api_client
-    .remove_favorite_provider(&params)
-    .await?
-    .map(|data| data.remove_favorite_provider)
-    .unwrap_or_default();
+/// Subscriptions forwarded to the frontend as `popcorntime://subscription` events,
+/// the frontend only holds their handle.
+///
+/// The API has no subscription yet: the state, and the [`unsubscribe`] command, are
+/// registered along with the command starting the first one.
+#[derive(Default)]
+pub struct Subscriptions {
+  next_handle: AtomicU32,
+  tasks: std::sync::Mutex<BTreeMap<u32, JoinHandle<()>>>,
+}
 
-  Ok(result)
+/// An event of a subscription started with [`Subscriptions::bridge`].
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum SubscriptionEvent {
+  Next {
+    handle: u32,
+    response: GraphqlResponse<serde_json::Value>,
+  },
+  /// The subscription failed and ends.
+  Error { handle: u32, error: Error },
+  /// The server completed the subscription.
+  Complete { handle: u32 },
 }
 
-#[tauri::command(async)]
-#[instrument(skip(api_client, auth_service), err(Debug))]
-pub async fn add_favorites_provider(
-  api_client: State<'_, ApiClient>,
-  auth_service: State<'_, AuthorizationService>,
-  params: add_favorite_provider::Variables,
-) -> Result<bool, Error> {
-  auth_service.validate().await?;
+impl Subscriptions {
+  /// Emit the events of `subscription` until it completes, returning the handle to
+  /// pass to [`unsubscribe`].
+  pub fn bridge<T>(&self, app_handle: &AppHandle, mut subscription: Subscription<T>) -> u32
+  where
+    T: DeserializeOwned + Serialize + Send + 'static,
+  {
+    let handle = self.next_handle.fetch_add(1, Ordering::Relaxed);
+    // the task removes itself once done, which waits for it to be inserted
+    let mut tasks = self.tasks.lock().unwrap_or_else(|err| err.into_inner());
+    let app_handle = app_handle.clone();
+    let task = tauri::async_runtime::spawn(async move {
+      while let Some(event) = subscription.next().await {
+        let event = match event.and_then(|response| Ok(response.try_map(serde_json::to_value)?)) {
+          Ok(response) => SubscriptionEvent::Next { handle, response },
+          Err(err) => SubscriptionEvent::Error {
+            handle,
+            error: err.into(),
+          },
+        };
+        if let Err(err) = FrontendEvent::from(event).send(&app_handle) {
+          tracing::error!("Failed to send subscription event: {:?}", err);
+        }
+      }
+      FrontendEvent::from(SubscriptionEvent::Complete { handle })
+        .send(&app_handle)
+        .ok();
 
-  let result = api_client
-    .add_favorite_provider(&params)
-    .await?
-    .map(|data| data.add_favorite_provider)
-    .unwrap_or_default();
+      let subscriptions = app_handle.state::<Subscriptions>();
+      let mut tasks = subscriptions
+        .tasks
+        .lock()
+        .unwrap_or_else(|err| err.into_inner());
+      tasks.remove(&handle);
+    });
+    tasks.insert(handle, task);
+    handle
+  }
+}
 
-  Ok(result)
+/// Stop the subscription, completing it on the server.
+#[tauri::command(async)]
+#[specta::specta]
+#[instrument(skip(subscriptions))]
+pub async fn unsubscribe(
+  subscriptions: State<'_, Subscriptions>,
+  handle: u32,
+) -> Result<(), Error> {
+  let task = {
+    let mut tasks = subscriptions
+      .tasks
+      .lock()
+      .unwrap_or_else(|err| err.into_inner());
+    tasks.remove(&handle)
+  };
+  // dropping the subscription sends `complete`
+  if let Some(task) = task {
+    task.abort();
+  }
+  Ok(())
 }
@@ -0,0 +1,76 @@
+use crate::error::Error;
+use popcorntime_images::{ImageCache, ImageRequest, Placeholder};
+use std::collections::HashMap;
+use tauri::{
+  http::{header, Request, Response, StatusCode},
+  Manager, Runtime, State, UriSchemeContext, UriSchemeResponder,
+};
+use tracing::instrument;
+
+/// The placeholders of the images among `paths` (e.g. `o/ID.jpg`), keyed by path. The
+/// images not cached yet are fetched in their smallest size, those which can't be
+/// (e.g. offline) have none.
+#[tauri::command(async)]
+#[specta::specta]
+#[instrument(skip(cache, paths), err(Debug))]
+pub async fn image_placeholders(
+  cache: State<'_, ImageCache>,
+  paths: Vec<String>,
+) -> Result<HashMap<String, Placeholder>, Error> {
+  let cache = cache.inner();
+  let placeholders = paths.into_iter().map(|path| async move {
+    let request = ImageRequest::parse(&path, None).ok()?;
+    Some((path, cache.placeholder(&request).await?))
+  });
+  Ok(
+    futures_util::future::join_all(placeholders)
+      .await
+      .into_iter()
+      .flatten()
+      .collect(),
+  )
+}
+
+/// Serve the `ptimg://` requests of the webview from the [`ImageCache`].
+pub fn protocol<R: Runtime>(
+  ctx: UriSchemeContext<'_, R>,
+  request: Request<Vec<u8>>,
+  responder: UriSchemeResponder,
+) {
+  let image_request = match ImageRequest::parse(request.uri().path(), request.uri().query()) {
+    Ok(image_request) => image_request,
+    Err(err) => {
+      tracing::debug!("Invalid image request: {:?}", err);
+      return responder.respond(empty(StatusCode::BAD_REQUEST));
+    }
+  };
+  // the protocol may be used before the setup managed the cache
+  let Some(cache) = ctx
+    .app_handle()
+    .try_state::<ImageCache>()
+    .map(|cache| cache.inner().clone())
+  else {
+    return responder.respond(empty(StatusCode::SERVICE_UNAVAILABLE));
+  };
+
+  tauri::async_runtime::spawn(async move {
+    let response = match cache.get(&image_request).await {
+      Ok(image) => Response::builder()
+        .header(header::CONTENT_TYPE, image.content_type)
+        .header(header::CACHE_CONTROL, "max-age=31536000, immutable")
+        .body(image.bytes)
+        .unwrap_or_else(|_| empty(StatusCode::INTERNAL_SERVER_ERROR)),
+      Err(err) => {
+        tracing::warn!("Failed to load image: {:?}", err);
+        empty(StatusCode::NOT_FOUND)
+      }
+    };
+    responder.respond(response);
+  });
+}
+
+fn empty(status: StatusCode) -> Response<Vec<u8>> {
+  let mut response = Response::new(Vec::new());
+  *response.status_mut() = status;
+  response
+}
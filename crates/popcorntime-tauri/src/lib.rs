@@ -1,7 +1,11 @@
+pub mod bindings;
 pub mod capabilities;
 pub mod error;
 pub mod event;
 pub mod graphql;
+pub mod images;
 pub mod logs;
+pub mod operations;
 pub mod session;
+pub mod storage;
 pub mod window;
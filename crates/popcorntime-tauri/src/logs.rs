@@ -1,11 +1,15 @@
 // Taken from: https://github.com/gitbutlerapp/gitbutler
 
+use popcorntime_graphql_client::trace::TraceContextLayer;
 use std::{fs, net::Ipv4Addr, path::Path, time::Duration};
 use tauri::{AppHandle, Manager};
 use tracing::{instrument, metadata::LevelFilter, subscriber::set_global_default};
 use tracing_appender::rolling::{RollingFileAppender, Rotation};
 use tracing_subscriber::{fmt::format::FmtSpan, layer::SubscriberExt, Layer};
 
+/// File of the https://github.com/tokio-rs/console recording, in the logs dir.
+pub const CONSOLE_RECORDING: &str = "PopcornTime.console.log";
+
 pub fn init(app_handle: &AppHandle) {
   let logs_dir = app_handle
     .path()
@@ -44,13 +48,15 @@ pub fn init(app_handle: &AppHandle) {
     .unwrap_or(LevelFilter::INFO);
 
   let subscriber = tracing_subscriber::registry()
+    // trace ids sent to the API in the `traceparent` header
+    .with(TraceContextLayer)
     .with(
       // subscriber for https://github.com/tokio-rs/console
       console_subscriber::ConsoleLayer::builder()
         .server_addr((Ipv4Addr::LOCALHOST, 6669))
         .retention(Duration::from_secs(3600)) // 1h
         .publish_interval(Duration::from_secs(1))
-        .recording_path(logs_dir.join("PopcornTime.console.log"))
+        .recording_path(logs_dir.join(CONSOLE_RECORDING))
         .spawn(),
     )
     .with(
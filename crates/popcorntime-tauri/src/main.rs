@@ -1,12 +1,19 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
-use popcorntime_graphql_client::client::ApiClient;
+use popcorntime_graphql_client::{
+  cache::ResponseCache, client::ApiClient, credentials::SessionCredentials, outbox::Outbox,
+  persisted::PersistedQueries,
+};
+use popcorntime_images::ImageCache;
 use popcorntime_session::AuthorizationService;
-use popcorntime_tauri::event::FrontendEvent;
+use popcorntime_tauri::{event::FrontendEvent, storage::Storage};
+use std::time::Duration;
 use tauri::Manager;
 use tauri_plugin_deep_link::DeepLinkExt;
 use tauri_plugin_log::{Target, TargetKind};
 
 fn main() {
+  let commands = popcorntime_tauri::bindings::builder();
+
   tokio::runtime::Builder::new_multi_thread()
     .enable_all()
     .build()
@@ -34,12 +41,13 @@ fn main() {
           popcorntime_tauri::capabilities::setup(app_handle)?;
 
           // intialize directories
-          let (app_data_dir, app_cache_dir, config_dir) = {
+          let (app_data_dir, app_cache_dir, config_dir, logs_dir) = {
             let paths = app_handle.path();
             (
               paths.app_data_dir().expect("missing app data dir"),
               paths.app_cache_dir().expect("missing app cache dir"),
               paths.config_dir().expect("missing config dir"),
+              paths.app_log_dir().expect("missing logs dir"),
             )
           };
 
@@ -52,23 +60,54 @@ fn main() {
                                    name = %app_handle.package_info().name, "starting app");
 
           let auth_service = AuthorizationService::new(&config_dir)?;
+          // the defaults when the proxy or certificate settings are invalid
+          let http_config = auth_service.http_config()?;
+
+          // posters and backdrops, served to the webview by the `ptimg://` protocol
+          let image_cache = ImageCache::new(&app_cache_dir.join("images"), &http_config)?
+            .with_max_size(auth_service.storage_limits()?.images);
+          app_handle.manage(image_cache.clone());
 
-          // initialize default API client
-          app_handle.manage(ApiClient::new(None)?);
+          // initialize API client, the access token is read from the session on every request
+          let response_cache = ResponseCache::new(&app_cache_dir.join("graphql"))?;
+          let api_client = ApiClient::new(SessionCredentials::new(auth_service.clone()))?
+            .with_http_config(&http_config)?
+            // the queries declared with `get` can be cached by the CDN
+            .with_persisted_queries(PersistedQueries::Get)
+            .with_cache(response_cache.clone())
+            .with_outbox(
+              Outbox::new(&app_data_dir.join("outbox.json"))?.with_on_event({
+                let app_handle = app_handle.clone();
+                move |event| FrontendEvent::from(event).send(&app_handle)
+              }),
+            )
+            .with_on_session_invalid({
+              let app_handle = app_handle.clone();
+              move || FrontendEvent::session_invalid().send(&app_handle)
+            });
+          // probe the API every 15 seconds while it's unreachable
+          api_client.watch_connectivity(Duration::from_secs(15), {
+            let app_handle = app_handle.clone();
+            move |online| FrontendEvent::connectivity(online).send(&app_handle)
+          });
+          // send the mutations queued while offline
+          api_client.replay_outbox_in_background();
+          app_handle.manage(api_client);
+          app_handle.manage(popcorntime_tauri::graphql::SearchCursors::default());
+          app_handle.manage(popcorntime_graphql_client::command::CancellableRequests::default());
+
+          // keep the caches and logs under their limits, which may change while the app runs
+          let storage = Storage::new(response_cache, image_cache, &logs_dir);
+          storage.run_janitor_in_background(Duration::from_secs(10 * 60), {
+            let auth_service = auth_service.clone();
+            move || auth_service.storage_limits().unwrap_or_default()
+          });
+          app_handle.manage(storage);
 
           // watch config in background
           auth_service.watch_config_in_background({
             let app_handle = app_handle.clone();
             move |app_settings| {
-              let api_client = app_handle.state::<ApiClient>();
-              match api_client.update_access_token(app_settings.access_token.clone()) {
-                Ok(_) => {
-                  tracing::debug!("[ApiClient] Access token updated");
-                }
-                Err(err) => {
-                  tracing::error!("[ApiClient] Failed to update access token: {:?}", err);
-                }
-              }
               // send frontend event
               popcorntime_tauri::event::FrontendEvent::from(app_settings).send(&app_handle)
             }
@@ -83,6 +122,10 @@ fn main() {
 
           Ok(())
         })
+        .register_asynchronous_uri_scheme_protocol(
+          popcorntime_images::PROTOCOL,
+          popcorntime_tauri::images::protocol,
+        )
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_process::init())
         .plugin(tauri_plugin_updater::Builder::new().build())
@@ -97,22 +140,7 @@ fn main() {
           }
         }))
         .plugin(log_plugin)
-        .invoke_handler(tauri::generate_handler![
-          popcorntime_tauri::window::show_main_window,
-          popcorntime_tauri::graphql::search_medias,
-          popcorntime_tauri::graphql::user_preferences,
-          popcorntime_tauri::graphql::update_user_preferences,
-          popcorntime_tauri::graphql::media,
-          popcorntime_tauri::graphql::providers,
-          popcorntime_tauri::session::is_onboarded,
-          popcorntime_tauri::session::set_onboarded,
-          popcorntime_tauri::session::validate,
-          popcorntime_tauri::session::validate,
-          popcorntime_tauri::session::logout,
-          popcorntime_tauri::session::initialize_session_authorization,
-          popcorntime_tauri::graphql::add_favorites_provider,
-          popcorntime_tauri::graphql::remove_favorites_provider
-        ])
+        .invoke_handler(commands.invoke_handler())
         .build(tauri::generate_context!())
         .expect("valid app")
         .run(|_app_handle, event| {
@@ -0,0 +1,57 @@
+//! The Tauri commands calling a GraphQL operation of the client, one per operation.
+
+use crate::error::Error;
+use popcorntime_graphql_client::{
+  add_favorite_provider, client::ApiClient, collections, command, count, home_collection, media,
+  preferences, providers, remove_favorite_provider, search, update_preferences,
+};
+use popcorntime_graphql_macros::graphql_command;
+
+graphql_command!(Search(
+  name = search_medias,
+  unwrap = search,
+  returns = Option<search::SearchSearch>,
+  cancellable
+));
+graphql_command!(Preferences(
+  name = user_preferences,
+  unwrap = preferences,
+  returns = Option<preferences::PreferencesPreferences>,
+  params = none
+));
+graphql_command!(UpdatePreferences(
+  name = update_user_preferences,
+  unwrap = update_preferences,
+  returns = Option<update_preferences::UpdatePreferencesUpdatePreferences>
+));
+graphql_command!(Media(
+  name = media,
+  unwrap = media,
+  returns = Option<media::MediaMedia>
+));
+graphql_command!(Providers(
+  name = providers,
+  unwrap = providers,
+  returns = Vec<providers::ProvidersProviders>
+));
+graphql_command!(AddFavoriteProvider(
+  name = add_favorites_provider,
+  unwrap = add_favorite_provider,
+  returns = bool
+));
+graphql_command!(RemoveFavoriteProvider(
+  name = remove_favorites_provider,
+  unwrap = remove_favorite_provider,
+  returns = bool
+));
+graphql_command!(HomeCollection(
+  name = home_collection,
+  unwrap = home_collection,
+  returns = Option<home_collection::HomeCollectionHomeCollection>
+));
+graphql_command!(Collections(
+  name = collections,
+  unwrap = collections,
+  returns = Vec<collections::CollectionsCollections>
+));
+graphql_command!(Count(name = count, unwrap = count, returns = i64));
@@ -1,9 +1,11 @@
 use crate::{error::Error, event::FrontendEvent};
+use popcorntime_graphql_client::client::ApiClient;
 use popcorntime_session::AuthorizationService;
 use tauri::State;
 use tracing::instrument;
 
 #[tauri::command(async)]
+#[specta::specta]
 #[instrument(skip(handle, service), err(Debug))]
 pub async fn initialize_session_authorization(
   handle: tauri::AppHandle,
@@ -17,25 +19,41 @@ pub async fn initialize_session_authorization(
 }
 
 #[tauri::command(async)]
+#[specta::specta]
 #[instrument(skip(service), err(Debug))]
 pub async fn validate(service: State<'_, AuthorizationService>) -> Result<(), Error> {
   service.validate().await.map_err(Into::into)
 }
 
 #[tauri::command(async)]
+#[specta::specta]
 #[instrument(skip(service), err(Debug))]
 pub async fn is_onboarded(service: State<'_, AuthorizationService>) -> Result<bool, Error> {
   service.is_onboarded().map_err(Into::into)
 }
 
 #[tauri::command(async)]
+#[specta::specta]
 #[instrument(skip(service), err(Debug))]
 pub async fn set_onboarded(service: State<'_, AuthorizationService>) -> Result<(), Error> {
   service.set_onboarded(true).map_err(Into::into)
 }
 
 #[tauri::command(async)]
-#[instrument(skip(service), err(Debug))]
-pub async fn logout(service: State<'_, AuthorizationService>) -> Result<(), Error> {
-  service.logout().await.map_err(Into::into)
+#[specta::specta]
+#[instrument(skip(service, api_client), err(Debug))]
+pub async fn logout(
+  service: State<'_, AuthorizationService>,
+  api_client: State<'_, ApiClient>,
+) -> Result<(), Error> {
+  service.logout().await?;
+  // the next user must not see the preferences and favorites of this one, nor
+  // replay their mutations
+  if let Some(cache) = api_client.cache() {
+    cache.clear().await?;
+  }
+  if let Some(outbox) = api_client.outbox() {
+    outbox.clear().await?;
+  }
+  Ok(())
 }
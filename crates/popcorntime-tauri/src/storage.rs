@@ -0,0 +1,358 @@
+use crate::{error::Error, logs::CONSOLE_RECORDING};
+use popcorntime_graphql_client::cache::ResponseCache;
+use popcorntime_images::ImageCache;
+use popcorntime_session::{storage::StorageLimits, AuthorizationService};
+use serde::{Deserialize, Serialize};
+use std::{
+  path::{Path, PathBuf},
+  time::{Duration, SystemTime},
+};
+use tauri::State;
+use tracing::instrument;
+
+/// Log files modified more recently may still be open, they are truncated instead of
+/// removed.
+const LOG_IN_USE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A category of the files written by the app.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, specta::Type)]
+#[serde(rename_all = "camelCase")]
+pub enum StorageCategory {
+  /// GraphQL responses, read while offline.
+  ApiCache,
+  /// Posters and backdrops served through `ptimg://`.
+  Images,
+  Logs,
+  /// The https://github.com/tokio-rs/console recording.
+  ConsoleRecording,
+}
+
+impl StorageCategory {
+  const ALL: [Self; 4] = [
+    Self::ApiCache,
+    Self::Images,
+    Self::Logs,
+    Self::ConsoleRecording,
+  ];
+
+  fn limit(self, limits: &StorageLimits) -> u64 {
+    match self {
+      Self::ApiCache => limits.api_cache,
+      Self::Images => limits.images,
+      Self::Logs => limits.logs,
+      Self::ConsoleRecording => limits.console_recording,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize, specta::Type)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageUsage {
+  pub category: StorageCategory,
+  pub path: PathBuf,
+  /// In bytes.
+  pub size: u64,
+  /// The size enforced by the janitor, in bytes.
+  pub limit: u64,
+}
+
+/// The caches and logs of the app, kept under their [`StorageLimits`] by
+/// [`Storage::run_janitor_in_background`].
+#[derive(Debug, Clone)]
+pub struct Storage {
+  api_cache: ResponseCache,
+  images: ImageCache,
+  logs_dir: PathBuf,
+}
+
+struct LogFile {
+  path: PathBuf,
+  size: u64,
+  modified: SystemTime,
+}
+
+impl Storage {
+  pub fn new(api_cache: ResponseCache, images: ImageCache, logs_dir: &Path) -> Self {
+    Self {
+      api_cache,
+      images,
+      logs_dir: logs_dir.to_path_buf(),
+    }
+  }
+
+  pub async fn usage(&self, limits: &StorageLimits) -> anyhow::Result<Vec<StorageUsage>> {
+    let mut usage = Vec::new();
+    for category in StorageCategory::ALL {
+      usage.push(StorageUsage {
+        category,
+        path: self.path(category),
+        size: self.size(category).await?,
+        limit: category.limit(limits),
+      });
+    }
+    Ok(usage)
+  }
+
+  pub async fn clear(&self, category: StorageCategory) -> anyhow::Result<()> {
+    match category {
+      StorageCategory::ApiCache => self.api_cache.clear().await,
+      StorageCategory::Images => self.images.clear().await,
+      StorageCategory::Logs => self.trim_logs(0).await,
+      StorageCategory::ConsoleRecording => truncate(&self.path(category)).await,
+    }
+  }
+
+  /// Remove the oldest files of the categories larger than their limit.
+  pub async fn enforce(&self, limits: &StorageLimits) -> anyhow::Result<()> {
+    self.api_cache.trim(limits.api_cache).await?;
+    if self.images.max_size() != limits.images {
+      self.images.set_max_size(limits.images).await;
+    }
+    self.trim_logs(limits.logs).await?;
+    if self.size(StorageCategory::ConsoleRecording).await? > limits.console_recording {
+      tracing::info!("console recording exceeds its limit, truncating it");
+      self.clear(StorageCategory::ConsoleRecording).await?;
+    }
+    Ok(())
+  }
+
+  /// Enforce the limits returned by `limits` every `interval`, starting now.
+  pub fn run_janitor_in_background(
+    &self,
+    interval: Duration,
+    limits: impl Fn() -> StorageLimits + Send + 'static,
+  ) {
+    let storage = self.clone();
+    tauri::async_runtime::spawn(async move {
+      let mut interval = tokio::time::interval(interval);
+      loop {
+        interval.tick().await;
+        if let Err(err) = storage.enforce(&limits()).await {
+          tracing::warn!("Failed to enforce storage limits: {:?}", err);
+        }
+      }
+    });
+  }
+
+  fn path(&self, category: StorageCategory) -> PathBuf {
+    match category {
+      StorageCategory::ApiCache => self.api_cache.dir().to_path_buf(),
+      StorageCategory::Images => self.images.dir().to_path_buf(),
+      StorageCategory::Logs => self.logs_dir.clone(),
+      StorageCategory::ConsoleRecording => self.logs_dir.join(CONSOLE_RECORDING),
+    }
+  }
+
+  async fn size(&self, category: StorageCategory) -> anyhow::Result<u64> {
+    match category {
+      StorageCategory::ApiCache => self.api_cache.size().await,
+      StorageCategory::Images => Ok(self.images.size()),
+      StorageCategory::Logs => Ok(self.log_files().await?.iter().map(|file| file.size).sum()),
+      StorageCategory::ConsoleRecording => Ok(
+        tokio::fs::metadata(self.path(category))
+          .await
+          .map(|metadata| allocated_size(&metadata))
+          .unwrap_or_default(),
+      ),
+    }
+  }
+
+  /// Remove the oldest log files until they take at most `max_size` bytes.
+  async fn trim_logs(&self, max_size: u64) -> anyhow::Result<()> {
+    let mut files = self.log_files().await?;
+    let mut size: u64 = files.iter().map(|file| file.size).sum();
+    files.sort_unstable_by_key(|file| file.modified);
+    for file in files {
+      if size <= max_size {
+        break;
+      }
+      let in_use = file
+        .modified
+        .elapsed()
+        .map_or(true, |elapsed| elapsed < LOG_IN_USE);
+      if in_use {
+        truncate(&file.path).await?;
+      } else {
+        tokio::fs::remove_file(&file.path).await?;
+      }
+      size -= file.size;
+    }
+    Ok(())
+  }
+
+  async fn log_files(&self) -> anyhow::Result<Vec<LogFile>> {
+    let mut entries = tokio::fs::read_dir(&self.logs_dir).await?;
+    let mut files = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+      let metadata = entry.metadata().await?;
+      if !metadata.is_file() || entry.file_name() == CONSOLE_RECORDING {
+        continue;
+      }
+      files.push(LogFile {
+        path: entry.path(),
+        size: metadata.len(),
+        modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+      });
+    }
+    Ok(files)
+  }
+}
+
+/// The disk space taken by a file. The console recorder keeps its offset when the
+/// recording is truncated, the file is sparse afterwards so its length only grows.
+fn allocated_size(metadata: &std::fs::Metadata) -> u64 {
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::MetadataExt;
+    metadata.blocks() * 512
+  }
+  #[cfg(not(unix))]
+  {
+    metadata.len()
+  }
+}
+
+/// Empty a file which may still be written to. The log writers append so they continue
+/// from the start, the console recorder keeps its offset so the recording stays sparse
+/// until it's recreated on the next start.
+async fn truncate(path: &Path) -> anyhow::Result<()> {
+  match tokio::fs::OpenOptions::new().write(true).open(path).await {
+    Ok(file) => file.set_len(0).await.map_err(Into::into),
+    Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+    Err(err) => Err(err.into()),
+  }
+}
+
+/// The disk usage and limit of each [`StorageCategory`].
+#[tauri::command(async)]
+#[specta::specta]
+#[instrument(skip(storage, service), err(Debug))]
+pub async fn storage_usage(
+  storage: State<'_, Storage>,
+  service: State<'_, AuthorizationService>,
+) -> Result<Vec<StorageUsage>, Error> {
+  Ok(storage.usage(&service.storage_limits()?).await?)
+}
+
+#[tauri::command(async)]
+#[specta::specta]
+#[instrument(skip(storage), err(Debug))]
+pub async fn clear_storage(
+  storage: State<'_, Storage>,
+  category: StorageCategory,
+) -> Result<(), Error> {
+  storage.clear(category).await.map_err(Into::into)
+}
+
+/// Save the limits in the settings and enforce them right away.
+#[tauri::command(async)]
+#[specta::specta]
+#[instrument(skip(storage, service), err(Debug))]
+pub async fn set_storage_limits(
+  storage: State<'_, Storage>,
+  service: State<'_, AuthorizationService>,
+  limits: StorageLimits,
+) -> Result<(), Error> {
+  service.set_storage_limits(limits)?;
+  storage.enforce(&limits).await.map_err(Into::into)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use popcorntime_http::HttpConfig;
+  use tauri::async_runtime::block_on;
+
+  const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+  fn storage(name: &str) -> (Storage, PathBuf) {
+    let dir = std::env::temp_dir().join(format!(
+      "popcorntime-storage-{}-{}",
+      name,
+      std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    let logs_dir = dir.join("logs");
+    std::fs::create_dir_all(&logs_dir).unwrap();
+    let storage = Storage::new(
+      ResponseCache::new(&dir.join("graphql")).unwrap(),
+      ImageCache::new(&dir.join("images"), &HttpConfig::default()).unwrap(),
+      &logs_dir,
+    );
+    (storage, dir)
+  }
+
+  fn write_file(path: &Path, size: usize, age: Duration) {
+    std::fs::write(path, vec![b'x'; size]).unwrap();
+    std::fs::File::options()
+      .write(true)
+      .open(path)
+      .and_then(|file| file.set_modified(SystemTime::now() - age))
+      .unwrap();
+  }
+
+  fn len(path: &Path) -> u64 {
+    std::fs::metadata(path).unwrap().len()
+  }
+
+  #[test]
+  fn trim_logs_removes_old_logs_and_truncates_recent_ones() {
+    let (storage, dir) = storage("trim");
+    let logs_dir = dir.join("logs");
+    let oldest = logs_dir.join("PopcornTime.log.2025-01-01");
+    let old = logs_dir.join("PopcornTime.log.2025-01-02");
+    let current = logs_dir.join("PopcornTime.log");
+    let recording = logs_dir.join(CONSOLE_RECORDING);
+    write_file(&oldest, 100, 3 * DAY);
+    write_file(&old, 100, 2 * DAY);
+    write_file(&current, 100, Duration::ZERO);
+    write_file(&recording, 100, 3 * DAY);
+
+    // removing the oldest log is enough
+    block_on(storage.trim_logs(200)).unwrap();
+    assert!(!oldest.exists());
+    assert_eq!(len(&old), 100);
+    assert_eq!(len(&current), 100);
+
+    // the recent log may still be open
+    block_on(storage.trim_logs(0)).unwrap();
+    assert!(!old.exists());
+    assert_eq!(len(&current), 0);
+    // enforced separately
+    assert_eq!(len(&recording), 100);
+    let _ = std::fs::remove_dir_all(&dir);
+  }
+
+  #[test]
+  fn clearing_the_logs_leaves_an_empty_dir() {
+    let (storage, dir) = storage("clear");
+    let logs_dir = dir.join("logs");
+    write_file(&logs_dir.join("PopcornTime.log.2025-01-01"), 100, 3 * DAY);
+    write_file(&logs_dir.join("PopcornTime.log.2025-01-02"), 100, 2 * DAY);
+
+    block_on(storage.clear(StorageCategory::Logs)).unwrap();
+    assert_eq!(std::fs::read_dir(&logs_dir).unwrap().count(), 0);
+    let _ = std::fs::remove_dir_all(&dir);
+  }
+
+  #[test]
+  fn enforce_truncates_the_console_recording() {
+    let (storage, dir) = storage("recording");
+    let recording = dir.join("logs").join(CONSOLE_RECORDING);
+    write_file(&recording, 64 * 1024, Duration::ZERO);
+    let limits = StorageLimits {
+      console_recording: 1024,
+      ..StorageLimits::default()
+    };
+
+    block_on(storage.enforce(&limits)).unwrap();
+    assert_eq!(len(&recording), 0);
+    let usage = block_on(storage.usage(&limits)).unwrap();
+    let recording_usage = usage
+      .iter()
+      .find(|usage| usage.category == StorageCategory::ConsoleRecording)
+      .unwrap();
+    assert_eq!((recording_usage.size, recording_usage.limit), (0, 1024));
+    let _ = std::fs::remove_dir_all(&dir);
+  }
+}
@@ -0,0 +1,453 @@
+
+// This file was generated by [tauri-specta](https://github.com/oscartbeaumont/tauri-specta). Do not edit this file manually.
+
+/** user-defined commands **/
+
+
+export const commands = {
+async showMainWindow() : Promise<void> {
+    await TAURI_INVOKE("show_main_window");
+},
+async searchMedias(requestKey: string | null, params: SearchVariables) : Promise<Result<Fetched<SearchSearch | null>, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("search_medias", { requestKey, params }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Start paginating a search, returning the handle to pass to [`search_next`].
+ * The page size is the `first` (or `last`) variable.
+ */
+async searchPaginate(params: SearchVariables) : Promise<Result<number, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("search_paginate", { params }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Return the next page of the search started with [`search_paginate`].
+ */
+async searchNext(handle: number) : Promise<Result<SearchPage, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("search_next", { handle }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Release the search started with [`search_paginate`].
+ */
+async searchClose(handle: number) : Promise<Result<null, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("search_close", { handle }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async userPreferences() : Promise<Result<Fetched<PreferencesPreferences | null>, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("user_preferences") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async updateUserPreferences(params: UpdatePreferencesVariables) : Promise<Result<Fetched<UpdatePreferencesUpdatePreferences | null>, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("update_user_preferences", { params }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async media(params: MediaVariables) : Promise<Result<Fetched<MediaMedia | null>, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("media", { params }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async providers(params: ProvidersVariables) : Promise<Result<Fetched<ProvidersProviders[]>, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("providers", { params }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Everything the home screen shows, sections which failed to load are listed in `errors`.
+ */
+async home(params: HomeCollectionVariables) : Promise<Result<Home, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("home", { params }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * The metrics of the recent GraphQL operations, to match slow interactions to the
+ * server traces.
+ */
+async diagnostics() : Promise<Result<Diagnostics, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("diagnostics") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * The placeholders of the images among `paths` (e.g. `o/ID.jpg`), keyed by path. The
+ * images not cached yet are fetched in their smallest size, those which can't be
+ * (e.g. offline) have none.
+ */
+async imagePlaceholders(paths: string[]) : Promise<Result<Partial<{ [key in string]: Placeholder }>, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("image_placeholders", { paths }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * The disk usage and limit of each [`StorageCategory`].
+ */
+async storageUsage() : Promise<Result<StorageUsage[], CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("storage_usage") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async clearStorage(category: StorageCategory) : Promise<Result<null, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("clear_storage", { category }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Save the limits in the settings and enforce them right away.
+ */
+async setStorageLimits(limits: StorageLimits) : Promise<Result<null, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("set_storage_limits", { limits }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async homeCollection(params: HomeCollectionVariables) : Promise<Result<Fetched<HomeCollectionHomeCollection | null>, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("home_collection", { params }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async collections(params: CollectionsVariables) : Promise<Result<Fetched<CollectionsCollections[]>, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("collections", { params }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async count(params: CountVariables) : Promise<Result<Fetched<number>, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("count", { params }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async isOnboarded() : Promise<Result<boolean, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("is_onboarded") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async setOnboarded() : Promise<Result<null, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("set_onboarded") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async validate() : Promise<Result<null, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("validate") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async logout() : Promise<Result<null, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("logout") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async initializeSessionAuthorization() : Promise<Result<null, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("initialize_session_authorization") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async addFavoritesProvider(params: AddFavoriteProviderVariables) : Promise<Result<Fetched<boolean>, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("add_favorites_provider", { params }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async removeFavoritesProvider(params: RemoveFavoriteProviderVariables) : Promise<Result<Fetched<boolean>, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("remove_favorites_provider", { params }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+}
+}
+
+/** user-defined events **/
+
+
+
+/** user-defined constants **/
+
+
+
+/** user-defined types **/
+
+export type AddFavoriteProviderVariables = { country: Country; providerKey: string }
+/**
+ * How the response cache answered an operation.
+ */
+export type CacheStatus = 
+/**
+ * The cache wasn't used, e.g. for mutations or clients without cache.
+ */
+"bypass" | "hit" | 
+/**
+ * Served from an outdated entry, revalidated in background.
+ */
+"stale" | "miss" | 
+/**
+ * Served from the cache because the API was unreachable.
+ */
+"fallback"
+export type CollectionsCollections = { id: number; slug: string; country: Country; language: Language | null }
+export type CollectionsVariables = { country: Country; language: Language | null }
+/**
+ * The serialized shape of [`Error`] and [`UnmarkedError`], for the TypeScript bindings.
+ */
+export type CommandError = { 
+/**
+ * One of the [`popcorntime_error::Code`] strings, e.g. `errors.offline`.
+ */
+code: string; message: string }
+export type CountVariables = { country: Country }
+/**
+ * An ISO 3166-1 alpha-2 country code, e.g. `US`.
+ */
+export type Country = string
+/**
+ * A calendar date, `YYYY-MM-DD` on the wire.
+ */
+export type Date = string
+/**
+ * A point in time, RFC 3339 on the wire.
+ */
+export type DateTime = string
+export type Diagnostics = { online: boolean; 
+/**
+ * The endpoint the requests are sent to, a mirror while the primary one is down.
+ */
+endpoint: string; endpoints: EndpointStatus[]; operations: OperationSummary[]; 
+/**
+ * The recent operations with their trace id, oldest first.
+ */
+recent: OperationMetrics[] }
+/**
+ * The health of an endpoint, for diagnostics.
+ */
+export type EndpointStatus = { url: string; 
+/**
+ * Whether the requests are sent to this endpoint.
+ */
+current: boolean; consecutiveFailures: number }
+/**
+ * The value returned by a command, with where it comes from so the frontend can tell
+ * e.g. that it's showing cached data while offline.
+ */
+export type Fetched<T> = { data: T; 
+/**
+ * The errors of a partial response, e.g. of the fields the server failed to resolve.
+ */
+errors: GraphqlError[]; origin: Origin }
+export type Genre = "ACTION" | "ADVENTURE" | "ANIMATION" | "COMEDY" | "CRIME" | "DOCUMENTARY" | "DRAMA" | "FAMILY" | "FANTASY" | "HISTORY" | "HORROR" | "MUSIC" | "MYSTERY" | "ROMANCE" | "SCIENCE_FICTION" | "TV_MOVIE" | "THRILLER" | "WAR" | "WESTERN" | "OTHER"
+/**
+ * A single entry of the `errors` array returned by the GraphQL server.
+ */
+export type GraphqlError = { message: string; 
+/**
+ * Path of the field the error applies to, e.g. `["media", "charts", "0"]`.
+ */
+path?: string[]; 
+/**
+ * The `extensions.code` set by the server, if any.
+ */
+code?: string | null }
+/**
+ * Everything the home screen shows. A section is `None` if it failed to load, the
+ * reason is listed in `errors`.
+ */
+export type Home = { homeCollection: HomeCollectionHomeCollection | null; collections: CollectionsCollections[] | null; count: number | null; errors: HomeError[] }
+export type HomeCollectionHomeCollection = { id: Country; topMovies: HomeMedia[]; topSeries: HomeMedia[]; newMovies: HomeMedia[]; newTvshows: HomeMedia[] }
+export type HomeCollectionVariables = { country: Country; language: Language | null }
+/**
+ * A section which failed to load, in the `{ code, message }` shape of command errors.
+ */
+export type HomeError = { section: HomeSection; code: string; message: string }
+export type HomeMedia = { id: number; slug: string; kind: MediaKind; title: string; overview: string | null; poster: string | null; backdrop: string | null; released: Date | null; updatedAt: DateTime; providers: HomeMediaProviders[]; year: number | null }
+export type HomeMediaProviders = { providerId: string; priceTypes: WatchPriceType[] }
+/**
+ * The sections of the home screen, each fetched with its own query.
+ */
+export type HomeSection = "homeCollection" | "collections" | "count"
+/**
+ * A BCP-47 language tag whose primary language is an ISO 639 code, e.g. `en` or `pt-BR`.
+ */
+export type Language = string
+export type MediaKind = "MOVIE" | "TV_SHOW" | "UNKNOWN"
+export type MediaMedia = (({ __typename: "Movie" } & MediaMediaOnMovie) | ({ __typename: "TVShow" } & MediaMediaOnTVShow)) & { id: number; title: string; slug: string; overview: string | null; tagline: string | null; languages: Language[]; poster: string | null; backdrop: string | null; released: string | null; year: number | null; country: Country | null; tags: string[]; trailers: string[]; genres: Genre[]; classification: string | null; countries: Country[]; kind: MediaKind; videos: MediaMediaVideos[]; ratings: MediaMediaRatings[]; ranking: MediaMediaRanking | null; pochoclinReview: MediaMediaPochoclinReview | null; similars: MediaMediaSimilars[]; similarsFree: MediaMediaSimilarsFree[]; charts: MediaMediaCharts[]; availabilities: MediaMediaAvailabilities[]; talents: MediaMediaTalents[] }
+export type MediaMediaAvailabilities = { providerId: string; providerName: string; logo: string | null; availableTo: Date | null; urlHash: string; audioLanguages: Language[] | null; subtitleLanguages: Language[] | null; pricesType: WatchPriceType[] | null }
+export type MediaMediaCharts = { title: string; kind: MediaKind; slug: string; poster: string | null; year: number | null; rank: MediaMediaChartsRank | null }
+export type MediaMediaChartsRank = { position: number; change: number; points: number; previousPoints: number }
+export type MediaMediaOnMovie = { runtime: string }
+export type MediaMediaOnTVShow = { inProduction: boolean }
+export type MediaMediaPochoclinReview = { review: string; excerpt: string }
+export type MediaMediaRanking = { score: number; position: number; points: number }
+export type MediaMediaRatings = { rating: number; source: RatingSource }
+export type MediaMediaSimilars = { title: string; overview: string | null; kind: MediaKind; slug: string; poster: string | null; year: number | null }
+export type MediaMediaSimilarsFree = { title: string; overview: string | null; kind: MediaKind; slug: string; poster: string | null; year: number | null }
+export type MediaMediaTalents = { id: number; rank: number; name: string; role: string | null; roleType: RoleType }
+export type MediaMediaVideos = { source: VideoSource; videoId: string }
+export type MediaVariables = { slug: string; country: Country; language: Language | null }
+/**
+ * One operation sent with `ApiClient::query`.
+ */
+export type OperationMetrics = { operation: string; 
+/**
+ * The trace id sent in the `traceparent` header, `None` if no request was sent.
+ */
+traceId: string | null; startedAt: string; latencyMs: number; 
+/**
+ * Size of the response bodies received, 0 if served from the cache.
+ */
+responseBytes: number; cache: CacheStatus; 
+/**
+ * Number of HTTP requests sent, including retries and replays.
+ */
+requests: number; 
+/**
+ * The error code, if the operation failed.
+ */
+error: string | null }
+/**
+ * Aggregates of the recent operations with the same name.
+ */
+export type OperationSummary = { operation: string; count: number; errors: number; cacheHits: number; p50LatencyMs: number; p95LatencyMs: number; maxLatencyMs: number; responseBytes: number }
+/**
+ * Where the data of a [`GraphqlResponse`] comes from.
+ */
+export type Origin = { source: "network" } | 
+/**
+ * Served from the response cache, `stale` when it's outdated or the API is unreachable.
+ */
+{ source: "cache"; fetchedAt: string; stale: boolean } | 
+/**
+ * The mutation waits in the outbox, the data is what the server is expected to return.
+ */
+{ source: "queued" }
+export type Placeholder = { blurhash: string; 
+/**
+ * The dominant colour, as `#rrggbb`.
+ */
+color: string }
+export type PreferencesPreferences = { language: Language; country: Country }
+export type ProvidersProviders = { key: string; name: string; logo: string | null; weight: number | null; priceTypes: WatchPriceType[]; parentKey: string | null }
+export type ProvidersVariables = { country: Country; query: string | null; favorites: boolean | null }
+export type RatingSource = "IMDB" | "TMDB" | "UNKNOWN"
+export type RemoveFavoriteProviderVariables = { country: Country; providerKey: string }
+export type RoleType = "EXECUTIVE_PRODUCER" | "DIRECTOR" | "FIRST_ASSISTANT_DIRECTOR" | "SECOND_ASSISTANT_DIRECTOR" | "SECOND_SECOND_ASSISTANT_DIRECTOR" | "SCRIPT_SUPERVISOR" | "PRODUCER" | "WRITER" | "ACTOR" | "UNKNOWN"
+export type SearchArguments = { collection: number | null; kind: MediaKind | null; year: number | null; providers: string[] | null; priceTypes: WatchPriceType[] | null; genres: Genre[] | null; audio: Language | null; subtitle: Language | null; country: Country | null; withPoster: boolean | null; featured: boolean | null }
+export type SearchPage = { nodes: SearchSearchNodes[]; hasNextPage: boolean }
+export type SearchSearch = { nodes: SearchSearchNodes[]; pageInfo: SearchSearchPageInfo }
+export type SearchSearchNodes = { id: number; slug: string; kind: MediaKind; title: string; overview: string | null; poster: string | null; backdrop: string | null; released: Date | null; updatedAt: DateTime; providers: SearchSearchNodesProviders[]; year: number | null }
+export type SearchSearchNodesProviders = { providerId: string; priceTypes: WatchPriceType[] }
+export type SearchSearchPageInfo = { endCursor: string | null; hasNextPage: boolean }
+export type SearchVariables = { after: string | null; before: string | null; first: number | null; last: number | null; sortKey: SortKey | null; country: Country; language: Language | null; query: string | null; arguments: SearchArguments | null }
+export type SortKey = "ID" | "RELEASED_AT" | "CREATED_AT" | "UPDATED_AT" | "POSITION" | "UNKNOWN"
+/**
+ * A category of the files written by the app.
+ */
+export type StorageCategory = 
+/**
+ * GraphQL responses, read while offline.
+ */
+"apiCache" | 
+/**
+ * Posters and backdrops served through `ptimg://`.
+ */
+"images" | "logs" | 
+/**
+ * The https://github.com/tokio-rs/console recording.
+ */
+"consoleRecording"
+/**
+ * The max size of each category of files written by the app, in bytes.
+ */
+export type StorageLimits = { apiCache: number; images: number; logs: number; consoleRecording: number }
+export type StorageUsage = { category: StorageCategory; path: string; 
+/**
+ * In bytes.
+ */
+size: number; 
+/**
+ * The size enforced by the janitor, in bytes.
+ */
+limit: number }
+export type UpdatePreferencesUpdatePreferences = { country: Country; language: Language }
+export type UpdatePreferencesVariables = { country: Country; language: Language }
+export type VideoSource = "RUMBLE" | "YOUTUBE" | "UNKNOWN"
+export type WatchPriceType = "RENT" | "BUY" | "FLATRATE" | "FREE" | "CINEMA" | "UNKNOWN"
+
+/** tauri-specta globals **/
+
+import { invoke as TAURI_INVOKE } from "@tauri-apps/api/core";
+
+export type Result<T, E> =
+	| { status: "ok"; data: T }
+	| { status: "error"; error: E };
@@ -1,6 +1,11 @@
-import { MediaKind, MediaSearch, SortKey } from "@popcorntime/graphql/types";
+import type {
+  Placeholder,
+  SearchSearchNodes,
+} from "@popcorntime/graphql/bindings";
+import { MediaKind, SortKey } from "@popcorntime/graphql/types";
 import { PosterSkeleton, Poster } from "@popcorntime/ui/components/poster";
 import { Button } from "@popcorntime/ui/components/button";
+import { artworkPath } from "@popcorntime/ui/lib/medias";
 import {
   Popover,
   PopoverContent,
@@ -81,7 +86,9 @@ interface BrowseMediasProps {
   sentryRef: React.Ref<HTMLDivElement> | undefined;
   isLoading: boolean;
   isReady: boolean;
-  medias: MediaSearch[];
+  medias: SearchSearchNodes[];
+  /** Placeholders of the posters, keyed by `artworkPath`. */
+  placeholders?: Partial<Record<string, Placeholder>>;
   onOpen(slug: string): void;
   onLoadMore(): void;
   placeholder: string;
@@ -101,6 +108,7 @@ export function BrowseMedias({
   isLoading,
   isReady,
   medias,
+  placeholders,
   onOpen,
   placeholder,
   translations,
@@ -133,6 +141,7 @@ export function BrowseMedias({
                   <Poster
                     media={media}
                     placeholder={placeholder}
+                    artwork={placeholders?.[artworkPath(media.poster) ?? ""]}
                     translations={{
                       free: translations.free,
                       kind:
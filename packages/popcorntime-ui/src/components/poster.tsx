@@ -1,6 +1,12 @@
 import { useMemo } from "react";
 import { cn } from "@popcorntime/ui/lib/utils";
-import { MediaSearch, WatchPriceType } from "@popcorntime/graphql/types";
+import { cachedImageUrl } from "@popcorntime/ui/lib/medias";
+import { blurhashToDataUrl } from "@popcorntime/ui/lib/blurhash";
+import type {
+  Placeholder,
+  SearchSearchNodes,
+} from "@popcorntime/graphql/bindings";
+import { WatchPriceType } from "@popcorntime/graphql/types";
 
 export function PosterSkeleton() {
   return (
@@ -15,12 +21,14 @@ export function PosterSkeleton() {
 export function Poster({
   media,
   placeholder,
+  artwork,
   isAboveTheFold = false,
   translations,
 }: {
   isAboveTheFold?: boolean;
   placeholder?: string;
-  media: MediaSearch;
+  artwork?: Placeholder;
+  media: SearchSearchNodes;
   translations: {
     free: string;
     kind: string;
@@ -62,6 +70,7 @@ export function Poster({
           title={media.title}
           posterId={posterId}
           placeholder={placeholder}
+          artwork={artwork}
         />
 
         {media.overview && (
@@ -85,12 +94,15 @@ export function MediaPosterAsPicture({
   className,
   title,
   placeholder,
+  artwork,
 }: {
   posterId?: string;
   loading: "lazy" | "eager";
   className?: string;
   title: string;
   placeholder?: string;
+  /** Shown until the poster is loaded. */
+  artwork?: Placeholder;
 }) {
   if (!posterId) {
     return (
@@ -106,24 +118,18 @@ export function MediaPosterAsPicture({
   }
 
   return (
-    <>
-      <picture>
-        <source
-          srcSet={`https://img.popcorntime.app/o/${posterId}@300.webp`}
-          type="image/webp"
-        />
-        <source
-          srcSet={`https://img.popcorntime.app/o/${posterId}@300.jpg`}
-          type="image/jpeg"
-        />
-        <img
-          alt={title}
-          src={`https://img.popcorntime.app/o/${posterId}@300.jpg`}
-          className={cn("w-full bg-cover", className)}
-          loading={loading}
-          fetchPriority={loading === "eager" ? "high" : undefined}
-        />
-      </picture>
-    </>
+    <img
+      alt={title}
+      src={cachedImageUrl(`o/${posterId}.jpg`, 300)}
+      className={cn("w-full bg-cover", className)}
+      style={
+        artwork && {
+          backgroundColor: artwork.color,
+          backgroundImage: `url(${blurhashToDataUrl(artwork.blurhash)})`,
+        }
+      }
+      loading={loading}
+      fetchPriority={loading === "eager" ? "high" : undefined}
+    />
   );
 }
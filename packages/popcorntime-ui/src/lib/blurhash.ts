@@ -0,0 +1,92 @@
+const CHARACTERS =
+  "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz#$%*+,-.:;=?@[]^_{|}~";
+
+const dataUrls = new Map<string, string>();
+
+function decode83(value: string) {
+  let result = 0;
+  for (const char of value) {
+    result = result * 83 + CHARACTERS.indexOf(char);
+  }
+  return result;
+}
+
+function srgbToLinear(value: number) {
+  const v = value / 255;
+  return v <= 0.04045 ? v / 12.92 : ((v + 0.055) / 1.055) ** 2.4;
+}
+
+function linearToSrgb(value: number) {
+  const v = Math.max(0, Math.min(1, value));
+  return Math.round(
+    v <= 0.0031308 ? v * 12.92 * 255 : (1.055 * v ** (1 / 2.4) - 0.055) * 255
+  );
+}
+
+function signPow(value: number, exp: number) {
+  return Math.sign(value) * Math.abs(value) ** exp;
+}
+
+/** Decode `blurhash` into RGBA pixels, see https://blurha.sh. */
+export function decodeBlurhash(blurhash: string, width: number, height: number) {
+  const sizeFlag = decode83(blurhash[0] ?? "");
+  const numY = Math.floor(sizeFlag / 9) + 1;
+  const numX = (sizeFlag % 9) + 1;
+  const maxValue = (decode83(blurhash[1] ?? "") + 1) / 166;
+
+  const dc = decode83(blurhash.slice(2, 6));
+  const colors = [
+    [srgbToLinear(dc >> 16), srgbToLinear((dc >> 8) & 255), srgbToLinear(dc & 255)],
+  ];
+  for (let i = 1; i < numX * numY; i++) {
+    const ac = decode83(blurhash.slice(4 + i * 2, 6 + i * 2));
+    colors.push(
+      [Math.floor(ac / 361), Math.floor(ac / 19) % 19, ac % 19].map(
+        (quantized) => signPow((quantized - 9) / 9, 2) * maxValue
+      )
+    );
+  }
+
+  const pixels = new Uint8ClampedArray(width * height * 4);
+  for (let y = 0; y < height; y++) {
+    for (let x = 0; x < width; x++) {
+      let r = 0;
+      let g = 0;
+      let b = 0;
+      for (let j = 0; j < numY; j++) {
+        for (let i = 0; i < numX; i++) {
+          const basis =
+            Math.cos((Math.PI * x * i) / width) * Math.cos((Math.PI * y * j) / height);
+          const color = colors[i + j * numX] ?? [0, 0, 0];
+          r += (color[0] ?? 0) * basis;
+          g += (color[1] ?? 0) * basis;
+          b += (color[2] ?? 0) * basis;
+        }
+      }
+      const offset = 4 * (x + y * width);
+      pixels[offset] = linearToSrgb(r);
+      pixels[offset + 1] = linearToSrgb(g);
+      pixels[offset + 2] = linearToSrgb(b);
+      pixels[offset + 3] = 255;
+    }
+  }
+  return pixels;
+}
+
+/** A small PNG of `blurhash`, stretched by the browser as a background. */
+export function blurhashToDataUrl(blurhash: string) {
+  const cached = dataUrls.get(blurhash);
+  if (cached) return cached;
+
+  const size = 32;
+  const canvas = document.createElement("canvas");
+  canvas.width = size;
+  canvas.height = size;
+  const context = canvas.getContext("2d");
+  if (!context) return undefined;
+  context.putImageData(new ImageData(decodeBlurhash(blurhash, size, size), size, size), 0, 0);
+
+  const dataUrl = canvas.toDataURL();
+  dataUrls.set(blurhash, dataUrl);
+  return dataUrl;
+}
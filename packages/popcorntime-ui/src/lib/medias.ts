@@ -5,6 +5,7 @@ import {
   SortKey,
   WatchPriceType,
 } from "@popcorntime/graphql/types";
+import { convertFileSrc } from "@tauri-apps/api/core";
 
 export type Filters = {
   kind: Array<MediaKind>;
@@ -104,3 +105,19 @@ export function buildMediaQuery(
     } as SearchArguments,
   };
 }
+
+/**
+ * Path of a poster or backdrop on `img.popcorntime.app`, `/ID.jpg` is the original.
+ */
+export function artworkPath(url?: string | null) {
+  const id = url?.match(/\/([^/.]+)\./)?.[1];
+  return id ? `o/${id}.jpg` : undefined;
+}
+
+/**
+ * Url of an image of `img.popcorntime.app` (e.g. `o/ID.jpg`), served from the image
+ * cache of the app, `width` wide.
+ */
+export function cachedImageUrl(path: string, width?: number) {
+  return `${convertFileSrc(path, "ptimg")}${width ? `?w=${width}` : ""}`;
+}